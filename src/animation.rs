@@ -1,6 +1,124 @@
 //! Animation of tilemaps
 
+use bevy::prelude::*;
+
 /// Trait for types that update meshes generated from tilemap [`Chunk`](super::tilemap::Chunk)s
 ///
 /// Only used for animated tilemaps
 pub trait MeshUpdater {}
+
+/// The Bevy plugin keeping [`SharedAnimation`] up to date
+///
+/// Needed for [`TileAnimation`]s to play
+#[derive(Debug, Default)]
+pub struct SharedAnimationPlugin;
+
+impl Plugin for SharedAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SharedAnimation>()
+            .add_system_to_stage(CoreStage::PreUpdate, shared_animation_system);
+    }
+}
+
+/// A clock shared by every animated tile
+///
+/// Tiles compute their current frame from this and a phase instead of storing their own
+/// animation state, so tiles with the same animation and phase are always in sync.
+/// Updated every frame by [`SharedAnimationPlugin`], before [`CoreStage::Update`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SharedAnimation {
+    elapsed: f64,
+}
+
+impl SharedAnimation {
+    /// Creates a clock at `elapsed` seconds
+    #[must_use]
+    pub fn new(elapsed: f64) -> Self {
+        SharedAnimation { elapsed }
+    }
+
+    /// The time in seconds since this started
+    #[must_use]
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Returns the frame an animation of `frame_count` frames, each shown for
+    /// `frame_duration` seconds, is on
+    ///
+    /// `phase` is the number of frames the animation is ahead of the clock; tiles with
+    /// phases differing by a whole number of frames show frames that far apart.  Loops
+    /// forever, so the result is always less than `frame_count`.  If `frame_duration` isn't
+    /// positive and finite, the animation is paused on the frame `phase` selects
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_count` is 0
+    #[must_use]
+    pub fn frame(&self, frame_count: usize, frame_duration: f32, phase: f32) -> usize {
+        assert_ne!(frame_count, 0, "animations must have at least 1 frame");
+        let elapsed_frames = if frame_duration > 0.0 && frame_duration.is_finite() {
+            self.elapsed / frame_duration as f64
+        } else {
+            0.0
+        };
+        let frame = (elapsed_frames + phase as f64).floor() as i64;
+        frame.rem_euclid(frame_count as i64) as usize
+    }
+}
+
+/// An animation played by a tile, by offsetting the UVs of it's vertices
+///
+/// The mesh of the tile shows frame 0, and each following frame is `frame_step` further
+/// along the texture, usually the size of a frame in a texture atlas.  Played by
+/// [`TilemapPlugin`](crate::TilemapPlugin) from the [`SharedAnimation`] clock, without
+/// regenerating chunk meshes.  See [`Tile::animation`](crate::tile::Tile::animation)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileAnimation {
+    /// The number of frames in the animation
+    pub frame_count: usize,
+    /// The number of seconds each frame is shown for
+    pub frame_duration: f32,
+    /// The number of frames this is ahead of the clock
+    pub phase: f32,
+    /// The UV offset between consecutive frames
+    pub frame_step: Vec2,
+}
+
+impl TileAnimation {
+    /// Returns the frame this is on at the time of `clock`
+    ///
+    /// See [`SharedAnimation::frame()`]
+    #[must_use]
+    pub fn frame(&self, clock: &SharedAnimation) -> usize {
+        clock.frame(self.frame_count, self.frame_duration, self.phase)
+    }
+}
+
+fn shared_animation_system(time: Res<Time>, mut animation: ResMut<SharedAnimation>) {
+    animation.elapsed = time.seconds_since_startup();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_phases_show_the_same_frame() {
+        let clock = SharedAnimation::new(2.5);
+        assert_eq!(clock.frame(4, 0.5, 0.0), 1);
+        assert_eq!(clock.frame(4, 0.5, 0.0), clock.frame(4, 0.5, 0.0));
+        assert_eq!(clock.frame(4, 0.5, 1.0), 2);
+        assert_eq!(clock.frame(4, 0.5, 3.0), 0);
+        assert_eq!(clock.frame(4, 0.5, -1.0), 0);
+    }
+
+    #[test]
+    fn invalid_frame_durations_pause_on_the_phase() {
+        let clock = SharedAnimation::new(7.0);
+        assert_eq!(clock.frame(4, 0.0, 2.0), 2);
+        assert_eq!(clock.frame(4, -1.0, 1.0), 1);
+        assert_eq!(clock.frame(4, f32::NAN, 5.0), 1);
+        assert_eq!(clock.frame(4, f32::INFINITY, 0.0), 0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Multi-tile brushes for stamping patterns into tilemaps
+
+use std::mem;
+
+use bevy::prelude::*;
+
+use crate::{
+    tile::Tile,
+    tilemap::{on_chunk_edge, Tilemap, TilemapPos},
+};
+
+/// A small rectangular grid of tile slots that can be stamped into a [`Tilemap`]
+///
+/// Positions in a brush start at (0, 0) in the bottom left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brush<T: Tile> {
+    size: UVec2,
+    /// Row-major, starting from the bottom left corner
+    tiles: Vec<Option<T>>,
+}
+
+impl<T: Tile> Brush<T> {
+    /// Creates an empty brush with the given width and height
+    #[must_use]
+    pub fn new(width: u32, height: u32) -> Self {
+        Brush {
+            size: UVec2::new(width, height),
+            tiles: vec![None; (width * height) as usize],
+        }
+    }
+
+    /// The width and height of this
+    #[must_use]
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Returns a reference to the tile slot at `pos`, or [`None`] if `pos` is outside of this
+    #[must_use]
+    pub fn get(&self, pos: UVec2) -> Option<&Option<T>> {
+        self.index(pos).map(|index| &self.tiles[index])
+    }
+
+    /// Sets the tile slot at `pos`, returning it's previous value
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` is outside of this
+    pub fn set(&mut self, pos: UVec2, tile: Option<T>) -> Option<T> {
+        let index = self.index(pos).expect("position outside of brush");
+        mem::replace(&mut self.tiles[index], tile)
+    }
+
+    /// Returns an iterator over all tile slots in this and their positions
+    ///
+    /// Iterates in row-major order
+    pub fn iter_positions(&self) -> impl Iterator<Item = (UVec2, &Option<T>)> {
+        let width = self.size.x;
+        self.tiles.iter().enumerate().map(move |(index, tile)| {
+            let index = index as u32;
+            (UVec2::new(index % width, index / width), tile)
+        })
+    }
+
+    /// Returns a copy of this rotated clockwise by 90 degrees `n` times
+    #[must_use]
+    pub fn rotated(&self, n: u8) -> Self {
+        let mut brush = self.clone();
+        for _ in 0..n % 4 {
+            let mut rotated = Brush::new(brush.size.y, brush.size.x);
+            for (pos, tile) in brush.iter_positions() {
+                rotated.set(UVec2::new(pos.y, brush.size.x - 1 - pos.x), tile.clone());
+            }
+            brush = rotated;
+        }
+        brush
+    }
+
+    /// Returns a copy of this mirrored horizontally
+    #[must_use]
+    pub fn flipped(&self) -> Self {
+        let mut flipped = Brush::new(self.size.x, self.size.y);
+        for (pos, tile) in self.iter_positions() {
+            flipped.set(UVec2::new(self.size.x - 1 - pos.x, pos.y), tile.clone());
+        }
+        flipped
+    }
+
+    fn index(&self, pos: UVec2) -> Option<usize> {
+        (pos.x < self.size.x && pos.y < self.size.y).then(|| (pos.x + pos.y * self.size.x) as usize)
+    }
+}
+
+impl<T: Tile, M> Tilemap<T, M> {
+    /// Writes every set tile in `brush` to this, with the bottom left corner of the brush at `at`
+    ///
+    /// Empty slots in the brush leave this unchanged.  If `overwrite` is `false`, tiles
+    /// are only written to empty positions.  Positions outside of the
+    /// [bounds](Self::bounds()) of this are skipped.  Tells every chunk a tile is written to,
+    /// and chunks sharing an edge with a written tile, to regenerate their meshes the next
+    /// time they're displayed
+    pub fn stamp(&mut self, at: TilemapPos, brush: &Brush<T>, overwrite: bool) {
+        let origin = IVec2::from(at);
+        let mut edges = Vec::new();
+        for (pos, tile) in brush.iter_positions() {
+            if let Some(tile) = tile {
+                let pos = TilemapPos::from(origin + pos.as_ivec2());
+                if !self.in_bounds(pos) {
+                    continue;
+                }
+                let chunk = self.get_or_create_chunk(pos.chunk);
+                if overwrite || !chunk.is_set(pos.tile) {
+                    chunk.set(pos.tile, tile.clone());
+                    if on_chunk_edge(pos.tile) {
+                        edges.push(pos);
+                    }
+                }
+            }
+        }
+        self.regenerate_neighbor_chunks(edges);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, tilemap::IRect};
+
+    /// A 2 by 1 brush with tile 1 on the left and tile 2 on the right
+    fn pair() -> Brush<TestTile> {
+        let mut brush = Brush::new(2, 1);
+        brush.set(UVec2::new(0, 0), Some(TestTile(1)));
+        brush.set(UVec2::new(1, 0), Some(TestTile(2)));
+        brush
+    }
+
+    #[test]
+    fn rotation_and_flipping() {
+        let rotated = pair().rotated(1);
+        assert_eq!(rotated.size(), UVec2::new(1, 2));
+        assert_eq!(rotated.get(UVec2::new(0, 1)), Some(&Some(TestTile(1))));
+        assert_eq!(rotated.get(UVec2::new(0, 0)), Some(&Some(TestTile(2))));
+        assert_eq!(pair().rotated(4), pair());
+
+        let flipped = pair().flipped();
+        assert_eq!(flipped.get(UVec2::new(0, 0)), Some(&Some(TestTile(2))));
+        assert_eq!(flipped.get(UVec2::new(2, 0)), None);
+    }
+
+    #[test]
+    fn rotated_stamp_spans_chunks() {
+        // Tiles 1 to 6 in row-major order, 2 wide and 3 tall
+        let mut brush = Brush::new(2, 3);
+        for (id, pos) in (1..).zip(IRect::new(IVec2::ZERO, IVec2::new(1, 2)).iter_positions()) {
+            brush.set(pos.as_uvec2(), Some(TestTile(id)));
+        }
+        let brush = brush.rotated(1);
+        assert_eq!(brush.size(), UVec2::new(3, 2));
+
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(32, 0), TestTile(9));
+        tilemap.stamp(pos(31, -1), &brush, false);
+        let row = |tilemap: &Tilemap<TestTile>, y| {
+            (31..34)
+                .map(|x| tilemap.get(pos(x, y)).map(|tile| tile.0))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(row(&tilemap, -1), [Some(2), Some(4), Some(6)]);
+        assert_eq!(row(&tilemap, 0), [Some(1), Some(9), Some(5)]);
+        assert_eq!(tilemap.chunk_positions().count(), 4);
+
+        tilemap.stamp(pos(31, -1), &brush, true);
+        assert_eq!(row(&tilemap, 0), [Some(1), Some(3), Some(5)]);
+    }
+}
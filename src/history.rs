@@ -0,0 +1,266 @@
+//! Undo/redo of edits to tilemaps
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    tile::Tile,
+    tilemap::{on_chunk_edge, ChunkPos, Tilemap, TilemapPos},
+};
+
+/// The changes an edit made to a single chunk
+#[derive(Debug, Clone)]
+pub struct ChunkDiff<T: Tile> {
+    /// The position of the chunk the changes were made to
+    pub chunk: IVec2,
+    /// Each changed tile slot, with it's value before and after the change
+    ///
+    /// Stored in the order the changes were made
+    pub changes: Vec<(ChunkPos, Option<T>, Option<T>)>,
+}
+
+impl<T: Tile> ChunkDiff<T> {
+    /// Creates an empty diff for the chunk at `chunk`
+    #[must_use]
+    pub fn new(chunk: IVec2) -> Self {
+        ChunkDiff {
+            chunk,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Returns a diff that undoes this one
+    #[must_use]
+    pub fn inverse(&self) -> Self {
+        ChunkDiff {
+            chunk: self.chunk,
+            changes: self
+                .changes
+                .iter()
+                .rev()
+                .map(|(pos, before, after)| (*pos, after.clone(), before.clone()))
+                .collect(),
+        }
+    }
+
+    /// Writes the new value of every change in this to `tilemap`
+    ///
+    /// Tells the chunk, and chunks sharing an edge with a changed tile, to regenerate their
+    /// meshes the next time they're displayed
+    pub fn apply<M>(&self, tilemap: &mut Tilemap<T, M>) {
+        let chunk = tilemap.get_or_create_chunk(self.chunk);
+        for (pos, _, after) in &self.changes {
+            chunk.set_raw(*pos, after.clone());
+        }
+        chunk.regenerate_mesh();
+        tilemap.regenerate_neighbor_chunks(self.changes.iter().map(|(tile, ..)| TilemapPos {
+            chunk: self.chunk,
+            tile: *tile,
+        }));
+    }
+}
+
+/// A history of edits made to a [`Tilemap`] that can be undone and redone
+///
+/// Edits are recorded with [`Tilemap::apply_edit`].  Once the history holds
+/// more than [`max_depth`](Self::max_depth) edits, the oldest are forgotten
+#[derive(Debug)]
+pub struct EditHistory<T: Tile> {
+    undo: VecDeque<Vec<ChunkDiff<T>>>,
+    redo: Vec<Vec<ChunkDiff<T>>>,
+    max_depth: usize,
+}
+
+impl<T: Tile> EditHistory<T> {
+    /// Creates an empty history remembering at most `max_depth` edits
+    #[must_use]
+    pub fn new(max_depth: usize) -> Self {
+        EditHistory {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_depth,
+        }
+    }
+
+    /// The maximum number of edits this remembers
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Sets the maximum number of edits this remembers, forgetting the oldest
+    /// edits if there are more than `max_depth`
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+        while self.undo.len() > max_depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Returns `true` if there is an edit that can be undone
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns `true` if there is an edit that can be redone
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Forgets all edits in this
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Records an edit made up of the given chunk diffs
+    ///
+    /// Forgets all edits that could be redone
+    pub fn push(&mut self, edit: Vec<ChunkDiff<T>>) {
+        self.redo.clear();
+        if self.max_depth == 0 {
+            return;
+        }
+        if self.undo.len() == self.max_depth {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+    }
+
+    /// Undoes the most recent edit to `tilemap`, returning `false` if there was
+    /// nothing to undo
+    ///
+    /// Tells every chunk the edit touched, and chunks sharing an edge with a changed tile, to
+    /// regenerate their meshes
+    pub fn undo<M>(&mut self, tilemap: &mut Tilemap<T, M>) -> bool {
+        match self.undo.pop_back() {
+            Some(edit) => {
+                for diff in edit.iter().rev() {
+                    diff.inverse().apply(tilemap);
+                }
+                self.redo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redoes the most recently undone edit to `tilemap`, returning `false` if
+    /// there was nothing to redo
+    ///
+    /// Tells every chunk the edit touched, and chunks sharing an edge with a changed tile, to
+    /// regenerate their meshes
+    pub fn redo<M>(&mut self, tilemap: &mut Tilemap<T, M>) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                for diff in &edit {
+                    diff.apply(tilemap);
+                }
+                self.undo.push_back(edit);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<T: Tile> Default for EditHistory<T> {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl<T: Tile, M> Tilemap<T, M> {
+    /// Writes each tile slot in `edit` and records the changes in `history`
+    /// as a single edit that can be undone
+    ///
+    /// Slots outside of the [bounds](Self::bounds()) of this are skipped, and not recorded.
+    /// Tells every chunk the edit touches, and chunks sharing an edge with a changed tile, to
+    /// regenerate their meshes
+    pub fn apply_edit(
+        &mut self,
+        history: &mut EditHistory<T>,
+        edit: impl IntoIterator<Item = (TilemapPos, Option<T>)>,
+    ) {
+        let mut diffs: HashMap<IVec2, ChunkDiff<T>> = HashMap::default();
+        let mut edges = Vec::new();
+        for (pos, tile) in edit {
+            if !self.in_bounds(pos) {
+                continue;
+            }
+            let chunk = self.get_or_create_chunk(pos.chunk);
+            chunk.regenerate_mesh();
+            let before = chunk.set_raw(pos.tile, tile.clone());
+            if on_chunk_edge(pos.tile) {
+                edges.push(pos);
+            }
+            diffs
+                .entry(pos.chunk)
+                .or_insert_with(|| ChunkDiff::new(pos.chunk))
+                .changes
+                .push((pos.tile, before, tile));
+        }
+        self.regenerate_neighbor_chunks(edges);
+        if !diffs.is_empty() {
+            history.push(diffs.into_values().collect());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::*, tilemap::IRect};
+
+    #[test]
+    fn undo_and_redo_restore_tiles() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        let mut history = EditHistory::default();
+        tilemap.set(pos(0, 0), TestTile(1));
+        tilemap.apply_edit(
+            &mut history,
+            [
+                (pos(0, 0), Some(TestTile(2))),
+                (pos(40, 0), Some(TestTile(3))),
+                (pos(0, 0), None),
+            ],
+        );
+        assert_eq!(tilemap.get(pos(0, 0)), None);
+
+        assert!(history.undo(&mut tilemap));
+        assert_eq!(tilemap.get(pos(0, 0)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(pos(40, 0)), None);
+        assert!(!history.undo(&mut tilemap));
+
+        assert!(history.redo(&mut tilemap));
+        assert_eq!(tilemap.get(pos(0, 0)), None);
+        assert_eq!(tilemap.get(pos(40, 0)), Some(&TestTile(3)));
+        assert!(!history.redo(&mut tilemap));
+    }
+
+    #[test]
+    fn max_depth_forgets_oldest_edits() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        let mut history = EditHistory::new(2);
+        for id in 0..3 {
+            tilemap.apply_edit(&mut history, [(pos(0, 0), Some(TestTile(id)))]);
+        }
+        assert!(history.undo(&mut tilemap));
+        assert!(history.undo(&mut tilemap));
+        assert!(!history.undo(&mut tilemap));
+        assert_eq!(tilemap.get(pos(0, 0)), Some(&TestTile(0)));
+    }
+
+    #[test]
+    fn out_of_bounds_edits_are_not_recorded() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set_bounds(Some(IRect::new(IVec2::ZERO, IVec2::splat(3))));
+        let mut history = EditHistory::default();
+        tilemap.apply_edit(&mut history, [(pos(10, 10), Some(TestTile(1)))]);
+        assert!(!history.can_undo());
+        assert_eq!(tilemap.chunk_positions().count(), 0);
+    }
+}
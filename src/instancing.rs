@@ -0,0 +1,804 @@
+//! Instanced rendering of tilemaps where every tile is a single textured square
+//!
+//! Instead of building a mesh with 4 vertices per tile, each chunk stores one
+//! [`TileInstance`] per tile, and every chunk is drawn as instances of a shared unit quad
+//! sampling from a [`TileAtlas`]
+
+use std::{marker::PhantomData, mem};
+
+use bevy::{
+    core::{cast_slice, Pod, Zeroable},
+    core_pipeline::core_2d::Transparent2d,
+    ecs::system::{lifetimeless::*, SystemParamItem},
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        mesh::{GpuBufferInfo, Indices, MeshVertexBufferLayout, PrimitiveTopology},
+        primitives::Aabb,
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, EntityRenderCommand, RenderCommandResult, RenderPhase,
+            SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        texture::BevyDefault,
+        view::VisibleEntities,
+        Extract, RenderApp, RenderStage,
+    },
+    sprite::{
+        Mesh2dHandle, Mesh2dPipeline, Mesh2dPipelineKey, Mesh2dUniform, SetMesh2dBindGroup,
+        SetMesh2dViewBindGroup,
+    },
+    utils::{FloatOrd, HashMap, HashSet},
+};
+
+use crate::{
+    chunk_transform, chunks_to_regenerate, set_aabb, spawn_tilemap_system,
+    tile::Tile,
+    tile_hooks_system,
+    tilemap::{DirtyTiles, Tilemap},
+    tiles_by_z_offset, RenderLabel, CHUNK_SIZE,
+};
+
+/// One tile drawn by the instanced renderer
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct TileInstance {
+    /// Offset of the bottom left corner of the tile from the chunk origin
+    pub offset: [f32; 2],
+    /// Index of the cell in the [`TileAtlas`] drawn on the tile, row-major from the top left
+    pub uv_index: u32,
+}
+
+// SAFETY: `TileInstance` is `repr(C)`, contains only `Pod` fields, and has no padding
+unsafe impl Zeroable for TileInstance {}
+unsafe impl Pod for TileInstance {}
+
+/// Collects the [`TileInstance`]s of a chunk, the instanced counterpart to
+/// [`MeshBuilder`](crate::rendering::MeshBuilder)
+#[derive(Debug, Default)]
+pub struct InstanceBuilder {
+    offset: Vec2,
+    instances: Vec<TileInstance>,
+}
+
+impl InstanceBuilder {
+    /// Creates a new builder with room for a full chunk of instances
+    #[must_use]
+    pub fn new() -> Self {
+        InstanceBuilder {
+            offset: Vec2::ZERO,
+            instances: Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE),
+        }
+    }
+
+    /// Sets the offset at which to add the next few instances
+    pub fn set_offset(&mut self, offset: Vec2) {
+        self.offset = offset;
+    }
+
+    /// Adds an instance drawing the atlas cell `uv_index` at the current offset
+    pub fn push(&mut self, uv_index: u32) {
+        self.instances.push(TileInstance {
+            offset: self.offset.to_array(),
+            uv_index,
+        });
+    }
+
+    /// The number of instances added so far
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Returns `true` if no instances have been added
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Finishes instance generation, returning the instances
+    #[must_use]
+    pub fn finish(self) -> Vec<TileInstance> {
+        self.instances
+    }
+}
+
+/// A [`Tile`] that can be drawn by the instanced renderer
+pub trait InstancedTile: Tile {
+    /// Adds the instances for this tile to the builder
+    ///
+    /// Usually a single call to [`InstanceBuilder::push`]
+    fn add_instances(&self, builder: &mut InstanceBuilder);
+}
+
+/// The texture atlas instanced tiles of type `T` are drawn from
+///
+/// Must be inserted as a resource before chunks are drawn
+#[derive(Debug, Clone)]
+pub struct TileAtlas<T: InstancedTile> {
+    /// The atlas texture
+    pub texture: Handle<Image>,
+    /// The number of columns and rows of cells in the atlas
+    pub size: UVec2,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: InstancedTile> TileAtlas<T> {
+    /// Creates a new atlas with `size` columns and rows of cells
+    #[must_use]
+    pub fn new(texture: Handle<Image>, size: UVec2) -> Self {
+        TileAtlas {
+            texture,
+            size,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// The instances of a chunk and the atlas they are drawn from
+///
+/// Only copied to the render world and uploaded to the GPU when changed
+#[derive(Debug, Clone, Component)]
+pub struct ChunkInstances {
+    /// One instance per tile in the chunk
+    pub instances: Vec<TileInstance>,
+    /// The atlas texture
+    pub atlas: Handle<Image>,
+    /// The number of columns and rows of cells in the atlas
+    pub atlas_size: UVec2,
+}
+
+/// Plugin that draws a [`Tilemap`] with the instanced renderer instead of chunk meshes
+///
+/// Use instead of [`TilemapPlugin`](crate::TilemapPlugin), and insert a [`TileAtlas`]
+/// for the tile type
+#[derive(Debug)]
+pub struct InstancedTilemapPlugin<T: InstancedTile, M = ()> {
+    /// The maximum number of chunks regenerated each frame
+    ///
+    /// See [`TilemapPlugin::max_regens_per_frame`](crate::TilemapPlugin::max_regens_per_frame)
+    pub max_regens_per_frame: usize,
+    _phantom: PhantomData<Tilemap<T, M>>,
+}
+
+impl<T: InstancedTile> InstancedTilemapPlugin<T> {
+    /// Creates a new plugin for the given tilemap
+    ///
+    /// For a tilemap with [metadata](Tilemap::with_metadata()) of type `M`, use
+    /// `InstancedTilemapPlugin::<T, M>::default()` instead
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: InstancedTile, M> InstancedTilemapPlugin<T, M> {
+    /// Sets the maximum number of chunks regenerated each frame
+    #[must_use]
+    pub fn with_max_regens_per_frame(mut self, max_regens_per_frame: usize) -> Self {
+        self.max_regens_per_frame = max_regens_per_frame;
+        self
+    }
+}
+
+impl<T: InstancedTile, M> Default for InstancedTilemapPlugin<T, M> {
+    fn default() -> Self {
+        InstancedTilemapPlugin {
+            max_regens_per_frame: usize::MAX,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: InstancedTile, M: Send + Sync + 'static> Plugin for InstancedTilemapPlugin<T, M> {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_tilemap_system::<T, M>(self.max_regens_per_frame))
+            .add_system_set(
+                SystemSet::new()
+                    .label(RenderLabel::MeshGeneration)
+                    .with_system(generate_instances_system::<T, M>),
+            )
+            .add_system(tile_hooks_system::<T, M>.before(RenderLabel::MeshGeneration));
+
+        // Shared by every instanced tilemap, so only set up once
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => return,
+        };
+        if render_app.world.contains_resource::<TileInstancePipeline>() {
+            return;
+        }
+        render_app
+            .add_render_command::<Transparent2d, DrawTileInstances>()
+            .init_resource::<TileInstancePipeline>()
+            .init_resource::<SpecializedMeshPipelines<TileInstancePipeline>>()
+            .init_resource::<TileInstanceBuffers>()
+            .add_system_to_stage(RenderStage::Extract, extract_tile_instances_system)
+            .add_system_to_stage(RenderStage::Prepare, prepare_tile_instances_system)
+            .add_system_to_stage(RenderStage::Queue, queue_tile_instances_system);
+
+        app.world.resource_mut::<Assets<Shader>>().set_untracked(
+            TILE_INSTANCE_SHADER_HANDLE,
+            Shader::from_wgsl(TILE_INSTANCE_SHADER),
+        );
+        app.world
+            .resource_mut::<Assets<Mesh>>()
+            .set_untracked(UNIT_QUAD_HANDLE, unit_quad());
+    }
+}
+
+fn generate_instances_system<T: InstancedTile, M: Send + Sync + 'static>(
+    mut commands: Commands,
+    mut tilemap: ResMut<Tilemap<T, M>>,
+    mut instance_query: Query<(&mut ChunkInstances, &mut Transform)>,
+    atlas: Option<Res<TileAtlas<T>>>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+    time: Res<Time>,
+) {
+    let atlas = match atlas {
+        Some(atlas) => atlas,
+        None => return,
+    };
+    let tilemap = &mut *tilemap;
+    let layout = tilemap.layout();
+    let z = tilemap.z();
+
+    for (chunk_pos, chunk) in tilemap.data.iter_mut() {
+        if chunk.update_transform {
+            chunk.update_transform = false;
+            if let Some((_, mut transform)) = chunk
+                .mesh_entity
+                .and_then(|entity| instance_query.get_mut(entity).ok())
+            {
+                *transform = chunk_transform(layout, z, *chunk_pos, chunk.visual_offset());
+            }
+        }
+    }
+
+    let now = time.time_since_startup();
+    let to_regenerate = chunks_to_regenerate(tilemap, camera_query.iter().next(), now);
+    tilemap.regen_stats.record_frame(to_regenerate.len());
+    for chunk_pos in to_regenerate {
+        let chunk = match tilemap.data.get_mut(&chunk_pos) {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+        chunk.regenerate_mesh = false;
+        chunk.last_regen = Some(now);
+        chunk.dirty_tiles = DirtyTiles::Clean;
+
+        let mut builder = InstanceBuilder::new();
+        let tiles = tiles_by_z_offset(chunk);
+        let found_tile = !tiles.is_empty();
+        for (tile_pos, tile) in tiles {
+            builder.set_offset(layout.tile_offset(tile_pos.as_ivec2()));
+            tile.add_instances(&mut builder);
+        }
+
+        if found_tile {
+            let new_instances = ChunkInstances {
+                instances: builder.finish(),
+                atlas: atlas.texture.clone(),
+                atlas_size: atlas.size,
+            };
+            let aabb = instance_bounds(&new_instances.instances);
+            chunk.set_mesh_aabb(aabb.as_ref());
+            if let Some((mut instances, _)) = chunk
+                .mesh_entity
+                .and_then(|entity| instance_query.get_mut(entity).ok())
+            {
+                *instances = new_instances;
+            } else {
+                chunk.mesh_entity = Some(
+                    commands
+                        .spawn_bundle((
+                            new_instances,
+                            Mesh2dHandle(UNIT_QUAD_HANDLE.typed()),
+                            chunk_transform(layout, z, chunk_pos, chunk.visual_offset()),
+                            GlobalTransform::default(),
+                            Visibility::default(),
+                            ComputedVisibility::default(),
+                        ))
+                        .id(),
+                );
+            }
+            // The unit quad's bounds don't cover the chunk
+            set_aabb(&mut commands, chunk.mesh_entity.unwrap(), aabb);
+        } else {
+            if let Some(entity) = chunk.mesh_entity {
+                commands.entity(entity).despawn();
+            }
+            tilemap.recycle_chunk(chunk_pos);
+        }
+    }
+}
+
+/// The area covered by `instances`, each a unit square, or [`None`] if there are none
+fn instance_bounds(instances: &[TileInstance]) -> Option<Aabb> {
+    let (min, max) = instances
+        .iter()
+        .map(|instance| Vec2::from(instance.offset))
+        .fold(None, |bounds: Option<(Vec2, Vec2)>, offset| match bounds {
+            Some((min, max)) => Some((min.min(offset), max.max(offset))),
+            None => Some((offset, offset)),
+        })?;
+    Some(Aabb::from_min_max(
+        min.extend(0.0),
+        (max + Vec2::ONE).extend(0.0),
+    ))
+}
+
+/// Handle to the shader used to draw tile instances
+const TILE_INSTANCE_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 7_190_454_210_843_816_331);
+
+/// Handle to the quad every tile instance is drawn with
+const UNIT_QUAD_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Mesh::TYPE_UUID, 7_190_454_210_843_816_332);
+
+/// A 1x1 square with it's bottom left corner at the origin
+fn unit_quad() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ],
+    );
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+    );
+    mesh.set_indices(Some(Indices::U16(vec![0, 1, 2, 0, 2, 3])));
+    mesh
+}
+
+const TILE_INSTANCE_SHADER: &str = r"
+#import bevy_sprite::mesh2d_types
+#import bevy_sprite::mesh2d_view_bindings
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh2d;
+
+@group(2) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var atlas_sampler: sampler;
+@group(2) @binding(2)
+var<uniform> atlas_size: vec4<u32>;
+
+#import bevy_sprite::mesh2d_functions
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) offset: vec2<f32>,
+    @location(3) uv_index: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let position = vec4<f32>(vertex.position.xy + vertex.offset, vertex.position.z, 1.0);
+    out.clip_position = mesh2d_position_local_to_clip(mesh.model, position);
+    let cell = vec2<u32>(vertex.uv_index % atlas_size.x, vertex.uv_index / atlas_size.x);
+    out.uv = (vec2<f32>(cell) + vertex.uv) / vec2<f32>(atlas_size.xy);
+    return out;
+}
+
+struct FragmentInput {
+    @location(0) uv: vec2<f32>,
+};
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    return textureSample(atlas_texture, atlas_sampler, in.uv);
+}
+";
+
+/// Render pipeline for tile instances
+struct TileInstancePipeline {
+    mesh2d_pipeline: Mesh2dPipeline,
+    atlas_layout: BindGroupLayout,
+}
+
+impl FromWorld for TileInstancePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let atlas_layout =
+            world
+                .resource::<RenderDevice>()
+                .create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("tile_atlas_layout"),
+                    entries: &[
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Texture {
+                                multisampled: false,
+                                sample_type: TextureSampleType::Float { filterable: true },
+                                view_dimension: TextureViewDimension::D2,
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: ShaderStages::VERTEX,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: BufferSize::new(16),
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        TileInstancePipeline {
+            mesh2d_pipeline: Mesh2dPipeline::from_world(world),
+            atlas_layout,
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for TileInstancePipeline {
+    type Key = Mesh2dPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+        ])?;
+        let instance_layout = VertexBufferLayout {
+            array_stride: mem::size_of::<TileInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: 0,
+                    shader_location: 2,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Uint32,
+                    offset: VertexFormat::Float32x2.size(),
+                    shader_location: 3,
+                },
+            ],
+        };
+
+        Ok(RenderPipelineDescriptor {
+            vertex: VertexState {
+                shader: TILE_INSTANCE_SHADER_HANDLE.typed::<Shader>(),
+                entry_point: "vertex".into(),
+                shader_defs: Vec::new(),
+                buffers: vec![vertex_layout, instance_layout],
+            },
+            fragment: Some(FragmentState {
+                shader: TILE_INSTANCE_SHADER_HANDLE.typed::<Shader>(),
+                shader_defs: Vec::new(),
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: TextureFormat::bevy_default(),
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            layout: Some(vec![
+                self.mesh2d_pipeline.view_layout.clone(),
+                self.mesh2d_pipeline.mesh_layout.clone(),
+                self.atlas_layout.clone(),
+            ]),
+            primitive: PrimitiveState {
+                front_face: FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+                topology: key.primitive_topology(),
+                strip_index_format: None,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: key.msaa_samples(),
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            label: Some("tile_instance_pipeline".into()),
+        })
+    }
+}
+
+/// GPU resources for the instances of a chunk
+struct PreparedTileInstances {
+    buffer: Buffer,
+    length: u32,
+    atlas_bind_group: BindGroup,
+}
+
+/// The instances of every chunk in the render world, kept between frames so they're only
+/// uploaded when they change
+///
+/// Keyed by the entity of the chunk, which is the same in both worlds
+#[derive(Default)]
+struct TileInstanceBuffers {
+    /// Instances that changed and haven't been uploaded yet, because they were extracted
+    /// this frame or their atlas hasn't loaded
+    pending: HashMap<Entity, ChunkInstances>,
+    prepared: HashMap<Entity, PreparedTileInstances>,
+}
+
+/// Marks chunks whose instances are visible this frame
+#[derive(Component)]
+struct VisibleTileInstances;
+
+#[allow(clippy::type_complexity)]
+fn extract_tile_instances_system(
+    mut commands: Commands,
+    mut buffers: ResMut<TileInstanceBuffers>,
+    mut previous_len: Local<usize>,
+    chunk_query: Extract<
+        Query<(
+            Entity,
+            &ComputedVisibility,
+            &ChunkInstances,
+            ChangeTrackers<ChunkInstances>,
+        )>,
+    >,
+) {
+    let mut visible = Vec::with_capacity(*previous_len);
+    let mut alive = HashSet::default();
+    for (entity, visibility, chunk_instances, tracker) in chunk_query.iter() {
+        alive.insert(entity);
+        if tracker.is_changed() {
+            buffers.pending.insert(entity, chunk_instances.clone());
+        }
+        if visibility.is_visible() {
+            visible.push((entity, (VisibleTileInstances,)));
+        }
+    }
+    *previous_len = visible.len();
+    commands.insert_or_spawn_batch(visible);
+
+    buffers.pending.retain(|entity, _| alive.contains(entity));
+    buffers.prepared.retain(|entity, _| alive.contains(entity));
+}
+
+fn prepare_tile_instances_system(
+    mut buffers: ResMut<TileInstanceBuffers>,
+    render_device: Res<RenderDevice>,
+    images: Res<RenderAssets<Image>>,
+    pipeline: Res<TileInstancePipeline>,
+) {
+    let TileInstanceBuffers { pending, prepared } = &mut *buffers;
+    pending.retain(|entity, chunk_instances| {
+        let atlas = match images.get(&chunk_instances.atlas) {
+            Some(atlas) => atlas,
+            None => return true,
+        };
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("tile_instance_buffer"),
+            contents: cast_slice(&chunk_instances.instances),
+            usage: BufferUsages::VERTEX,
+        });
+        let atlas_size = chunk_instances.atlas_size.max(UVec2::ONE);
+        let size_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("tile_atlas_size_buffer"),
+            contents: cast_slice(&[atlas_size.x, atlas_size.y, 0, 0]),
+            usage: BufferUsages::UNIFORM,
+        });
+        let atlas_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("tile_atlas_bind_group"),
+            layout: &pipeline.atlas_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&atlas.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&atlas.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: size_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        prepared.insert(
+            *entity,
+            PreparedTileInstances {
+                buffer,
+                length: chunk_instances.instances.len() as u32,
+                atlas_bind_group,
+            },
+        );
+        false
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_tile_instances_system(
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    pipeline: Res<TileInstancePipeline>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<TileInstancePipeline>>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    msaa: Res<Msaa>,
+    render_meshes: Res<RenderAssets<Mesh>>,
+    buffers: Res<TileInstanceBuffers>,
+    instanced_query: Query<(&Mesh2dHandle, &Mesh2dUniform), With<VisibleTileInstances>>,
+    mut views: Query<(&VisibleEntities, &mut RenderPhase<Transparent2d>)>,
+) {
+    if instanced_query.is_empty() {
+        return;
+    }
+    let draw_function = draw_functions.read().get_id::<DrawTileInstances>().unwrap();
+    let msaa_key = Mesh2dPipelineKey::from_msaa_samples(msaa.samples);
+
+    for (visible_entities, mut phase) in &mut views {
+        for entity in &visible_entities.entities {
+            let (mesh_handle, uniform) = match instanced_query.get(*entity) {
+                Ok(item) if buffers.prepared.contains_key(entity) => item,
+                _ => continue,
+            };
+            let mesh = match render_meshes.get(&mesh_handle.0) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let key =
+                msaa_key | Mesh2dPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline =
+                match pipelines.specialize(&mut pipeline_cache, &pipeline, key, &mesh.layout) {
+                    Ok(pipeline) => pipeline,
+                    Err(err) => {
+                        error!("{}", err);
+                        continue;
+                    }
+                };
+            phase.add(Transparent2d {
+                sort_key: FloatOrd(uniform.transform.w_axis.z),
+                entity: *entity,
+                pipeline,
+                draw_function,
+                batch_range: None,
+            });
+        }
+    }
+}
+
+type DrawTileInstances = (
+    SetItemPipeline,
+    SetMesh2dViewBindGroup<0>,
+    SetMesh2dBindGroup<1>,
+    DrawTileInstancesCommand,
+);
+
+/// Sets the atlas bind group and draws every instance of the chunk
+struct DrawTileInstancesCommand;
+
+impl EntityRenderCommand for DrawTileInstancesCommand {
+    type Param = (
+        SRes<RenderAssets<Mesh>>,
+        SQuery<Read<Mesh2dHandle>>,
+        SRes<TileInstanceBuffers>,
+    );
+
+    fn render<'w>(
+        _view: Entity,
+        item: Entity,
+        (meshes, mesh_query, buffers): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let (mesh_handle, instances) = match (
+            mesh_query.get(item),
+            buffers.into_inner().prepared.get(&item),
+        ) {
+            (Ok(mesh_handle), Some(instances)) => (mesh_handle, instances),
+            _ => return RenderCommandResult::Failure,
+        };
+        let gpu_mesh = match meshes.into_inner().get(&mesh_handle.0) {
+            Some(gpu_mesh) => gpu_mesh,
+            None => return RenderCommandResult::Failure,
+        };
+
+        pass.set_bind_group(2, &instances.atlas_bind_group, &[]);
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instances.buffer.slice(..));
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instances.length);
+            }
+            GpuBufferInfo::NonIndexed { vertex_count } => {
+                pass.draw(0..*vertex_count, 0..instances.length);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::{
+        test_util::*,
+        tilemap::{ChunkPos, IRect},
+    };
+
+    impl InstancedTile for TestTile {
+        fn add_instances(&self, builder: &mut InstanceBuilder) {
+            builder.push(self.0 as u32);
+        }
+    }
+
+    #[test]
+    fn hooks_run_with_instanced_renderer() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<ColorMaterial>()
+            .add_plugin(InstancedTilemapPlugin::<TestTile>::new());
+        app.update();
+        {
+            let mut tilemap = tilemap::<TestTile>(&mut app);
+            tilemap.set_hooks_enabled(true);
+            tilemap.set(pos(0, 0), TestTile(1));
+            assert!(!tilemap.pending_hooks.is_empty());
+        }
+        app.update();
+        assert!(tilemap::<TestTile>(&mut app).pending_hooks.is_empty());
+    }
+
+    #[test]
+    fn full_chunk_uses_one_instance_per_tile() {
+        let mut builder = InstanceBuilder::new();
+        for tile_pos in ChunkPos::iter_positions() {
+            builder.set_offset(tile_pos.as_ivec2().as_vec2());
+            TestTile(1).add_instances(&mut builder);
+        }
+        assert_eq!(builder.len(), CHUNK_SIZE * CHUNK_SIZE);
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<ColorMaterial>()
+            .add_plugin(InstancedTilemapPlugin::<TestTile>::new())
+            .insert_resource(TileAtlas::<TestTile>::new(Handle::default(), UVec2::ONE));
+        app.update();
+        tilemap::<TestTile>(&mut app).generate_region(
+            IRect::new(IVec2::ZERO, IVec2::splat(CHUNK_SIZE as i32 - 1)),
+            |_| Some(TestTile(1)),
+        );
+        app.update();
+        let entity = tilemap::<TestTile>(&mut app)
+            .get_chunk(IVec2::ZERO)
+            .unwrap()
+            .mesh_entity
+            .unwrap();
+        let instances = &app.world.get::<ChunkInstances>(entity).unwrap().instances;
+        assert_eq!(instances.len(), 1024);
+        assert_eq!(instances[33].offset, [1.0, 1.0]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Multiple tilemaps layered on top of each other
+
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+
+use crate::{
+    generate_meshes, run_tile_hooks, select_lods,
+    tile::Tile,
+    tilemap::{Tilemap, TilemapPos},
+    MeshGeneration, RenderLabel,
+};
+
+/// The Bevy plugin to add support for [`TilemapLayers`] with a specific tile type
+///
+/// Adds an empty [`TilemapLayers`] resource if there isn't one.  Independent from
+/// [`TilemapPlugin`](crate::TilemapPlugin), which only manages the [`Tilemap`] resource
+#[derive(Debug)]
+pub struct TilemapLayersPlugin<T: Tile> {
+    _phantom: PhantomData<TilemapLayers<T>>,
+}
+
+impl<T: Tile> TilemapLayersPlugin<T> {
+    /// Creates a new plugin for the given tilemap layers
+    pub fn new() -> Self {
+        TilemapLayersPlugin {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Tile> Default for TilemapLayersPlugin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Tile> Plugin for TilemapLayersPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TilemapLayers<T>>()
+            .add_system_set(
+                SystemSet::new()
+                    .label(RenderLabel::MeshGeneration)
+                    .with_system(generate_layer_meshes_system::<T>),
+            )
+            .add_system(select_layer_lods_system::<T>.after(RenderLabel::MeshGeneration));
+    }
+}
+
+/// Tilemaps sharing the same coordinates, each displayed at it's own z
+///
+/// Layer `n` is displayed at z `n`, so later layers are drawn over earlier ones.  Each
+/// layer is a full [`Tilemap`] with it's own chunks, material and settings
+pub struct TilemapLayers<T: Tile> {
+    layers: Vec<Tilemap<T>>,
+}
+
+impl<T: Tile> TilemapLayers<T> {
+    /// Creates a new instance of this with no layers
+    #[must_use]
+    pub fn new() -> Self {
+        TilemapLayers { layers: Vec::new() }
+    }
+
+    /// Adds `tilemap` as a new layer on top of the others, returning it's index
+    ///
+    /// Sets the [z](Tilemap::set_z) of `tilemap` to it's index
+    pub fn push(&mut self, mut tilemap: Tilemap<T>) -> usize {
+        let layer = self.layers.len();
+        tilemap.set_z(layer as f32);
+        self.layers.push(tilemap);
+        layer
+    }
+
+    /// Returns the number of layers in this
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns `true` if this has no layers
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Returns a reference to the tilemap of `layer`, if it exists
+    #[must_use]
+    pub fn layer(&self, layer: usize) -> Option<&Tilemap<T>> {
+        self.layers.get(layer)
+    }
+
+    /// Returns a mutable reference to the tilemap of `layer`, if it exists
+    #[must_use]
+    pub fn layer_mut(&mut self, layer: usize) -> Option<&mut Tilemap<T>> {
+        self.layers.get_mut(layer)
+    }
+
+    /// Returns a reference to the tile at `pos` in `layer`, if there is one
+    #[must_use]
+    pub fn get(&self, layer: usize, pos: TilemapPos) -> Option<&T> {
+        self.layer(layer)?.get(pos)
+    }
+
+    /// Sets the tile at `pos` in `layer` to `tile`, returning the old tile if there was one
+    ///
+    /// See [`Tilemap::set`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layer` doesn't exist
+    pub fn set(&mut self, layer: usize, pos: TilemapPos, tile: impl Into<T>) -> Option<T> {
+        self.layers[layer].set(pos, tile)
+    }
+
+    /// Returns an iterator over the layers in this, from bottom to top
+    pub fn iter_layers(&self) -> impl Iterator<Item = &Tilemap<T>> {
+        self.layers.iter()
+    }
+
+    /// Returns an iterator over all tiles in every layer of this, their layers and their
+    /// positions
+    ///
+    /// Tiles are returned layer by layer, from bottom to top
+    pub fn iter(&self) -> impl Iterator<Item = (usize, TilemapPos, &T)> {
+        self.layers.iter().enumerate().flat_map(|(layer, tilemap)| {
+            tilemap
+                .iter_positions()
+                .map(move |(pos, tile)| (layer, pos, tile))
+        })
+    }
+}
+
+impl<T: Tile> Default for TilemapLayers<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn generate_layer_meshes_system<T: Tile>(
+    mut layers: ResMut<TilemapLayers<T>>,
+    mut mesh_generation: MeshGeneration<T>,
+) {
+    for tilemap in &mut layers.layers {
+        if !tilemap.pending_hooks.is_empty() {
+            run_tile_hooks(tilemap, &mut mesh_generation.commands);
+        }
+        generate_meshes(tilemap, &mut mesh_generation);
+    }
+}
+
+fn select_layer_lods_system<T: Tile>(
+    mut layers: ResMut<TilemapLayers<T>>,
+    mut mesh_query: Query<&mut Mesh2dHandle>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+) {
+    if let Some(camera) = camera_query.iter().next() {
+        for tilemap in &mut layers.layers {
+            select_lods(tilemap, &mut mesh_query, camera);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn layers_are_stacked_in_order() {
+        let mut layers = TilemapLayers::<TestTile>::new();
+        assert_eq!(layers.push(test_tilemap()), 0);
+        assert_eq!(layers.push(test_tilemap()), 1);
+        assert_eq!(layers.layer(1).unwrap().z(), 1.0);
+
+        layers.set(1, pos(0, 0), TestTile(2));
+        layers.set(0, pos(3, 0), TestTile(1));
+        assert_eq!(layers.get(0, pos(0, 0)), None);
+        let tiles: Vec<_> = layers.iter().collect();
+        assert_eq!(
+            tiles,
+            [(0, pos(3, 0), &TestTile(1)), (1, pos(0, 0), &TestTile(2))]
+        );
+
+        let z = |layer| {
+            layers
+                .layer(layer)
+                .unwrap()
+                .chunk_world_transform(IVec2::ZERO)
+                .translation
+                .z
+        };
+        assert!(z(0) < z(1), "higher layers should be drawn in front");
+    }
+}
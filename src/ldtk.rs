@@ -0,0 +1,229 @@
+//! Importing tilemaps from [LDtk](https://ldtk.io) projects
+
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    rendering::MeshBuilder,
+    tile::Tile,
+    tilemap::{Tilemap, TilemapPos},
+};
+
+/// The parts of an LDtk project file used for importing
+#[derive(Debug, Deserialize)]
+struct Project {
+    levels: Vec<Level>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Level {
+    identifier: String,
+    #[serde(rename = "layerInstances", default)]
+    layer_instances: Option<Vec<LayerInstance>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LayerInstance {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__type")]
+    layer_type: String,
+    #[serde(rename = "__cWid")]
+    width: i32,
+    #[serde(rename = "__cHei")]
+    height: i32,
+    #[serde(rename = "intGridCsv", default)]
+    int_grid_csv: Vec<i32>,
+}
+
+/// The reasons importing an LDtk project can fail
+#[derive(Debug)]
+pub enum LdtkError {
+    /// The project isn't valid LDtk JSON
+    Json(serde_json::Error),
+    /// The project has no level with this identifier
+    MissingLevel(String),
+    /// The level has no layer with this identifier
+    MissingLayer(String),
+    /// The layer with this identifier isn't an IntGrid layer
+    NotIntGrid(String),
+    /// The number of values in the layer with this identifier doesn't match it's size
+    WrongSize(String),
+}
+
+impl fmt::Display for LdtkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LdtkError::Json(err) => write!(f, "invalid LDtk project: {err}"),
+            LdtkError::MissingLevel(level) => write!(f, "no level named {level:?}"),
+            LdtkError::MissingLayer(layer) => write!(f, "no layer named {layer:?}"),
+            LdtkError::NotIntGrid(layer) => write!(f, "layer {layer:?} is not an IntGrid layer"),
+            LdtkError::WrongSize(layer) => {
+                write!(f, "layer {layer:?} has the wrong number of values")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LdtkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LdtkError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for LdtkError {
+    fn from(err: serde_json::Error) -> Self {
+        LdtkError::Json(err)
+    }
+}
+
+impl<T: Tile> Tilemap<T> {
+    /// Creates a tilemap whose chunk meshes use `material` from an IntGrid layer of an
+    /// LDtk project
+    ///
+    /// `project` is the contents of the `.ldtk` file, and `level` and `layer` are the
+    /// identifiers of the level and layer to import.  Each cell with a nonzero value is passed
+    /// to `tile_from_id`, and the tile it returns (if any) is placed there.  LDtk places the
+    /// origin at the top left of a level with y pointing down, so the bottom left cell of the
+    /// layer is placed at (0, 0).  Every chunk is flagged to generate it's mesh the next time
+    /// it's displayed
+    pub fn from_ldtk(
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+        project: &str,
+        level: &str,
+        layer: &str,
+        tile_from_id: impl Fn(i32) -> Option<T>,
+    ) -> Result<Self, LdtkError> {
+        let project: Project = serde_json::from_str(project)?;
+        let level = project
+            .levels
+            .into_iter()
+            .find(|l| l.identifier == level)
+            .ok_or_else(|| LdtkError::MissingLevel(level.to_owned()))?;
+        let layer = level
+            .layer_instances
+            .into_iter()
+            .flatten()
+            .find(|l| l.identifier == layer)
+            .ok_or_else(|| LdtkError::MissingLayer(layer.to_owned()))?;
+        if layer.layer_type != "IntGrid" {
+            return Err(LdtkError::NotIntGrid(layer.identifier));
+        }
+        let size = match (usize::try_from(layer.width), usize::try_from(layer.height)) {
+            (Ok(width), Ok(height)) => width.checked_mul(height),
+            _ => None,
+        };
+        if size != Some(layer.int_grid_csv.len()) {
+            return Err(LdtkError::WrongSize(layer.identifier));
+        }
+
+        let mut tilemap = Self::new(material);
+        for (i, id) in layer.int_grid_csv.into_iter().enumerate() {
+            if id == 0 {
+                continue;
+            }
+            if let Some(tile) = tile_from_id(id) {
+                let (x, row) = (i as i32 % layer.width, i as i32 / layer.width);
+                let pos = TilemapPos::from(IVec2::new(x, layer.height - 1 - row));
+                tilemap.set(pos, tile);
+            }
+        }
+        Ok(tilemap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    const PROJECT: &str = r#"{
+        "levels": [{
+            "identifier": "Level_0",
+            "layerInstances": [
+                {
+                    "__identifier": "Walls",
+                    "__type": "IntGrid",
+                    "__cWid": 3,
+                    "__cHei": 2,
+                    "intGridCsv": [1, 0, 0, 0, 0, 2]
+                },
+                {
+                    "__identifier": "Entities",
+                    "__type": "Entities",
+                    "__cWid": 3,
+                    "__cHei": 2
+                }
+            ]
+        }]
+    }"#;
+
+    fn import(level: &str, layer: &str) -> Result<Tilemap<TestTile>, LdtkError> {
+        Tilemap::from_ldtk(Handle::default(), PROJECT, level, layer, |id| {
+            Some(TestTile(id as u8))
+        })
+    }
+
+    #[test]
+    fn flips_rows() {
+        let tilemap = import("Level_0", "Walls").unwrap();
+        assert_eq!(tilemap.get(pos(0, 1)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(pos(2, 0)), Some(&TestTile(2)));
+        assert_eq!(tilemap.iter_positions().count(), 2);
+    }
+
+    #[test]
+    fn reports_missing_and_wrong_layers() {
+        assert!(matches!(
+            import("Level_1", "Walls"),
+            Err(LdtkError::MissingLevel(_))
+        ));
+        assert!(matches!(
+            import("Level_0", "Floor"),
+            Err(LdtkError::MissingLayer(_))
+        ));
+        assert!(matches!(
+            import("Level_0", "Entities"),
+            Err(LdtkError::NotIntGrid(_))
+        ));
+        assert!(matches!(
+            Tilemap::<TestTile>::from_ldtk(Handle::default(), "{", "Level_0", "Walls", |_| None),
+            Err(LdtkError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_sizes() {
+        let project = |width: i64, height: i64, values: &str| {
+            format!(
+                r#"{{"levels": [{{"identifier": "L", "layerInstances": [{{
+                    "__identifier": "Walls", "__type": "IntGrid",
+                    "__cWid": {width}, "__cHei": {height}, "intGridCsv": [{values}]
+                }}]}}]}}"#
+            )
+        };
+        let import = |project: String| {
+            Tilemap::<TestTile>::from_ldtk(Handle::default(), &project, "L", "Walls", |_| None)
+        };
+        for (width, height, values) in [
+            (2, 2, "1, 1, 1"),
+            (-1, -1, "1"),
+            (65536, 65536, ""),
+            (i32::MAX as i64, 2, ""),
+        ] {
+            assert!(
+                matches!(
+                    import(project(width, height, values)),
+                    Err(LdtkError::WrongSize(_))
+                ),
+                "{width} by {height}"
+            );
+        }
+        assert!(import(project(0, 5, "")).unwrap().iter().next().is_none());
+    }
+}
@@ -4,15 +4,21 @@
 
 #![warn(missing_docs)]
 
-use std::{marker::PhantomData, mem};
+use std::{collections::BTreeMap, marker::PhantomData, mem, time::Duration};
 
+use animation::SharedAnimation;
 use bevy::{
+    ecs::system::SystemParam,
     prelude::*,
+    render::{mesh::VertexAttributeValues, primitives::Aabb},
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
 };
 use rendering::MeshBuilder;
 use tile::Tile;
-use tilemap::Tilemap;
+use tilemap::{
+    AnimatedVertices, Chunk, ChunkMap, ChunkPos, Direction, DirtyTiles, HookEvent, TileLayout,
+    Tilemap,
+};
 
 /// The width/height of tilemap chunks
 ///
@@ -20,9 +26,24 @@ use tilemap::Tilemap;
 pub const CHUNK_SIZE: usize = 32;
 
 pub mod animation;
+pub mod brush;
+pub mod history;
+#[cfg(feature = "instancing")]
+pub mod instancing;
+pub mod layers;
+#[cfg(feature = "ldtk")]
+pub mod ldtk;
+pub mod network;
+#[cfg(feature = "picking")]
+pub mod picking;
+pub mod query;
 pub mod rendering;
+#[cfg(test)]
+mod test_util;
 pub mod tile;
 pub mod tilemap;
+#[cfg(feature = "rand")]
+pub mod wfc;
 
 /// Stage label for stages related to tilemap rendering
 #[derive(Debug, SystemLabel)]
@@ -38,98 +59,1112 @@ pub enum RenderLabel {
 /// If using multiple tilemaps, a plugin must be added for each and
 /// they must use different structs for tiles
 #[derive(Debug)]
-pub struct TilemapPlugin<T: Tile> {
-    _phantom: PhantomData<Tilemap<T>>,
+pub struct TilemapPlugin<T: Tile, M = ()> {
+    /// The maximum number of chunk meshes regenerated each frame
+    ///
+    /// Chunks that don't fit in the budget stay flagged and are regenerated in later
+    /// frames, closest to the camera first.  Defaults to [`usize::MAX`] (no limit)
+    pub max_regens_per_frame: usize,
+    _phantom: PhantomData<Tilemap<T, M>>,
 }
 
 impl<T: Tile> TilemapPlugin<T> {
     /// Creates a new plugin for the given tilemap
+    ///
+    /// For a tilemap with [metadata](Tilemap::with_metadata()) of type `M`, use
+    /// `TilemapPlugin::<T, M>::default()` instead
     pub fn new() -> Self {
-        TilemapPlugin {
-            _phantom: PhantomData::default(),
-        }
+        Self::default()
+    }
+}
+
+impl<T: Tile, M> TilemapPlugin<T, M> {
+    /// Sets the maximum number of chunk meshes regenerated each frame
+    #[must_use]
+    pub fn with_max_regens_per_frame(mut self, max_regens_per_frame: usize) -> Self {
+        self.max_regens_per_frame = max_regens_per_frame;
+        self
     }
 }
 
-impl<T: Tile> Default for TilemapPlugin<T> {
+impl<T: Tile, M> Default for TilemapPlugin<T, M> {
     fn default() -> Self {
-        Self::new()
+        TilemapPlugin {
+            max_regens_per_frame: usize::MAX,
+            _phantom: PhantomData::default(),
+        }
     }
 }
 
-impl<T: Tile> Plugin for TilemapPlugin<T> {
+impl<T: Tile, M: Send + Sync + 'static> Plugin for TilemapPlugin<T, M> {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(spawn_tilemap_system::<T>)
+        app.add_startup_system(spawn_tilemap_system::<T, M>(self.max_regens_per_frame))
             .add_system_set(
                 SystemSet::new()
                     .label(RenderLabel::MeshGeneration)
-                    .with_system(generate_meshes_system::<T>),
+                    .with_system(generate_meshes_system::<T, M>),
+            )
+            .add_system(tile_hooks_system::<T, M>.before(RenderLabel::MeshGeneration))
+            .add_system(select_lods_system::<T, M>.after(RenderLabel::MeshGeneration))
+            .add_system(
+                animate_tiles_system::<T, M>
+                    .label(RenderLabel::Animation)
+                    .after(RenderLabel::MeshGeneration),
             );
     }
 }
 
-fn spawn_tilemap_system<T: Tile>(
+/// The material chunk meshes of tiles of type `T` use
+type TileMaterial<T> = <<T as Tile>::MeshBuilder as MeshBuilder>::Material;
+
+/// The layer, mesh, material and bounds of a mesh layer other than 0 of a chunk
+type SublayerMesh<T> = (u8, Mesh2dHandle, Handle<TileMaterial<T>>, Option<Aabb>);
+
+#[allow(clippy::type_complexity)]
+fn spawn_tilemap_system<T: Tile, M: Send + Sync + 'static>(
+    max_regens_per_frame: usize,
+) -> impl FnMut(Commands, ResMut<Assets<TileMaterial<T>>>, Option<Res<Tilemap<T, M>>>) {
+    move |mut commands, mut materials, existing| {
+        if existing.is_some() {
+            return;
+        }
+        let mut tilemap = Tilemap::<T, M>::with_metadata(materials.add(T::MeshBuilder::material()));
+        tilemap.set_max_regens_per_frame(max_regens_per_frame);
+        commands.insert_resource(tilemap)
+    }
+}
+
+/// Calls [`Tile::on_set`] and [`Tile::on_remove`] for tiles set and removed since this
+/// last ran
+fn tile_hooks_system<T: Tile, M: Send + Sync + 'static>(
     mut commands: Commands,
-    mut materials: ResMut<Assets<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>>,
+    mut tilemap: ResMut<Tilemap<T, M>>,
 ) {
-    commands.insert_resource(Tilemap::<T>::new(materials.add(T::MeshBuilder::material())))
+    if !tilemap.pending_hooks.is_empty() {
+        run_tile_hooks(&mut tilemap, &mut commands);
+    }
 }
 
-fn generate_meshes_system<T: Tile>(
-    mut commands: Commands,
-    mut tilemap: ResMut<Tilemap<T>>,
-    mut mesh_query: Query<&mut Mesh2dHandle>,
-    mut meshes: ResMut<Assets<Mesh>>,
+fn run_tile_hooks<T: Tile, M>(tilemap: &mut Tilemap<T, M>, commands: &mut Commands) {
+    for event in mem::take(&mut tilemap.pending_hooks) {
+        match event {
+            HookEvent::Set(pos, tile) => tile.on_set(pos, commands),
+            HookEvent::Removed(pos, tile) => tile.on_remove(pos, commands),
+        }
+    }
+}
+
+/// The parts of the world used to generate chunk meshes
+#[derive(SystemParam)]
+struct MeshGeneration<'w, 's, T: Tile> {
+    commands: Commands<'w, 's>,
+    mesh_query: Query<'w, 's, (&'static mut Mesh2dHandle, &'static mut Transform)>,
+    material_query: Query<'w, 's, &'static mut Handle<TileMaterial<T>>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    camera_query: Query<'w, 's, &'static GlobalTransform, With<Camera>>,
+    time: Res<'w, Time>,
+}
+
+fn generate_meshes_system<T: Tile, M: Send + Sync + 'static>(
+    mut tilemap: ResMut<Tilemap<T, M>>,
+    mut mesh_generation: MeshGeneration<T>,
 ) {
-    let tilemap = &mut *tilemap;
-    tilemap.data.retain(|chunk_pos, chunk| {
-        if chunk.regenerate_mesh {
-            chunk.regenerate_mesh = false;
-
-            let mut mesh_builder = T::MeshBuilder::init(mem::take(&mut chunk.mesh_carry_data));
-            let mut found_tile = false;
-            for (tile_pos, tile) in chunk.iter_tile_positions_mut() {
-                mesh_builder.set_offset(tile_pos.as_ivec2().as_vec2());
-                tile.add_to_mesh(&mut mesh_builder);
-                found_tile = true;
+    generate_meshes(&mut tilemap, &mut mesh_generation);
+}
+
+/// Generates the meshes of the chunks in `tilemap` that need it, and updates their
+/// mesh entities
+fn generate_meshes<T: Tile, M>(tilemap: &mut Tilemap<T, M>, params: &mut MeshGeneration<T>) {
+    let MeshGeneration {
+        commands,
+        mesh_query,
+        material_query,
+        meshes,
+        camera_query,
+        time,
+    } = params;
+    let layout = tilemap.layout();
+    let z = tilemap.z();
+
+    for (chunk_pos, chunk) in tilemap.data.iter_mut() {
+        if chunk.update_transform {
+            chunk.update_transform = false;
+            if let Some((_, mut transform)) = chunk
+                .mesh_entity
+                .and_then(|entity| mesh_query.get_mut(entity).ok())
+            {
+                *transform = chunk_transform(layout, z, *chunk_pos, chunk.visual_offset());
             }
+        }
+    }
 
-            if found_tile {
-                let (new_mesh, carry_data) = mesh_builder.finish();
-                let new_mesh = meshes.add(new_mesh).into();
-                chunk.mesh_carry_data = carry_data;
-
-                if let Some(mut mesh) = chunk
-                    .mesh_entity
-                    .and_then(|entity| mesh_query.get_mut(entity).ok())
-                {
-                    *mesh = new_mesh;
-                } else {
-                    chunk.mesh_entity = Some(
-                        commands
-                            .spawn_bundle(MaterialMesh2dBundle {
-                                mesh: new_mesh,
-                                transform: Transform::from_translation(Vec3::new(
-                                    (CHUNK_SIZE as i32 * chunk_pos.x) as f32,
-                                    (CHUNK_SIZE as i32 * chunk_pos.y) as f32,
-                                    0.0,
-                                )),
-                                material: tilemap.material.clone(),
-                                ..default()
-                            })
-                            .id(),
-                    );
+    if tilemap.update_material {
+        tilemap.update_material = false;
+        for chunk in tilemap.data.values() {
+            let entities = chunk.mesh_entity.map(|entity| (0, entity)).into_iter();
+            for (layer, entity) in entities.chain(chunk.sublayer_entities.iter().copied()) {
+                if let Ok(mut material) = material_query.get_mut(entity) {
+                    *material = tilemap.layer_material(layer).clone();
                 }
-            } else if let Some(entity) = chunk.mesh_entity {
-                commands.entity(entity).despawn();
             }
+        }
+    }
+
+    let now = time.time_since_startup();
+    let to_regenerate = chunks_to_regenerate(tilemap, camera_query.iter().next(), now);
+    tilemap.regen_stats.record_frame(to_regenerate.len());
+    for chunk_pos in to_regenerate {
+        let chunk = match tilemap.data.get_mut(&chunk_pos) {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+        chunk.regenerate_mesh = false;
+        chunk.last_regen = Some(now);
+        let dirty_tiles = mem::replace(&mut chunk.dirty_tiles, DirtyTiles::Clean);
+        let mesh_handle = chunk
+            .mesh_entity
+            .and_then(|entity| mesh_query.get(entity).ok())
+            .map(|(mesh, _)| mesh.0.clone_weak());
+        // Patching the mesh would leave any other levels of detail out of date
+        let single_lod = chunk.lod_meshes.len() <= 1;
+        if let (DirtyTiles::One(tile_pos), Some(mesh_handle)) = (dirty_tiles, mesh_handle) {
+            if single_lod && update_tile_mesh(tilemap, chunk_pos, tile_pos, &mesh_handle, meshes) {
+                let aabb = meshes.get(&mesh_handle).and_then(Mesh::compute_aabb);
+                let chunk = tilemap.data.get_mut(&chunk_pos).unwrap();
+                chunk.set_mesh_aabb(aabb.as_ref());
+                set_aabb(commands, chunk.mesh_entity.unwrap(), aabb);
+                continue;
+            }
+        }
+
+        let lods = build_chunk_mesh(tilemap, chunk_pos);
+        let sublayers: Vec<_> = build_sublayer_meshes(tilemap, chunk_pos)
+            .into_iter()
+            .map(|(layer, mesh)| {
+                let material = tilemap.layer_material(layer).clone();
+                let aabb = mesh.compute_aabb();
+                (layer, Mesh2dHandle(meshes.add(mesh)), material, aabb)
+            })
+            .collect();
+        let chunk = tilemap.data.get_mut(&chunk_pos).unwrap();
+        if let Some(lods) = lods {
+            // Covers every level of detail, since the one displayed changes without this
+            let aabb = aabb_union(lods.iter().filter_map(Mesh::compute_aabb));
+            chunk.set_mesh_aabb(
+                aabb_union(
+                    aabb.iter()
+                        .chain(sublayers.iter().filter_map(|sublayer| sublayer.3.as_ref()))
+                        .cloned(),
+                )
+                .as_ref(),
+            );
+            chunk.lod_meshes = lods.into_iter().map(|lod| meshes.add(lod)).collect();
+            chunk.lod_level = 0;
+            let new_mesh = Mesh2dHandle(chunk.lod_meshes[0].clone());
 
-            found_tile
+            if let Some((mut mesh, _)) = chunk
+                .mesh_entity
+                .and_then(|entity| mesh_query.get_mut(entity).ok())
+            {
+                *mesh = new_mesh;
+            } else {
+                chunk.mesh_entity = Some(
+                    commands
+                        .spawn_bundle(MaterialMesh2dBundle {
+                            mesh: new_mesh,
+                            transform: chunk_transform(layout, z, chunk_pos, chunk.visual_offset()),
+                            material: tilemap.material.clone(),
+                            ..default()
+                        })
+                        .id(),
+                );
+            }
+            set_aabb(commands, chunk.mesh_entity.unwrap(), aabb);
+            update_sublayer_entities(commands, mesh_query, chunk, sublayers);
         } else {
-            true
+            if let Some(entity) = chunk.mesh_entity {
+                commands.entity(entity).despawn_recursive();
+            }
+            tilemap.recycle_chunk(chunk_pos);
+        }
+    }
+}
+
+fn select_lods_system<T: Tile, M: Send + Sync + 'static>(
+    mut tilemap: ResMut<Tilemap<T, M>>,
+    mut mesh_query: Query<&mut Mesh2dHandle>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+) {
+    if let Some(camera) = camera_query.iter().next() {
+        select_lods(&mut tilemap, &mut mesh_query, camera);
+    }
+}
+
+/// Switches the mesh entity of each chunk in `tilemap` with multiple levels of detail to
+/// the level it should display with the camera at `camera`
+///
+/// Meshes are only switched when the level changes
+fn select_lods<T: Tile, M>(
+    tilemap: &mut Tilemap<T, M>,
+    mesh_query: &mut Query<&mut Mesh2dHandle>,
+    camera: &GlobalTransform,
+) {
+    if tilemap.lod_distances().is_empty() {
+        return;
+    }
+    let camera = camera.translation().truncate();
+    let changed: Vec<(IVec2, usize)> = tilemap
+        .iter_chunk_positions()
+        .filter(|(_, chunk)| chunk.lod_meshes.len() > 1 && chunk.mesh_entity.is_some())
+        .filter_map(|(chunk_pos, chunk)| {
+            let level = tilemap
+                .chunk_lod_level(*chunk_pos, camera)
+                .min(chunk.lod_meshes.len() - 1);
+            (level != chunk.lod_level).then_some((*chunk_pos, level))
+        })
+        .collect();
+    for (chunk_pos, level) in changed {
+        let chunk = tilemap.get_chunk_mut(chunk_pos).unwrap();
+        if let Ok(mut mesh) = mesh_query.get_mut(chunk.mesh_entity.unwrap()) {
+            *mesh = Mesh2dHandle(chunk.lod_meshes[level].clone());
+            chunk.lod_level = level;
+        }
+    }
+}
+
+fn animate_tiles_system<T: Tile, M: Send + Sync + 'static>(
+    mut tilemap: ResMut<Tilemap<T, M>>,
+    clock: Option<Res<SharedAnimation>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if let Some(clock) = clock {
+        animate_tiles(&mut tilemap, &clock, &mut meshes);
+    }
+}
+
+/// Offsets the UVs of the animated tiles in the mesh of each chunk in `tilemap` to the frame
+/// they're on at the time of `clock`
+///
+/// Meshes are only changed when the frame of a tile in them changes
+fn animate_tiles<T: Tile, M>(
+    tilemap: &mut Tilemap<T, M>,
+    clock: &SharedAnimation,
+    meshes: &mut Assets<Mesh>,
+) {
+    for chunk in tilemap.data.values_mut() {
+        if chunk
+            .animated_tiles
+            .iter()
+            .all(|tile| tile.animation.frame(clock) == tile.frame)
+        {
+            continue;
+        }
+        let uvs = match chunk
+            .lod_meshes
+            .first()
+            .and_then(|mesh| meshes.get_mut(mesh))
+            .and_then(|mesh| mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0))
+        {
+            Some(VertexAttributeValues::Float32x2(uvs)) => uvs,
+            _ => continue,
+        };
+        for tile in &mut chunk.animated_tiles {
+            tile.frame = tile.animation.frame(clock);
+            let offset = tile.animation.frame_step * tile.frame as f32;
+            let vertices = uvs.iter_mut().skip(tile.first_vertex);
+            for (uv, base) in vertices.zip(&tile.base_uvs) {
+                *uv = (Vec2::from(*base) + offset).into();
+            }
+        }
+    }
+}
+
+/// Generates the whole mesh of the chunk at `chunk_pos` at every level of detail, or returns
+/// [`None`] if it has no tiles
+///
+/// Only includes tiles in mesh layer 0, so the mesh may be empty.  Passes the carry data of
+/// the chunk to the mesh builder, and stores the new carry data
+fn build_chunk_mesh<T: Tile, M>(
+    tilemap: &mut Tilemap<T, M>,
+    chunk_pos: IVec2,
+) -> Option<Vec<Mesh>> {
+    let layout = tilemap.layout();
+    let chunk = tilemap.data.get_mut(&chunk_pos)?;
+    let carry_data = mem::take(&mut chunk.mesh_carry_data);
+
+    let neighborhood = ChunkNeighborhood::new(&tilemap.data, chunk_pos);
+    let tiles = tiles_by_z_offset(neighborhood.center());
+    if tiles.is_empty() {
+        return None;
+    }
+    let tiles: Vec<_> = tiles
+        .into_iter()
+        .filter(|(_, tile)| tile.mesh_layer() == 0)
+        .collect();
+    let mut mesh_builder = T::MeshBuilder::init(carry_data);
+    mesh_builder.reserve(tiles.len());
+    let mut animated = Vec::new();
+    for (tile_pos, tile) in tiles {
+        mesh_builder.set_offset(layout.tile_offset(tile_pos.as_ivec2()));
+        mesh_builder.set_z_offset(tile.z_offset());
+        let first_vertex = mesh_builder.vertex_count();
+        add_tile_to_mesh(tile, &mut mesh_builder, &neighborhood, tile_pos);
+        if let (Some(animation), Some(first_vertex), Some(end)) =
+            (tile.animation(), first_vertex, mesh_builder.vertex_count())
+        {
+            animated.push((animation, first_vertex..end));
+        }
+    }
+
+    let (lods, carry_data) = mesh_builder.finish_with_lods();
+    assert!(
+        !lods.is_empty(),
+        "mesh builders must generate at least 1 mesh"
+    );
+    let uvs = match lods[0].attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float32x2(uvs)) => uvs.as_slice(),
+        _ => &[],
+    };
+    let chunk = tilemap.data.get_mut(&chunk_pos).unwrap();
+    chunk.mesh_carry_data = carry_data;
+    chunk.animated_tiles = animated
+        .into_iter()
+        .filter_map(|(animation, vertices)| {
+            Some(AnimatedVertices {
+                animation,
+                first_vertex: vertices.start,
+                base_uvs: uvs.get(vertices)?.to_vec(),
+                frame: 0,
+            })
+        })
+        .collect();
+    Some(lods)
+}
+
+/// Generates a mesh for each mesh layer other than 0 used by tiles in the chunk at
+/// `chunk_pos`, ordered by layer
+///
+/// Mesh builders for these meshes start with the default carry data
+fn build_sublayer_meshes<T: Tile, M>(tilemap: &Tilemap<T, M>, chunk_pos: IVec2) -> Vec<(u8, Mesh)> {
+    let layout = tilemap.layout();
+    if !tilemap.data.contains_key(&chunk_pos) {
+        return Vec::new();
+    }
+    let neighborhood = ChunkNeighborhood::new(&tilemap.data, chunk_pos);
+    let mut builders = BTreeMap::new();
+    for (tile_pos, tile) in tiles_by_z_offset(neighborhood.center()) {
+        let layer = tile.mesh_layer();
+        if layer == 0 {
+            continue;
+        }
+        let mesh_builder = builders
+            .entry(layer)
+            .or_insert_with(|| T::MeshBuilder::init(Default::default()));
+        mesh_builder.set_offset(layout.tile_offset(tile_pos.as_ivec2()));
+        mesh_builder.set_z_offset(tile.z_offset());
+        add_tile_to_mesh(tile, mesh_builder, &neighborhood, tile_pos);
+    }
+    builders
+        .into_iter()
+        .map(|(layer, mesh_builder)| (layer, mesh_builder.finish().0))
+        .collect()
+}
+
+/// Points the sublayer entities of `chunk` at the meshes in `sublayers`, spawning and
+/// despawning children of the chunk mesh entity as needed
+///
+/// Sublayer `n` is displayed `n / 256` in front of the chunk mesh
+fn update_sublayer_entities<T: Tile>(
+    commands: &mut Commands,
+    mesh_query: &mut Query<(&mut Mesh2dHandle, &mut Transform)>,
+    chunk: &mut Chunk<T>,
+    sublayers: Vec<SublayerMesh<T>>,
+) {
+    let parent = match chunk.mesh_entity {
+        Some(entity) => entity,
+        None => return,
+    };
+    let mut old = mem::take(&mut chunk.sublayer_entities);
+    for (layer, new_mesh, material, aabb) in sublayers {
+        let existing = old
+            .iter()
+            .position(|(old_layer, _)| *old_layer == layer)
+            .map(|i| old.swap_remove(i).1);
+        if let Some((entity, (mut mesh, _))) =
+            existing.and_then(|entity| Some((entity, mesh_query.get_mut(entity).ok()?)))
+        {
+            *mesh = new_mesh;
+            set_aabb(commands, entity, aabb);
+            chunk.sublayer_entities.push((layer, entity));
+        } else {
+            let entity = commands
+                .spawn_bundle(MaterialMesh2dBundle {
+                    mesh: new_mesh,
+                    transform: Transform::from_xyz(0.0, 0.0, layer as f32 / 256.0),
+                    material,
+                    ..default()
+                })
+                .id();
+            commands.entity(parent).add_child(entity);
+            set_aabb(commands, entity, aabb);
+            chunk.sublayer_entities.push((layer, entity));
+        }
+    }
+    for (_, entity) in old {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Adds `tile`, at `tile_pos` in the center of `neighborhood`, to `builder`
+fn add_tile_to_mesh<T: Tile>(
+    tile: &T,
+    builder: &mut T::MeshBuilder,
+    neighborhood: &ChunkNeighborhood<T>,
+    tile_pos: ChunkPos,
+) {
+    if !matches!(tile.mesh_template(), Some(template) if builder.add_template(template)) {
+        tile.add_to_mesh_with_neighbors(builder, neighborhood.neighbors(tile_pos));
+    }
+}
+
+/// The tiles in `chunk` and their positions, in the order they are added to meshes
+///
+/// Sorted by [`Tile::z_offset`], keeping storage order for equal offsets
+fn tiles_by_z_offset<T: Tile>(chunk: &Chunk<T>) -> Vec<(ChunkPos, &T)> {
+    let mut tiles: Vec<_> = chunk.iter_tile_positions().collect();
+    tiles.sort_by(|(_, a), (_, b)| a.z_offset().total_cmp(&b.z_offset()));
+    tiles
+}
+
+/// Patches the mesh of the chunk at `chunk_pos` after only the tile at `tile_pos` changed
+///
+/// Returns `false` if the whole mesh must be regenerated instead
+fn update_tile_mesh<T: Tile, M>(
+    tilemap: &mut Tilemap<T, M>,
+    chunk_pos: IVec2,
+    tile_pos: ChunkPos,
+    mesh_handle: &Handle<Mesh>,
+    meshes: &mut Assets<Mesh>,
+) -> bool {
+    let layout = tilemap.layout();
+    let chunk = match tilemap.data.get_mut(&chunk_pos) {
+        // Empty chunks are removed by full regeneration, and sublayer meshes aren't patched.
+        // Patching could move the vertices of animated tiles
+        Some(chunk)
+            if !chunk.is_empty()
+                && chunk.sublayer_entities.is_empty()
+                && chunk.animated_tiles.is_empty() =>
+        {
+            chunk
+        }
+        _ => return false,
+    };
+    if matches!(&chunk[tile_pos], Some(tile) if tile.mesh_layer() != 0 || tile.animation().is_some())
+    {
+        return false;
+    }
+    let mesh = match meshes.get_mut(mesh_handle) {
+        Some(mesh) => mesh,
+        None => return false,
+    };
+    // Taken so the neighborhood can borrow the chunk, and always put back
+    let mut carry_data = mem::take(&mut chunk.mesh_carry_data);
+
+    let neighborhood = ChunkNeighborhood::new(&tilemap.data, chunk_pos);
+    let mut mesh_builder = T::MeshBuilder::init(Default::default());
+    if let Some(tile) = &neighborhood.center()[tile_pos] {
+        mesh_builder.set_offset(layout.tile_offset(tile_pos.as_ivec2()));
+        mesh_builder.set_z_offset(tile.z_offset());
+        add_tile_to_mesh(tile, &mut mesh_builder, &neighborhood, tile_pos);
+    }
+    let patched = mesh_builder.update_tile(mesh, tile_pos, &mut carry_data);
+    tilemap.data.get_mut(&chunk_pos).unwrap().mesh_carry_data = carry_data;
+    patched
+}
+
+/// The smallest box containing every box in `aabbs`, or [`None`] if there are none
+fn aabb_union(aabbs: impl IntoIterator<Item = Aabb>) -> Option<Aabb> {
+    aabbs
+        .into_iter()
+        .map(|aabb| (aabb.min(), aabb.max()))
+        .reduce(|(a_min, a_max), (b_min, b_max)| (a_min.min(b_min), a_max.max(b_max)))
+        .map(|(min, max)| Aabb::from_min_max(min.into(), max.into()))
+}
+
+/// Sets the bounds `entity` is frustum culled with to `aabb`, the bounds of it's mesh
+///
+/// Meshes with no vertices have no bounds, so entities displaying them are never culled
+pub(crate) fn set_aabb(commands: &mut Commands, entity: Entity, aabb: Option<Aabb>) {
+    match aabb {
+        Some(aabb) => commands.entity(entity).insert(aabb),
+        None => commands.entity(entity).remove::<Aabb>(),
+    };
+}
+
+/// The positions of the chunks to regenerate this frame
+///
+/// At most [`Tilemap::max_regens_per_frame()`] chunks, closest to `camera` first.
+/// Chunks regenerated less than their minimum regeneration interval before `now` are skipped
+fn chunks_to_regenerate<T: Tile, M>(
+    tilemap: &Tilemap<T, M>,
+    camera: Option<&GlobalTransform>,
+    now: Duration,
+) -> Vec<IVec2> {
+    let mut dirty: Vec<IVec2> = tilemap
+        .data
+        .iter()
+        .filter(|(_, chunk)| chunk.ready_to_regenerate(now))
+        .map(|(pos, _)| *pos)
+        .collect();
+    if dirty.len() > tilemap.max_regens_per_frame {
+        if let Some(camera) = camera {
+            let camera_chunk = tilemap.world_to_tile(camera.translation().truncate()).chunk;
+            dirty.sort_unstable_by_key(|pos| {
+                let offset = *pos - camera_chunk;
+                offset.x as i64 * offset.x as i64 + offset.y as i64 * offset.y as i64
+            });
+        }
+        dirty.truncate(tilemap.max_regens_per_frame);
+    }
+    dirty
+}
+
+/// The transform of the mesh entity of the chunk at `chunk_pos` with the visual offset
+/// `visual_offset`, in a tilemap at `z`
+fn chunk_transform(layout: TileLayout, z: f32, chunk_pos: IVec2, visual_offset: Vec2) -> Transform {
+    Transform::from_translation(
+        (layout.tile_offset(chunk_pos * CHUNK_SIZE as i32) + visual_offset).extend(z),
+    )
+}
+
+/// A chunk and the 8 chunks surrounding it, used to find the neighbors of tiles
+/// during mesh generation
+struct ChunkNeighborhood<'a, T: Tile> {
+    /// Row-major, starting from the bottom left chunk
+    chunks: [Option<&'a Chunk<T>>; 9],
+}
+
+impl<'a, T: Tile> ChunkNeighborhood<'a, T> {
+    fn new(data: &'a ChunkMap<T>, center: IVec2) -> Self {
+        let mut chunks = [None; 9];
+        for (i, chunk) in chunks.iter_mut().enumerate() {
+            let offset = IVec2::new(i as i32 % 3 - 1, i as i32 / 3 - 1);
+            *chunk = data.get(&(center + offset));
+        }
+        ChunkNeighborhood { chunks }
+    }
+
+    fn center(&self) -> &'a Chunk<T> {
+        self.chunks[4].unwrap()
+    }
+
+    /// Returns the tile at `pos`, relative to the bottom left corner of the center chunk
+    ///
+    /// `pos` must be no more than one chunk away from the center chunk
+    fn get(&self, pos: IVec2) -> Option<&'a T> {
+        let chunk_size = CHUNK_SIZE as i32;
+        let chunk = IVec2::new(pos.x.div_euclid(chunk_size), pos.y.div_euclid(chunk_size));
+        let tile = ChunkPos::new(
+            pos.x.rem_euclid(chunk_size) as u8,
+            pos.y.rem_euclid(chunk_size) as u8,
+        );
+        self.chunks[(chunk.x + 1 + (chunk.y + 1) * 3) as usize]
+            .and_then(|chunk| chunk[tile].as_ref())
+    }
+
+    /// Returns the 8 tiles surrounding `tile` in the center chunk
+    ///
+    /// In the order described by [`Tile::add_to_mesh_with_neighbors`].  Ignores the layout
+    /// of the tilemap, hexagonal neighbors are a subset of these
+    fn neighbors(&self, tile: ChunkPos) -> [Option<&'a T>; 8] {
+        let pos = tile.as_ivec2();
+        Direction::ALL.map(|direction| self.get(pos + direction.offset()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::{animation::TileAnimation, rendering::TileMesh, test_util::*, tilemap::RegenStats};
+
+    #[test]
+    fn patched_mesh_matches_full_rebuild() {
+        let mut app = test_app::<TestTile, _>();
+        for x in 0..4 {
+            tilemap::<TestTile>(&mut app).set(pos(x, 0), TestTile(1));
+        }
+        app.update();
+        tilemap::<TestTile>(&mut app).set(pos(2, 0), TestTile(7));
+        app.update();
+        assert_eq!(
+            full_builds::<TestTile, true>(&app, IVec2::ZERO),
+            1,
+            "the mesh should have been patched"
+        );
+        let patched = mesh_data(chunk_mesh::<TestTile>(&app, IVec2::ZERO).unwrap());
+
+        let mut rebuilt = test_tilemap::<TestTile>();
+        for x in 0..4 {
+            rebuilt.set(pos(x, 0), TestTile(if x == 2 { 7 } else { 1 }));
+        }
+        let lods = build_chunk_mesh(&mut rebuilt, IVec2::ZERO).unwrap();
+        assert_eq!(patched, mesh_data(&lods[0]));
+    }
+
+    #[test]
+    fn regeneration_budget_spreads_chunks_across_frames() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set_max_regens_per_frame(2);
+        for x in 0..5 {
+            tilemap::<TestTile>(&mut app).set(pos(x * 32, 0), TestTile(1));
+        }
+        let meshed = |app: &mut App| {
+            tilemap::<TestTile>(app)
+                .iter_chunks()
+                .filter(|chunk| chunk.mesh_entity.is_some())
+                .count()
+        };
+        app.update();
+        assert_eq!(meshed(&mut app), 2);
+        assert_eq!(tilemap::<TestTile>(&mut app).count_chunks_dirty(), 3);
+        app.update();
+        app.update();
+        assert_eq!(meshed(&mut app), 5);
+        assert_eq!(tilemap::<TestTile>(&mut app).count_chunks_dirty(), 0);
+    }
+
+    #[test]
+    fn failed_patch_keeps_carry_data() {
+        let mut app = test_app::<UnpatchedTile, _>();
+        tilemap::<UnpatchedTile>(&mut app).set(pos(0, 0), UnpatchedTile(1));
+        app.update();
+        tilemap::<UnpatchedTile>(&mut app).set(pos(0, 0), UnpatchedTile(2));
+        app.update();
+        assert_eq!(full_builds::<UnpatchedTile, false>(&app, IVec2::ZERO), 2);
+    }
+
+    #[test]
+    fn reservation_survives_mesh_generation() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set(pos(0, 0), TestTile(1));
+        app.update();
+        let before = tilemap::<TestTile>(&mut app).memory_usage();
+        tilemap::<TestTile>(&mut app).reserve(64);
+        let reserved = tilemap::<TestTile>(&mut app).memory_usage();
+        // Sorted maps don't preallocate
+        assert!(reserved > before || cfg!(feature = "deterministic"));
+        app.update();
+        app.update();
+        let tilemap = tilemap::<TestTile>(&mut app);
+        assert_eq!(tilemap.memory_usage(), reserved);
+        assert_eq!(tilemap.get(pos(0, 0)), Some(&TestTile(1)));
+    }
+
+    #[test]
+    fn visual_offset_moves_mesh_entity() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set(pos(40, 0), TestTile(1));
+        app.update();
+        let translation = |app: &mut App| {
+            let entity = tilemap::<TestTile>(app)
+                .get_chunk(IVec2::X)
+                .unwrap()
+                .mesh_entity
+                .unwrap();
+            app.world.get::<Transform>(entity).unwrap().translation
+        };
+        assert_eq!(translation(&mut app), Vec3::new(32.0, 0.0, 0.0));
+
+        tilemap::<TestTile>(&mut app)
+            .get_chunk_mut(IVec2::X)
+            .unwrap()
+            .set_visual_offset(Vec2::new(0.5, -2.0));
+        app.update();
+        assert_eq!(translation(&mut app), Vec3::new(32.5, -2.0, 0.0));
+        assert_eq!(full_builds::<TestTile, true>(&app, IVec2::X), 1);
+    }
+
+    #[test]
+    fn drain_despawns_every_mesh() {
+        let mut app = test_app::<TestTile, _>();
+        for x in [-40, 0, 40] {
+            tilemap::<TestTile>(&mut app).set(pos(x, 0), TestTile(1));
         }
-    });
-    if tilemap.data.len() * 4 < tilemap.data.capacity() {
-        tilemap.data.shrink_to(tilemap.data.len() + 2);
+        app.update();
+        let entities: Vec<_> = tilemap::<TestTile>(&mut app)
+            .iter_chunks()
+            .filter_map(|chunk| chunk.mesh_entity)
+            .collect();
+        assert_eq!(entities.len(), 3);
+
+        let drained: Vec<_> = tilemap::<TestTile>(&mut app).drain().collect();
+        assert_eq!(drained.len(), 3);
+        app.update();
+        assert_eq!(tilemap::<TestTile>(&mut app).iter().count(), 0);
+        assert_eq!(tilemap::<TestTile>(&mut app).chunk_positions().count(), 0);
+        assert!(entities
+            .into_iter()
+            .all(|entity| app.world.get_entity(entity).is_none()));
+    }
+
+    #[test]
+    fn mesh_bounds_cover_geometry() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set(pos(1, 0), TestTile(1));
+        tilemap::<TestTile>(&mut app).set(pos(3, 2), TestTile(1));
+        app.update();
+        let chunk_aabb = |app: &mut App| {
+            let chunk = tilemap::<TestTile>(app);
+            let chunk = chunk.get_chunk(IVec2::ZERO).unwrap();
+            (
+                chunk.mesh_aabb().min,
+                chunk.mesh_aabb().max,
+                chunk.mesh_entity.unwrap(),
+            )
+        };
+        let (min, max, entity) = chunk_aabb(&mut app);
+        assert_eq!((min, max), (Vec2::new(1.0, 0.0), Vec2::new(4.0, 3.0)));
+        let aabb = app.world.get::<Aabb>(entity).unwrap();
+        assert_eq!(Vec3::from(aabb.min()), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(Vec3::from(aabb.max()), Vec3::new(4.0, 3.0, 0.0));
+
+        tilemap::<TestTile>(&mut app).remove(pos(3, 2));
+        app.update();
+        let (min, max, _) = chunk_aabb(&mut app);
+        assert_eq!((min, max), (Vec2::new(1.0, 0.0), Vec2::new(2.0, 1.0)));
+    }
+
+    /// A tile playing a 3 frame animation, 10 units along the texture per frame
+    #[derive(Clone)]
+    struct AnimatedTestTile(f32);
+
+    impl Tile for AnimatedTestTile {
+        type MeshBuilder = QuadBuilder<true>;
+        type MeshUpdater = NoUpdater;
+
+        fn add_to_mesh(&self, builder: &mut Self::MeshBuilder) {
+            TestTile(1).add_to_mesh(builder);
+        }
+
+        fn animation(&self) -> Option<TileAnimation> {
+            Some(TileAnimation {
+                frame_count: 3,
+                frame_duration: 1.0,
+                phase: self.0,
+                frame_step: Vec2::new(10.0, 0.0),
+            })
+        }
+    }
+
+    #[test]
+    fn animation_offsets_uvs() {
+        let mut app = test_app::<AnimatedTestTile, _>();
+        app.insert_resource(SharedAnimation::new(1.5));
+        tilemap::<AnimatedTestTile>(&mut app).set(pos(0, 0), AnimatedTestTile(0.0));
+        tilemap::<AnimatedTestTile>(&mut app).set(pos(1, 0), AnimatedTestTile(1.0));
+        app.update();
+        let uvs =
+            |app: &App| mesh_data(chunk_mesh::<AnimatedTestTile>(app, IVec2::ZERO).unwrap()).1;
+        let expected = |frames: [f32; 2]| {
+            frames
+                .into_iter()
+                .flat_map(|frame| [[1.0 + 10.0 * frame, 0.0]; 4])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(uvs(&app), expected([1.0, 2.0]));
+
+        app.insert_resource(SharedAnimation::new(2.5));
+        app.update();
+        assert_eq!(uvs(&app), expected([2.0, 0.0]));
+    }
+
+    /// A tile with a mesh template, for builders that don't support templates
+    #[derive(Clone)]
+    struct TemplateTile(TileMesh);
+
+    impl Tile for TemplateTile {
+        type MeshBuilder = QuadBuilder<true>;
+        type MeshUpdater = NoUpdater;
+
+        fn add_to_mesh(&self, builder: &mut Self::MeshBuilder) {
+            TestTile(3).add_to_mesh(builder);
+        }
+
+        fn mesh_template(&self) -> Option<&TileMesh> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn unsupported_template_falls_back_to_add_to_mesh() {
+        let mut tilemap = test_tilemap::<TemplateTile>();
+        tilemap.set(pos(0, 0), TemplateTile(TileMesh::default()));
+        let lods = build_chunk_mesh(&mut tilemap, IVec2::ZERO).unwrap();
+
+        let mut expected = test_tilemap::<TestTile>();
+        expected.set(pos(0, 0), TestTile(3));
+        let expected = build_chunk_mesh(&mut expected, IVec2::ZERO).unwrap();
+        assert_eq!(mesh_data(&lods[0]), mesh_data(&expected[0]));
+    }
+
+    #[test]
+    fn inserted_tilemap_is_kept() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<ColorMaterial>();
+        let material = app
+            .world
+            .resource_mut::<Assets<ColorMaterial>>()
+            .add(ColorMaterial::from(Color::RED));
+        let mut tilemap = Tilemap::<TestTile>::new(material.clone());
+        tilemap.set(pos(3, 4), TestTile(1));
+        app.insert_resource(tilemap)
+            .add_plugin(TilemapPlugin::<TestTile>::new());
+        app.update();
+
+        let tilemap = app.world.resource::<Tilemap<TestTile>>();
+        assert_eq!(tilemap.material(), &material);
+        assert_eq!(tilemap.get(pos(3, 4)), Some(&TestTile(1)));
+        assert!(chunk_mesh::<TestTile>(&app, IVec2::ZERO).is_some());
+    }
+
+    #[test]
+    fn builders_reserve_the_tile_count() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        for x in 0..5 {
+            tilemap.set(pos(x, x), TestTile(0));
+        }
+        tilemap.set(pos(40, 0), TestTile(0));
+        build_chunk_mesh(&mut tilemap, IVec2::ZERO).unwrap();
+        let chunk = tilemap.get_chunk(IVec2::ZERO).unwrap();
+        assert_eq!(chunk.mesh_carry_data.reserved, 5);
+    }
+
+    #[test]
+    fn z_offset_sets_vertex_z() {
+        let mut tilemap = test_tilemap::<LayeredTile>();
+        let tile = |z_offset| LayeredTile {
+            z_offset,
+            ..default()
+        };
+        tilemap.set(pos(0, 0), tile(0.5));
+        tilemap.set(pos(1, 0), tile(-0.25));
+        tilemap.set(pos(2, 0), tile(0.0));
+        let lods = build_chunk_mesh(&mut tilemap, IVec2::ZERO).unwrap();
+
+        // Quads are added from lowest to highest offset
+        let (positions, ..) = mesh_data(&lods[0]);
+        let quads: Vec<_> = positions
+            .chunks(4)
+            .map(|quad| {
+                (
+                    quad[0][0],
+                    quad.iter().map(|pos| pos[2]).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            quads,
+            [
+                (1.0, vec![-0.25; 4]),
+                (2.0, vec![0.0; 4]),
+                (0.0, vec![0.5; 4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn dirty_chunks_last_until_meshes_are_generated() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set(pos(40, -5), TestTile(1));
+        let dirty: Vec<_> = tilemap::<TestTile>(&mut app).dirty_chunks().collect();
+        assert_eq!(dirty, [IVec2::new(1, -1)]);
+
+        app.update();
+        assert_eq!(tilemap::<TestTile>(&mut app).dirty_chunks().count(), 0);
+        assert!(chunk_mesh::<TestTile>(&app, IVec2::new(1, -1)).is_some());
+    }
+
+    #[test]
+    fn regenerate_all_and_cancel_regeneration() {
+        let mut app = test_app::<TestTile, _>();
+        for x in 0..3 {
+            tilemap::<TestTile>(&mut app).set(pos(x * 32 + 5, 5), TestTile(1));
+        }
+        app.update();
+        let builds = |app: &App| {
+            (0..3)
+                .map(|x| full_builds::<TestTile, true>(app, IVec2::new(x, 0)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(builds(&app), [1, 1, 1]);
+
+        tilemap::<TestTile>(&mut app).regenerate_all();
+        assert_eq!(tilemap::<TestTile>(&mut app).dirty_chunks().count(), 3);
+        app.update();
+        assert_eq!(builds(&app), [2, 2, 2]);
+
+        tilemap::<TestTile>(&mut app).set(pos(5, 6), TestTile(2));
+        tilemap::<TestTile>(&mut app).regenerate_all();
+        tilemap::<TestTile>(&mut app).cancel_regeneration();
+        assert_eq!(tilemap::<TestTile>(&mut app).dirty_chunks().count(), 0);
+        app.update();
+        assert_eq!(builds(&app), [2, 2, 2]);
+    }
+
+    #[test]
+    fn chunk_world_transform_matches_mesh_entity() {
+        let mut app = test_app::<TestTile, _>();
+        let mut tiles = tilemap::<TestTile>(&mut app);
+        tiles.set_layout(TileLayout::HexPointyTop);
+        tiles.set_z(3.0);
+        tiles.set(pos(-60, 100), TestTile(1));
+        tiles.set(pos(5, 5), TestTile(1));
+        tiles
+            .get_chunk_mut(IVec2::new(-2, 3))
+            .unwrap()
+            .set_visual_offset(Vec2::new(0.25, 1.0));
+        app.update();
+
+        for chunk_pos in [IVec2::new(-2, 3), IVec2::ZERO] {
+            let tiles = app.world.resource::<Tilemap<TestTile>>();
+            let entity = tiles.get_chunk(chunk_pos).unwrap().mesh_entity.unwrap();
+            assert_eq!(
+                app.world.get::<Transform>(entity),
+                Some(&tiles.chunk_world_transform(chunk_pos)),
+                "chunk {chunk_pos}"
+            );
+        }
+    }
+
+    #[test]
+    fn set_material_swaps_without_regenerating() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set(pos(0, 0), TestTile(1));
+        tilemap::<TestTile>(&mut app).set(pos(40, 0), TestTile(1));
+        app.update();
+
+        let material = app
+            .world
+            .resource_mut::<Assets<ColorMaterial>>()
+            .add(ColorMaterial::from(Color::BLUE));
+        tilemap::<TestTile>(&mut app).set_material(material.clone());
+        app.update();
+
+        for chunk_pos in [IVec2::ZERO, IVec2::X] {
+            let entity = tilemap::<TestTile>(&mut app)
+                .get_chunk(chunk_pos)
+                .unwrap()
+                .mesh_entity
+                .unwrap();
+            let handle = app.world.get::<Handle<ColorMaterial>>(entity);
+            assert_eq!(handle, Some(&material));
+            assert_eq!(full_builds::<TestTile, true>(&app, chunk_pos), 1);
+        }
+    }
+
+    #[test]
+    fn regen_stats_count_regenerated_chunks() {
+        let mut app = test_app::<TestTile, _>();
+        let stats = |app: &mut App| tilemap::<TestTile>(app).regen_stats();
+        assert_eq!(stats(&mut app), RegenStats::default());
+
+        for x in 0..3 {
+            tilemap::<TestTile>(&mut app).set(pos(x * 32 + 5, 5), TestTile(1));
+        }
+        app.update();
+        assert_eq!(
+            stats(&mut app),
+            RegenStats {
+                last_frame: 3,
+                total: 3
+            }
+        );
+
+        tilemap::<TestTile>(&mut app).set(pos(5, 5), TestTile(2));
+        app.update();
+        assert_eq!(
+            stats(&mut app),
+            RegenStats {
+                last_frame: 1,
+                total: 4
+            }
+        );
+
+        app.update();
+        assert_eq!(
+            stats(&mut app),
+            RegenStats {
+                last_frame: 0,
+                total: 4
+            }
+        );
+    }
+
+    #[test]
+    fn mesh_layers_get_child_entities() {
+        let mut app = test_app::<LayeredTile, _>();
+        let layer = |mesh_layer| LayeredTile {
+            mesh_layer,
+            ..default()
+        };
+        let mut tiles = tilemap::<LayeredTile>(&mut app);
+        tiles.set(pos(0, 0), layer(0));
+        tiles.set(pos(1, 0), layer(1));
+        tiles.set(pos(2, 0), layer(2));
+        tiles.set(pos(3, 0), layer(2));
+        app.update();
+
+        let chunk = app
+            .world
+            .resource::<Tilemap<LayeredTile>>()
+            .get_chunk(IVec2::ZERO)
+            .unwrap();
+        let parent = chunk.mesh_entity.unwrap();
+        let mut sublayers = chunk.sublayer_entities.clone();
+        sublayers.sort();
+        assert_eq!(
+            sublayers
+                .iter()
+                .map(|(layer, _)| *layer)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        let meshes = app.world.resource::<Assets<Mesh>>();
+        for (layer, entity) in sublayers {
+            assert_eq!(
+                app.world.get::<Parent>(entity).map(Parent::get),
+                Some(parent)
+            );
+            let handle = app.world.get::<Mesh2dHandle>(entity).unwrap();
+            let vertices = meshes.get(&handle.0).unwrap().count_vertices();
+            assert_eq!(vertices, layer as usize * 4, "layer {layer}");
+        }
+        assert_eq!(
+            chunk_mesh::<LayeredTile>(&app, IVec2::ZERO)
+                .unwrap()
+                .count_vertices(),
+            4
+        );
+    }
+
+    #[test]
+    fn lazily_created_chunks_get_no_mesh_entity() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).get_or_create_chunk_lazy(IVec2::new(2, -1));
+        app.update();
+
+        let tilemap = app.world.resource::<Tilemap<TestTile>>();
+        let chunk = tilemap.get_chunk(IVec2::new(2, -1)).unwrap();
+        assert!(chunk.mesh_entity.is_none());
+        assert!(!chunk.regenerate_mesh);
+        assert_eq!(
+            app.world.query::<&Mesh2dHandle>().iter(&app.world).count(),
+            0
+        );
     }
 }
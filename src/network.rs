@@ -0,0 +1,298 @@
+//! Compact encoding of tilemaps for sending over the network
+//!
+//! The wire format is versioned and doesn't depend on the layout of types in this crate.
+//! All numbers are little endian:
+//!
+//! - The format version, a `u8`
+//! - The number of chunks, a `u32`
+//! - For each chunk, it's x and y positions as `i32`s, followed by runs of tile slots in
+//!   row-major order, starting from the bottom left, until every slot in the chunk is covered.
+//!   Each run is the number of slots in it as a `u16`, then the slot value as a `u32`, which
+//!   is 0 for empty slots or one more than the id of the tile
+
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::{
+    tile::Tile,
+    tilemap::{on_chunk_edge, ChunkPos, IRect, Tilemap, TilemapPos},
+    CHUNK_SIZE,
+};
+
+/// The version of the wire format written by this crate
+pub const NETWORK_FORMAT_VERSION: u8 = 1;
+
+/// The reasons applying network bytes to a tilemap can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkError {
+    /// The bytes use this version of the wire format, which isn't supported
+    Version(u8),
+    /// The bytes end in the middle of a value
+    Truncated,
+    /// A chunk has runs covering more slots than it has
+    Overflow,
+    /// A slot value is too large to be an empty slot or a tile id
+    InvalidValue(u32),
+    /// There are bytes left after the last chunk
+    TrailingBytes,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Version(version) => write!(
+                f,
+                "unsupported network format version {version} (expected {NETWORK_FORMAT_VERSION})"
+            ),
+            NetworkError::Truncated => write!(f, "network bytes are truncated"),
+            NetworkError::Overflow => write!(f, "chunk has more tiles than fit in a chunk"),
+            NetworkError::InvalidValue(value) => write!(f, "invalid tile slot value {value}"),
+            NetworkError::TrailingBytes => write!(f, "unexpected bytes after the last chunk"),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl<T: Tile, M> Tilemap<T, M> {
+    /// Encodes every chunk in this to send over the network
+    ///
+    /// `tile_to_id` is called with each tile, and the id it returns is what's sent.  See the
+    /// [module docs](self) for the format.  Only tiles are sent, not metadata or chunk settings
+    #[must_use]
+    pub fn to_network_bytes(&self, tile_to_id: impl Fn(&T) -> u16) -> Vec<u8> {
+        let chunks: Vec<_> = self.chunk_positions().collect();
+        self.encode_chunks(&chunks, tile_to_id)
+    }
+
+    /// Encodes every chunk overlapping `bounds` to send over the network, including chunks that
+    /// don't exist
+    ///
+    /// Whole chunks are sent, and chunks that don't exist are sent as empty, so applying the
+    /// bytes clears them on the receiving end.  Otherwise the same as
+    /// [`to_network_bytes()`](Self::to_network_bytes())
+    #[must_use]
+    pub fn region_to_network_bytes(
+        &self,
+        bounds: IRect,
+        tile_to_id: impl Fn(&T) -> u16,
+    ) -> Vec<u8> {
+        let chunks: Vec<_> = match bounds.is_empty() {
+            true => Vec::new(),
+            false => bounds.chunks().iter_positions().collect(),
+        };
+        self.encode_chunks(&chunks, tile_to_id)
+    }
+
+    fn encode_chunks(&self, chunks: &[IVec2], tile_to_id: impl Fn(&T) -> u16) -> Vec<u8> {
+        let mut bytes = vec![NETWORK_FORMAT_VERSION];
+        bytes.extend((chunks.len() as u32).to_le_bytes());
+        for &chunk_pos in chunks {
+            bytes.extend(chunk_pos.x.to_le_bytes());
+            bytes.extend(chunk_pos.y.to_le_bytes());
+            let chunk = self.get_chunk(chunk_pos);
+            let values = ChunkPos::iter_positions().map(|tile_pos| {
+                chunk
+                    .and_then(|chunk| chunk[tile_pos].as_ref())
+                    .map_or(0, |tile| tile_to_id(tile) as u32 + 1)
+            });
+            let mut run: Option<(u16, u32)> = None;
+            for value in values {
+                run = match run {
+                    Some((len, run_value)) if run_value == value => Some((len + 1, value)),
+                    _ => {
+                        write_run(&mut bytes, run);
+                        Some((1, value))
+                    }
+                };
+            }
+            write_run(&mut bytes, run);
+        }
+        bytes
+    }
+
+    /// Replaces the tiles in every chunk in `bytes` with the tiles they contain
+    ///
+    /// `tile_from_id` is called with the id of each tile, and the tile it returns (if any) is
+    /// placed there.  Nothing is changed if `bytes` aren't valid.  Tells each chunk in `bytes`
+    /// to regenerate it's mesh the next time it's displayed, along with the chunks sharing an
+    /// edge with them.  Chunks not in `bytes`, and
+    /// positions outside of the [bounds](Self::bounds()) of this, aren't changed
+    pub fn apply_network_bytes(
+        &mut self,
+        bytes: &[u8],
+        tile_from_id: impl Fn(u16) -> Option<T>,
+    ) -> Result<(), NetworkError> {
+        let mut reader = Reader(bytes);
+        let version = reader.u8()?;
+        if version != NETWORK_FORMAT_VERSION {
+            return Err(NetworkError::Version(version));
+        }
+        let chunk_count = reader.u32()?;
+        let mut chunks = Vec::new();
+        for _ in 0..chunk_count {
+            let chunk_pos = IVec2::new(reader.i32()?, reader.i32()?);
+            let mut values = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE);
+            while values.len() < CHUNK_SIZE * CHUNK_SIZE {
+                let (len, value) = (reader.u16()? as usize, reader.u32()?);
+                if values.len() + len > CHUNK_SIZE * CHUNK_SIZE {
+                    return Err(NetworkError::Overflow);
+                }
+                if value > u16::MAX as u32 + 1 {
+                    return Err(NetworkError::InvalidValue(value));
+                }
+                values.resize(values.len() + len, value);
+            }
+            chunks.push((chunk_pos, values));
+        }
+        if !reader.0.is_empty() {
+            return Err(NetworkError::TrailingBytes);
+        }
+
+        let mut edges = Vec::new();
+        for (chunk_pos, values) in chunks {
+            let in_bounds: Vec<bool> = ChunkPos::iter_positions()
+                .map(|tile| {
+                    self.in_bounds(TilemapPos {
+                        chunk: chunk_pos,
+                        tile,
+                    })
+                })
+                .collect();
+            if !in_bounds.contains(&true) {
+                continue;
+            }
+            let chunk = self.get_or_create_chunk(chunk_pos);
+            for ((tile_pos, value), in_bounds) in
+                ChunkPos::iter_positions().zip(values).zip(in_bounds)
+            {
+                if in_bounds {
+                    let tile = value.checked_sub(1).and_then(|id| tile_from_id(id as u16));
+                    let changed = tile.is_some() || chunk.is_set(tile_pos);
+                    chunk.set_raw(tile_pos, tile);
+                    if changed && on_chunk_edge(tile_pos) {
+                        edges.push(TilemapPos {
+                            chunk: chunk_pos,
+                            tile: tile_pos,
+                        });
+                    }
+                }
+            }
+            chunk.regenerate_mesh();
+        }
+        self.regenerate_neighbor_chunks(edges);
+        Ok(())
+    }
+}
+
+fn write_run(bytes: &mut Vec<u8>, run: Option<(u16, u32)>) {
+    if let Some((len, value)) = run {
+        bytes.extend(len.to_le_bytes());
+        bytes.extend(value.to_le_bytes());
+    }
+}
+
+/// Reads little endian values from the front of a byte slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], NetworkError> {
+        if self.0.len() < N {
+            return Err(NetworkError::Truncated);
+        }
+        let (value, rest) = self.0.split_at(N);
+        self.0 = rest;
+        Ok(value.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, NetworkError> {
+        self.take().map(u8::from_le_bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, NetworkError> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, NetworkError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, NetworkError> {
+        self.take().map(i32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    fn to_id(tile: &TestTile) -> u16 {
+        tile.0 as u16
+    }
+
+    fn from_id(id: u16) -> Option<TestTile> {
+        Some(TestTile(id as u8))
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut sent = test_tilemap::<TestTile>();
+        sent.set(pos(0, 0), TestTile(0));
+        sent.set(pos(1, 0), TestTile(0));
+        sent.set(pos(5, 9), TestTile(7));
+        sent.set(pos(-40, 70), TestTile(255));
+
+        let mut received = test_tilemap::<TestTile>();
+        received
+            .apply_network_bytes(&sent.to_network_bytes(to_id), from_id)
+            .unwrap();
+        assert!(sent.diff(&received).is_empty());
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut sent = test_tilemap::<TestTile>();
+        sent.set(pos(0, 0), TestTile(1));
+        let mut bytes = sent.to_network_bytes(to_id);
+        bytes[0] = NETWORK_FORMAT_VERSION + 1;
+
+        let mut received = test_tilemap::<TestTile>();
+        assert_eq!(
+            received.apply_network_bytes(&bytes, from_id),
+            Err(NetworkError::Version(NETWORK_FORMAT_VERSION + 1))
+        );
+        assert_eq!(received.chunk_positions().count(), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_bytes() {
+        let mut sent = test_tilemap::<TestTile>();
+        sent.set(pos(0, 0), TestTile(1));
+        let bytes = sent.to_network_bytes(to_id);
+        let mut received = test_tilemap::<TestTile>();
+
+        assert_eq!(
+            received.apply_network_bytes(&bytes[..bytes.len() - 1], from_id),
+            Err(NetworkError::Truncated)
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            received.apply_network_bytes(&trailing, from_id),
+            Err(NetworkError::TrailingBytes)
+        );
+        assert_eq!(received.chunk_positions().count(), 0);
+    }
+
+    #[test]
+    fn region_clears_missing_chunks() {
+        let sent = test_tilemap::<TestTile>();
+        let mut received = test_tilemap::<TestTile>();
+        received.set(pos(3, 3), TestTile(1));
+        let bytes = sent.region_to_network_bytes(IRect::new(IVec2::ZERO, IVec2::ONE), to_id);
+        received.apply_network_bytes(&bytes, from_id).unwrap();
+        assert_eq!(received.get(pos(3, 3)), None);
+    }
+}
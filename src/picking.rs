@@ -0,0 +1,149 @@
+//! Finding the tile under the mouse cursor
+
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, render::camera::RenderTarget};
+
+use crate::{
+    tile::Tile,
+    tilemap::{Tilemap, TilemapPos},
+};
+
+/// The position of the tile under the cursor in the primary window, or [`None`] if the
+/// cursor is outside of the window
+///
+/// Updated every frame by [`TilePickingPlugin`], before [`CoreStage::Update`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HoveredTile(pub Option<TilemapPos>);
+
+/// The Bevy plugin keeping [`HoveredTile`] up to date for a [`Tilemap`] with a specific
+/// tile type
+///
+/// Uses the first active camera rendering to the primary window.  Only one of these
+/// should be added, since all of them write to the same resource
+#[derive(Debug)]
+pub struct TilePickingPlugin<T: Tile> {
+    _phantom: PhantomData<Tilemap<T>>,
+}
+
+impl<T: Tile> TilePickingPlugin<T> {
+    /// Creates a new plugin for the given tilemap
+    pub fn new() -> Self {
+        TilePickingPlugin {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Tile> Default for TilePickingPlugin<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Tile> Plugin for TilePickingPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredTile>()
+            .add_system_to_stage(CoreStage::PreUpdate, hovered_tile_system::<T>);
+    }
+}
+
+/// Converts a cursor position to world space
+///
+/// `cursor` is in logical pixels from the bottom left corner of a window `window_size`
+/// logical pixels in size
+#[must_use]
+pub fn cursor_to_world(
+    cursor: Vec2,
+    window_size: Vec2,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Vec2 {
+    let ndc = cursor / window_size * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    ndc_to_world.project_point3(ndc.extend(-1.0)).truncate()
+}
+
+fn hovered_tile_system<T: Tile>(
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    tilemap: Option<Res<Tilemap<T>>>,
+    mut hovered: ResMut<HoveredTile>,
+) {
+    let tile = tilemap.and_then(|tilemap| hovered_tile(&windows, &camera_query, &tilemap));
+    if hovered.0 != tile {
+        hovered.0 = tile;
+    }
+}
+
+fn hovered_tile<T: Tile>(
+    windows: &Windows,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+    tilemap: &Tilemap<T>,
+) -> Option<TilemapPos> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let (camera, camera_transform) = camera_query.iter().find(|(camera, _)| {
+        camera.is_active && matches!(camera.target, RenderTarget::Window(id) if id == window.id())
+    })?;
+    let window_size = Vec2::new(window.width(), window.height());
+    let world = cursor_to_world(cursor, window_size, camera, camera_transform);
+    Some(tilemap.world_to_tile(world))
+}
+#[cfg(test)]
+mod tests {
+    use bevy::{math::DVec2, window::WindowId};
+    use raw_window_handle::{RawWindowHandle, WebHandle};
+
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn cursor_maps_through_camera() {
+        let camera = Camera::default();
+        let transform = GlobalTransform::from_translation(Vec3::new(10.0, -4.0, 0.0));
+        let window_size = Vec2::new(200.0, 100.0);
+        let world = |cursor| cursor_to_world(cursor, window_size, &camera, &transform);
+        assert_eq!(world(Vec2::new(100.0, 50.0)), Vec2::new(10.0, -4.0));
+        assert_eq!(world(Vec2::new(200.0, 0.0)), Vec2::new(11.0, -5.0));
+    }
+
+    #[test]
+    fn hovered_tile_follows_cursor() {
+        let mut windows = Windows::default();
+        windows.add(Window::new(
+            WindowId::primary(),
+            &WindowDescriptor::default(),
+            200,
+            100,
+            1.0,
+            None,
+            RawWindowHandle::Web(WebHandle::empty()),
+        ));
+        let mut app = App::new();
+        app.insert_resource(windows)
+            .insert_resource(test_tilemap::<TestTile>())
+            .add_plugin(TilePickingPlugin::<TestTile>::new());
+        app.world.spawn().insert_bundle((
+            Camera::default(),
+            GlobalTransform::from_translation(Vec3::new(10.0, -4.0, 0.0)),
+        ));
+        let set_cursor = |app: &mut App, cursor: Option<DVec2>| {
+            let mut windows = app.world.resource_mut::<Windows>();
+            let window = windows.get_primary_mut().unwrap();
+            window.update_cursor_physical_position_from_backend(cursor);
+            app.update();
+            *app.world.resource::<HoveredTile>()
+        };
+
+        assert_eq!(
+            set_cursor(&mut app, Some(DVec2::new(150.0, 75.0))),
+            HoveredTile(Some(pos(10, -4)))
+        );
+        assert_eq!(
+            set_cursor(&mut app, Some(DVec2::new(0.0, 0.0))),
+            HoveredTile(Some(pos(9, -5)))
+        );
+        assert_eq!(set_cursor(&mut app, None), HoveredTile(None));
+    }
+}
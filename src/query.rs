@@ -0,0 +1,99 @@
+//! Ergonomic access to tilemaps from systems
+
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    tile::Tile,
+    tilemap::{Tilemap, TilemapPos},
+};
+
+/// A [`SystemParam`] giving mutable access to a [`Tilemap`]
+///
+/// Dereferences to the tilemap, so all of it's methods (such as
+/// [`get`](Tilemap::get) and [`set`](Tilemap::set)) can be called directly
+#[derive(SystemParam)]
+pub struct TileQuery<'w, 's, T: Tile> {
+    tilemap: ResMut<'w, Tilemap<T>>,
+    #[system_param(ignore)]
+    _phantom: PhantomData<&'s ()>,
+}
+
+impl<'w, 's, T: Tile> TileQuery<'w, 's, T> {
+    /// Returns a reference to the tile covering the point `world` if it exists
+    ///
+    /// `world` is relative to the origin of the tilemap
+    #[must_use]
+    pub fn at(&self, world: Vec2) -> Option<&T> {
+        self.tilemap.get(self.tilemap.world_to_tile(world))
+    }
+
+    /// Returns a mutable reference to the tile covering the point `world` if it exists
+    ///
+    /// `world` is relative to the origin of the tilemap.
+    /// If mutating the tile results in a change that requires regenerating the chunk mesh,
+    /// call [`regenerate_mesh()`](crate::tilemap::Chunk::regenerate_mesh()) on the chunk
+    #[must_use]
+    pub fn at_mut(&mut self, world: Vec2) -> Option<&mut T> {
+        let pos = self.tilemap.world_to_tile(world);
+        self.tilemap.get_mut(pos)
+    }
+
+    /// Returns an iterator over the tiles adjacent to `pos` and their positions,
+    /// according to the [layout](Tilemap::layout()) of the tilemap
+    ///
+    /// Empty positions are skipped
+    pub fn neighbors(&self, pos: TilemapPos) -> impl Iterator<Item = (TilemapPos, &T)> {
+        self.tilemap
+            .neighbor_positions(pos)
+            .filter_map(|pos| self.tilemap.get(pos).map(|tile| (pos, tile)))
+    }
+}
+
+impl<'w, 's, T: Tile> Deref for TileQuery<'w, 's, T> {
+    type Target = Tilemap<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tilemap
+    }
+}
+
+impl<'w, 's, T: Tile> DerefMut for TileQuery<'w, 's, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tilemap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn at_and_neighbors() {
+        let mut world = World::new();
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(0, 0), TestTile(1));
+        tilemap.set(pos(1, 0), TestTile(2));
+        tilemap.set(pos(1, 1), TestTile(3));
+        world.insert_resource(tilemap);
+
+        let mut system = IntoSystem::into_system(|mut query: TileQuery<TestTile>| {
+            assert_eq!(query.at(Vec2::new(1.5, 0.5)), Some(&TestTile(2)));
+            assert_eq!(query.at(Vec2::new(-0.5, 0.5)), None);
+            *query.at_mut(Vec2::new(0.5, 0.5)).unwrap() = TestTile(4);
+            let neighbors: Vec<_> = query.neighbors(pos(0, 0)).collect();
+            assert_eq!(neighbors, [(pos(1, 0), &TestTile(2))]);
+        });
+        system.initialize(&mut world);
+        system.run((), &mut world);
+        assert_eq!(
+            world.resource::<Tilemap<TestTile>>().get(pos(0, 0)),
+            Some(&TestTile(4))
+        );
+    }
+}
@@ -2,9 +2,17 @@
 
 use std::fmt::Debug;
 
-use bevy::{prelude::*, sprite::Material2d};
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+    sprite::{Material2d, Rect},
+};
+
+use crate::tilemap::ChunkPos;
 
 /// Trait for types used to build meshes for tilemap [`Chunk`](crate::tilemap::Chunk)s
+///
+/// [`VertexBuffer`] can be used to store vertices and turn them into a mesh
 pub trait MeshBuilder {
     /// Saved after mesh generation is finished and
     /// passed back when mesh generation begins again
@@ -31,9 +39,301 @@ pub trait MeshBuilder {
     /// Sets the offset at which to add the next few tile meshes
     fn set_offset(&mut self, offset: Vec2);
 
+    /// Sets the z offset, relative to the chunk, of the next few tile meshes
+    ///
+    /// Called with [`Tile::z_offset`](crate::tile::Tile::z_offset) after
+    /// [`set_offset()`](Self::set_offset()).  Default implementation does nothing
+    #[allow(unused_variables)]
+    fn set_z_offset(&mut self, z_offset: f32) {}
+
+    /// Adds `template` to the mesh at the current offset, returning `false` if this doesn't
+    /// support templates
+    ///
+    /// Called for tiles returning a template from
+    /// [`Tile::mesh_template`](crate::tile::Tile::mesh_template).  If this returns `false`,
+    /// [`Tile::add_to_mesh_with_neighbors`](crate::tile::Tile::add_to_mesh_with_neighbors)
+    /// is called instead.  Default implementation adds nothing and returns `false`
+    #[allow(unused_variables)]
+    fn add_template(&mut self, template: &TileMesh) -> bool {
+        false
+    }
+
+    /// The number of vertices added to this so far, or [`None`] if this doesn't count them
+    ///
+    /// Used to find the vertices of tiles with an
+    /// [animation](crate::tile::Tile::animation).  Must count vertices in the order they are
+    /// in the mesh from [`finish()`](Self::finish()).  Default implementation returns
+    /// [`None`], so no tiles are animated
+    fn vertex_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Called with the number of tiles in the chunk before they are added to this
+    ///
+    /// Can be used to allocate buffers of the right size up front.
+    /// Default implementation does nothing
+    #[allow(unused_variables)]
+    fn reserve(&mut self, tile_count: usize) {}
+
     /// Finishes mesh generation.
     ///
     /// Returns the generated mesh and the new carry data.  This function will not be called
     /// every time a MeshBuilder is created
     fn finish(self) -> (Mesh, Self::CarryData);
+
+    /// Finishes mesh generation, generating the mesh at multiple levels of detail
+    ///
+    /// Returns the generated meshes, from most to least detailed, and the new carry data.
+    /// Must return at least one mesh; the first is the one displayed by default, and the
+    /// others are displayed based on
+    /// [`Tilemap::set_lod_distances()`](crate::tilemap::Tilemap::set_lod_distances()).
+    /// Called instead of [`finish()`](Self::finish()) during full mesh generation.  Default
+    /// implementation calls [`finish()`](Self::finish()), producing a single level of detail
+    fn finish_with_lods(self) -> (Vec<Mesh>, Self::CarryData)
+    where
+        Self: Sized,
+    {
+        let (mesh, carry_data) = self.finish();
+        (vec![mesh], carry_data)
+    }
+
+    /// Patches the vertices of the tile at `pos` in `mesh`, the mesh previously generated for
+    /// the chunk, instead of regenerating the whole mesh
+    ///
+    /// Called instead of [`finish()`](Self::finish()) when the tile at `pos` is the only tile
+    /// in the chunk that changed and the chunk has a single level of detail; the new tile (if
+    /// there is one) has been added to this the same way as during full mesh generation.
+    /// This is created with the default carry data, and the carry data of the chunk is passed
+    /// as `carry_data` instead.  Returns `false` if the mesh couldn't be patched, in which
+    /// case the whole mesh is regenerated, starting from `carry_data`.
+    ///
+    /// Default implementation returns `false`.  Builders for tiles whose meshes depend on
+    /// their neighbors should not patch meshes, since neighboring tiles may need updating too
+    #[allow(unused_variables)]
+    fn update_tile(self, mesh: &mut Mesh, pos: ChunkPos, carry_data: &mut Self::CarryData) -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+}
+
+/// Precomputed geometry for a single tile, for tiles that aren't quads
+///
+/// Positions are relative to the offset of the tile, and indices are into `positions`
+/// and `uvs`.  See [`Tile::mesh_template`](crate::tile::Tile::mesh_template)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TileMesh {
+    /// The position of each vertex
+    pub positions: Vec<[f32; 2]>,
+    /// The UV coordinates of each vertex
+    pub uvs: Vec<[f32; 2]>,
+    /// The vertices of each triangle, counterclockwise
+    pub indices: Vec<u32>,
+}
+
+impl TileMesh {
+    /// The number of vertices in this
+    #[must_use]
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+}
+
+/// A vertex that can be stored in a [`VertexBuffer`]
+///
+/// Describes how the vertex maps to the standard mesh attributes
+pub trait Vertex: Copy {
+    /// The position of this, relative to the chunk
+    fn position(&self) -> [f32; 3];
+
+    /// The UV coordinates of this
+    fn uv(&self) -> [f32; 2];
+
+    /// The color of this, if it has one
+    ///
+    /// If any vertex in a mesh has a color, vertices without one are white.  Default
+    /// implementation returns [`None`]
+    fn color(&self) -> Option<[f32; 4]> {
+        None
+    }
+}
+
+/// A [`Vertex`] with only a position and UV coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TexturedVertex {
+    /// The position of this, relative to the chunk
+    pub position: [f32; 3],
+    /// The UV coordinates of this
+    pub uv: [f32; 2],
+}
+
+impl Vertex for TexturedVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn uv(&self) -> [f32; 2] {
+        self.uv
+    }
+}
+
+/// Vertices and triangles collected by a [`MeshBuilder`], which can be turned into a mesh
+/// with the standard attributes
+///
+/// Builders can store one of these and return [`into_mesh()`](Self::into_mesh()) from
+/// [`MeshBuilder::finish`] instead of packing attributes themselves
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexBuffer<V: Vertex> {
+    vertices: Vec<V>,
+    indices: Vec<u32>,
+}
+
+impl<V: Vertex> VertexBuffer<V> {
+    /// Creates an empty buffer
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty buffer with room for `vertices` vertices
+    #[must_use]
+    pub fn with_capacity(vertices: usize) -> Self {
+        VertexBuffer {
+            vertices: Vec::with_capacity(vertices),
+            indices: Vec::with_capacity(vertices / 2 * 3),
+        }
+    }
+
+    /// The number of vertices in this
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    /// Returns `true` if there are no vertices in this
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Adds `vertex` to this, returning it's index
+    pub fn push_vertex(&mut self, vertex: V) -> u32 {
+        self.vertices.push(vertex);
+        self.vertices.len() as u32 - 1
+    }
+
+    /// Adds a triangle between the vertices at `indices`, counterclockwise
+    pub fn push_triangle(&mut self, indices: [u32; 3]) {
+        self.indices.extend(indices);
+    }
+
+    /// Adds a quad with the corners `vertices`, counterclockwise from the bottom left
+    pub fn push_quad(&mut self, vertices: [V; 4]) {
+        let [a, b, c, d] = vertices.map(|vertex| self.push_vertex(vertex));
+        self.indices.extend([a, b, c, a, c, d]);
+    }
+
+    /// Removes every vertex and triangle from this, keeping the allocated memory
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    /// Creates a triangle list mesh with position, normal and UV attributes, and a color
+    /// attribute if any vertex has a color
+    #[must_use]
+    pub fn into_mesh(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        let positions: Vec<_> = self.vertices.iter().map(Vertex::position).collect();
+        let uvs: Vec<_> = self.vertices.iter().map(Vertex::uv).collect();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            vec![[0.0, 0.0, 1.0]; self.vertices.len()],
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        if self.vertices.iter().any(|vertex| vertex.color().is_some()) {
+            let colors: Vec<_> = self
+                .vertices
+                .iter()
+                .map(|vertex| vertex.color().unwrap_or([1.0; 4]))
+                .collect();
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        }
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+impl<V: Vertex> Default for VertexBuffer<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shrinks the UV rectangle of a tile by `inset` on every side
+///
+/// Used by [`MeshBuilder`]s that sample from a texture atlas, to prevent bilinear sampling
+/// from bleeding in the edges of neighboring tiles.  `inset` is in UV units; half a texel
+/// (`0.5 / atlas_size`) is usually enough.  Works with flipped rectangles, and never
+/// shrinks a rectangle past it's center
+#[must_use]
+pub fn inset_uvs(uvs: Rect, inset: Vec2) -> Rect {
+    let size = uvs.max - uvs.min;
+    let inset = inset.min(size.abs() / 2.0) * size.signum();
+    Rect {
+        min: uvs.min + inset,
+        max: uvs.max - inset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::mesh_data;
+
+    fn vertex(x: f32, y: f32) -> TexturedVertex {
+        TexturedVertex {
+            position: [x, y, 0.0],
+            uv: [x, y],
+        }
+    }
+
+    #[test]
+    fn vertex_buffer_builds_quads() {
+        let mut buffer = VertexBuffer::new();
+        buffer.push_vertex(vertex(5.0, 5.0));
+        buffer.clear();
+        assert!(buffer.is_empty());
+        buffer.push_quad([
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+            vertex(0.0, 1.0),
+        ]);
+        assert_eq!(buffer.len(), 4);
+
+        let mesh = buffer.into_mesh();
+        let (positions, uvs, indices) = mesh_data(&mesh);
+        assert_eq!(positions.len(), 4);
+        assert_eq!(uvs[2], [1.0, 1.0]);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_COLOR).is_none());
+    }
+
+    #[test]
+    fn inset_uvs_never_crosses_center() {
+        let uvs = Rect {
+            min: Vec2::new(0.0, 1.0),
+            max: Vec2::new(0.5, 0.0),
+        };
+        let inset = inset_uvs(uvs, Vec2::splat(0.1));
+        assert_eq!(inset.min, Vec2::new(0.1, 0.9));
+        assert_eq!(inset.max, Vec2::new(0.4, 0.1));
+
+        let collapsed = inset_uvs(uvs, Vec2::splat(1.0));
+        assert_eq!(collapsed.min, collapsed.max);
+        assert_eq!(collapsed.min, Vec2::new(0.25, 0.5));
+    }
 }
@@ -0,0 +1,242 @@
+//! Tiles and helpers shared by the tests of this crate
+
+use bevy::{
+    asset::AssetPlugin,
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+    sprite::Mesh2dHandle,
+};
+
+use crate::{
+    animation::MeshUpdater,
+    rendering::{MeshBuilder, TexturedVertex, VertexBuffer},
+    tile::Tile,
+    tilemap::{ChunkPos, Tilemap, TilemapPos},
+    TilemapPlugin,
+};
+
+/// A tile displayed as a unit quad, with it's id as the u coordinate of every vertex
+///
+/// It's mesh builder patches meshes when a tile is replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct TestTile(pub u8);
+
+/// The same as [`TestTile`], but it's mesh builder never patches meshes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnpatchedTile(pub u8);
+
+/// A tile displayed the same as [`UnpatchedTile`], with a configurable z offset, mesh layer
+/// and whether it blocks light
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LayeredTile {
+    pub z_offset: f32,
+    pub mesh_layer: u8,
+    pub blocks_light: bool,
+}
+
+pub struct NoUpdater;
+
+impl MeshUpdater for NoUpdater {}
+
+impl Tile for TestTile {
+    type MeshBuilder = QuadBuilder<true>;
+    type MeshUpdater = NoUpdater;
+
+    fn add_to_mesh(&self, builder: &mut Self::MeshBuilder) {
+        builder.add_quad(self.0);
+    }
+
+    fn visually_eq(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+impl Tile for UnpatchedTile {
+    type MeshBuilder = QuadBuilder<false>;
+    type MeshUpdater = NoUpdater;
+
+    fn add_to_mesh(&self, builder: &mut Self::MeshBuilder) {
+        builder.add_quad(self.0);
+    }
+}
+
+impl Tile for LayeredTile {
+    type MeshBuilder = QuadBuilder<false>;
+    type MeshUpdater = NoUpdater;
+
+    fn add_to_mesh(&self, builder: &mut Self::MeshBuilder) {
+        builder.add_quad(0);
+    }
+
+    fn z_offset(&self) -> f32 {
+        self.z_offset
+    }
+
+    fn blocks_light(&self) -> bool {
+        self.blocks_light
+    }
+
+    fn mesh_layer(&self) -> u8 {
+        self.mesh_layer
+    }
+}
+
+/// Carry data recording how the mesh of a chunk was built
+#[derive(Debug, Default)]
+pub struct QuadCarryData {
+    /// The number of times the whole mesh was built
+    pub full_builds: usize,
+    /// The positions of the quads in the mesh, in the order they were added
+    pub quads: Vec<ChunkPos>,
+    /// The tile count passed to [`MeshBuilder::reserve()`] in the last full build
+    pub reserved: usize,
+}
+
+/// Builds a quad for each tile, only supporting square layouts
+pub struct QuadBuilder<const PATCH: bool> {
+    buffer: VertexBuffer<TexturedVertex>,
+    offset: Vec2,
+    z_offset: f32,
+    carry_data: QuadCarryData,
+    added: Vec<(ChunkPos, u8)>,
+}
+
+impl<const PATCH: bool> QuadBuilder<PATCH> {
+    fn add_quad(&mut self, id: u8) {
+        let pos = ChunkPos::new(self.offset.x as u8, self.offset.y as u8);
+        self.added.push((pos, id));
+        let uv = [id as f32, 0.0];
+        self.buffer
+            .push_quad(
+                [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y].map(|corner| TexturedVertex {
+                    position: (self.offset + corner).extend(self.z_offset).into(),
+                    uv,
+                }),
+            );
+    }
+}
+
+impl<const PATCH: bool> MeshBuilder for QuadBuilder<PATCH> {
+    type CarryData = QuadCarryData;
+    type Material = ColorMaterial;
+
+    fn material() -> Self::Material {
+        ColorMaterial::default()
+    }
+
+    fn init(carry_data: Self::CarryData) -> Self {
+        QuadBuilder {
+            buffer: VertexBuffer::new(),
+            offset: Vec2::ZERO,
+            z_offset: 0.0,
+            carry_data,
+            added: Vec::new(),
+        }
+    }
+
+    fn set_offset(&mut self, offset: Vec2) {
+        self.offset = offset;
+    }
+
+    fn set_z_offset(&mut self, z_offset: f32) {
+        self.z_offset = z_offset;
+    }
+
+    fn reserve(&mut self, tile_count: usize) {
+        self.carry_data.reserved = tile_count;
+    }
+
+    fn vertex_count(&self) -> Option<usize> {
+        Some(self.buffer.len())
+    }
+
+    fn finish(mut self) -> (Mesh, Self::CarryData) {
+        self.carry_data.full_builds += 1;
+        self.carry_data.quads = self.added.into_iter().map(|(pos, _)| pos).collect();
+        (self.buffer.into_mesh(), self.carry_data)
+    }
+
+    fn update_tile(self, mesh: &mut Mesh, pos: ChunkPos, carry_data: &mut Self::CarryData) -> bool {
+        let (index, id) = match (PATCH, self.added.as_slice()) {
+            (true, [(_, id)]) => match carry_data.quads.iter().position(|quad| *quad == pos) {
+                Some(index) => (index, *id),
+                None => return false,
+            },
+            _ => return false,
+        };
+        match mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => {
+                uvs[index * 4..index * 4 + 4].fill([id as f32, 0.0]);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Creates an app with the plugins needed to generate the meshes of a tilemap with tiles of
+/// type `T`, and an empty tilemap
+pub fn test_app<T: Tile<MeshBuilder = B>, B: MeshBuilder<Material = ColorMaterial>>() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_asset::<Mesh>()
+        .add_asset::<ColorMaterial>()
+        .add_plugin(TilemapPlugin::<T>::new());
+    app.update();
+    app
+}
+
+/// Creates a tilemap with no material
+pub fn test_tilemap<T: Tile>() -> Tilemap<T> {
+    Tilemap::new(Handle::default())
+}
+
+/// Returns the tilemap in `app`
+pub fn tilemap<T: Tile>(app: &mut App) -> Mut<'_, Tilemap<T>> {
+    app.world.resource_mut::<Tilemap<T>>()
+}
+
+/// Returns the mesh displayed for the chunk at `chunk` in `app`, if it has one
+pub fn chunk_mesh<T: Tile>(app: &App, chunk: IVec2) -> Option<&Mesh> {
+    let entity = app
+        .world
+        .resource::<Tilemap<T>>()
+        .get_chunk(chunk)?
+        .mesh_entity?;
+    let handle = app.world.get::<Mesh2dHandle>(entity)?;
+    app.world.resource::<Assets<Mesh>>().get(&handle.0)
+}
+
+/// The number of times the whole mesh of the chunk at `chunk` in `app` was built
+pub fn full_builds<T: Tile<MeshBuilder = QuadBuilder<PATCH>>, const PATCH: bool>(
+    app: &App,
+    chunk: IVec2,
+) -> usize {
+    app.world
+        .resource::<Tilemap<T>>()
+        .get_chunk(chunk)
+        .map_or(0, |chunk| chunk.mesh_carry_data.full_builds)
+}
+
+/// The positions, UVs and indices of `mesh`, for comparing meshes
+pub fn mesh_data(mesh: &Mesh) -> (Vec<[f32; 3]>, Vec<[f32; 2]>, Vec<u32>) {
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+        _ => Vec::new(),
+    };
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+        _ => Vec::new(),
+    };
+    let indices = match mesh.indices() {
+        Some(Indices::U32(indices)) => indices.clone(),
+        _ => Vec::new(),
+    };
+    (positions, uvs, indices)
+}
+
+/// The position of the tile at `(x, y)` in a tilemap
+pub fn pos(x: i32, y: i32) -> TilemapPos {
+    IVec2::new(x, y).into()
+}
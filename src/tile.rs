@@ -1,6 +1,12 @@
 //! Tiles in the tilemap
 
-use crate::{animation::MeshUpdater, rendering::MeshBuilder};
+use bevy::prelude::*;
+
+use crate::{
+    animation::{MeshUpdater, TileAnimation},
+    rendering::{MeshBuilder, TileMesh},
+    tilemap::TilemapPos,
+};
 
 /// A tile in the tilemap
 pub trait Tile: Send + Sync + Clone + 'static {
@@ -16,6 +22,107 @@ pub trait Tile: Send + Sync + Clone + 'static {
     /// Called before [`Self::animate`]
     fn add_to_mesh(&self, builder: &mut Self::MeshBuilder);
 
+    /// Add this tile to the mesh, given the tiles surrounding it
+    ///
+    /// `neighbors` are ordered clockwise starting from north, the same as
+    /// [`Direction::ALL`](crate::tilemap::Direction::ALL), and are always the 8 tiles around
+    /// this on the square grid of tile positions, regardless of the
+    /// [`TileLayout`](crate::tilemap::TileLayout) of the tilemap.  For hexagonal layouts,
+    /// [`TileLayout::neighbor_offsets()`](crate::tilemap::TileLayout::neighbor_offsets())
+    /// with [`Direction::from_offset()`](crate::tilemap::Direction::from_offset()) picks out
+    /// the 6 that share an edge with this.  Neighbors in adjacent chunks
+    /// are included, and setting or removing a tile on the edge of a chunk through
+    /// [`Tilemap`](crate::tilemap::Tilemap) regenerates the chunks next to it; changing tiles
+    /// through a [`Chunk`](crate::tilemap::Chunk) doesn't.  Used to emit geometry that depends
+    /// on surrounding tiles, such as edges between differing tiles.
+    ///
+    /// Called instead of [`Self::add_to_mesh`] during mesh generation.  Default
+    /// implementation ignores the neighbors and calls [`Self::add_to_mesh`]
+    #[allow(unused_variables)]
+    fn add_to_mesh_with_neighbors(
+        &self,
+        builder: &mut Self::MeshBuilder,
+        neighbors: [Option<&Self>; 8],
+    ) {
+        self.add_to_mesh(builder)
+    }
+
+    /// Precomputed geometry to add to the mesh for this, for tiles that aren't quads
+    ///
+    /// If this returns [`Some`], the template is passed to [`MeshBuilder::add_template`]
+    /// instead of calling [`Self::add_to_mesh_with_neighbors`], unless the builder doesn't
+    /// support templates.  Default implementation returns [`None`]
+    fn mesh_template(&self) -> Option<&TileMesh> {
+        None
+    }
+
+    /// Returns `true` if this adds the same mesh as `other`, given the same neighbors
+    ///
+    /// Setting a tile that is visually equal to the tile it replaces doesn't cause the chunk
+    /// mesh to be regenerated.  Default implementation returns `false`; for tiles
+    /// implementing [`PartialEq`] whose every field affects their mesh, this can be
+    /// implemented as `self == other`
+    #[allow(unused_variables)]
+    fn visually_eq(&self, other: &Self) -> bool {
+        false
+    }
+
+    /// How far in front of the other tiles in it's chunk this is drawn
+    ///
+    /// Tiles in a chunk are added to the mesh from lowest to highest offset, so tiles with a
+    /// higher offset are drawn over tiles with a lower one, and the offset is passed to
+    /// [`MeshBuilder::set_z_offset`].  Tiles with equal offsets are added in storage order.
+    /// Default implementation returns `0.0`
+    fn z_offset(&self) -> f32 {
+        0.0
+    }
+
+    /// Returns `true` if this blocks light
+    ///
+    /// Used by [`Tilemap::build_light_mask`](crate::tilemap::Tilemap::build_light_mask).
+    /// Default implementation returns `false`
+    fn blocks_light(&self) -> bool {
+        false
+    }
+
+    /// The mesh layer this is added to
+    ///
+    /// Tiles in layer 0 are added to the chunk mesh.  Tiles in each other layer are added to a
+    /// separate mesh for that layer, displayed by a child of the chunk mesh entity using the
+    /// [material of the layer](crate::tilemap::Tilemap::set_layer_material), so they can be
+    /// drawn differently (for example, with a different blend mode).  Default implementation
+    /// returns `0`
+    fn mesh_layer(&self) -> u8 {
+        0
+    }
+
+    /// Called after this is set at `pos`, if
+    /// [hooks are enabled](crate::tilemap::Tilemap::set_hooks_enabled)
+    ///
+    /// Called before meshes are next generated, with a copy of the tile that was set.  Can be
+    /// used to spawn entities associated with this.  Default implementation does nothing
+    #[allow(unused_variables)]
+    fn on_set(&self, pos: TilemapPos, commands: &mut Commands) {}
+
+    /// Called after this is removed from `pos`, if
+    /// [hooks are enabled](crate::tilemap::Tilemap::set_hooks_enabled)
+    ///
+    /// Called before meshes are next generated.  Replacing a tile removes it, and this is
+    /// called for the old tile before [`Self::on_set`] is called for the new one.  Can be used
+    /// to despawn entities associated with this.  Default implementation does nothing
+    #[allow(unused_variables)]
+    fn on_remove(&self, pos: TilemapPos, commands: &mut Commands) {}
+
+    /// The animation this plays, or [`None`] if this isn't animated
+    ///
+    /// Only played in mesh layer 0 and at the most detailed level of detail, and only if
+    /// [`MeshBuilder::vertex_count`] is implemented.  Animated tiles are found when chunk
+    /// meshes are generated, so the animation of a tile shouldn't change without it being
+    /// set again.  Default implementation returns [`None`]
+    fn animation(&self) -> Option<TileAnimation> {
+        None
+    }
+
     /// Animates this tile
     ///
     /// If animation is enabled, called after [`Self::add_to_mesh`].
@@ -1,25 +1,70 @@
 //! Storage of tiles and interface with the Bevy engine
 
-use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::{
+    fmt, iter, mem,
+    ops::{Add, AddAssign, Range, Sub, SubAssign},
+};
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{
+    prelude::*,
+    tasks::{ComputeTaskPool, ParallelSliceMut},
+    utils::{HashMap, HashSet},
+};
 
 use crate::{rendering::MeshBuilder, tile::Tile, CHUNK_SIZE};
 
 mod chunk;
+mod chunk_map;
+mod direction;
+mod layout;
+mod rect;
 
 pub use chunk::*;
+pub(crate) use chunk_map::ChunkMap;
+pub use direction::Direction;
+pub use layout::*;
+pub use rect::*;
 
-/// A tilemap with tiles of type `T`
+/// A tilemap with tiles of type `T`, and metadata of type `M` attached to positions
 ///
-/// Uses chunks to generate more efficient meshes
+/// `M` defaults to `()`; to attach metadata, create the tilemap with
+/// [`with_metadata()`](Tilemap::with_metadata()) and add the plugin with
+/// `TilemapPlugin::<T, M>::default()`.  Uses chunks to generate more efficient meshes.
+/// Chunks are iterated in an arbitrary order, unless the `deterministic` feature is enabled,
+/// in which case they are stored sorted by y, then by x, and always iterated in that order at
+/// the cost of slower lookups
 #[derive(Debug)]
-pub struct Tilemap<T: Tile> {
-    pub(crate) data: HashMap<IVec2, Chunk<T>>,
+pub struct Tilemap<T: Tile, M = ()> {
+    pub(crate) data: ChunkMap<T>,
     pub(crate) material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+    /// Set when the material changes, so existing chunk meshes are switched to it
+    pub(crate) update_material: bool,
+    layer_materials: HashMap<u8, Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>>,
+    pub(crate) max_regens_per_frame: usize,
+    lod_distances: Vec<f32>,
+    layout: TileLayout,
+    z: f32,
+    metadata: HashMap<TilemapPos, M>,
+    /// Emptied chunks kept to be reused by [`Tilemap::get_or_create_chunk()`]
+    pool: Vec<Chunk<T>>,
+    max_pool_size: usize,
+    bounds: Option<IRect>,
+    pub(crate) regen_stats: RegenStats,
+    hooks_enabled: bool,
+    /// Tiles set and removed since [`Tile::on_set`] and [`Tile::on_remove`] were last called
+    pub(crate) pending_hooks: Vec<HookEvent<T>>,
 }
 
-impl<T: Tile> Tilemap<T> {
+/// A tile set or removed, waiting for it's lifecycle hook to be called
+#[derive(Debug)]
+pub(crate) enum HookEvent<T> {
+    /// This tile was set at this position
+    Set(TilemapPos, T),
+    /// This tile was removed from this position
+    Removed(TilemapPos, T),
+}
+
+impl<T: Tile, M> Tilemap<T, M> {
     /// Returns a reference to the chunk at the given position if it exists
     #[must_use]
     pub fn get_chunk(&self, pos: IVec2) -> Option<&Chunk<T>> {
@@ -32,330 +77,3831 @@ impl<T: Tile> Tilemap<T> {
         self.data.get_mut(&pos)
     }
 
+    /// Returns a mutable reference to the chunk at `pos` and references to the 8 chunks
+    /// surrounding it, or [`None`] if there is no chunk at `pos`
+    ///
+    /// Neighbors are ordered clockwise starting from north, the same as [`Direction::ALL`],
+    /// and are [`None`] if they don't exist.  Useful for changing a chunk based on the tiles
+    /// across it's borders
+    #[allow(clippy::type_complexity)]
+    pub fn chunk_with_neighbors(
+        &mut self,
+        pos: IVec2,
+    ) -> Option<(&mut Chunk<T>, [Option<&Chunk<T>>; 8])> {
+        self.data.get_with_neighbors(pos)
+    }
+
     /// Returns a mutable refernece to the chunk at the given position,
     /// creating one if it doesn't exist
+    ///
+    /// A created chunk isn't flagged to regenerate it's mesh.  See
+    /// [`get_or_create_chunk_lazy()`](Self::get_or_create_chunk_lazy())
     pub fn get_or_create_chunk(&mut self, pos: IVec2) -> &mut Chunk<T> {
-        self.data.entry(pos).or_default()
+        self.get_or_create_chunk_lazy(pos)
+    }
+
+    /// Returns a mutable refernece to the chunk at the given position,
+    /// creating one if it doesn't exist, without flagging it to regenerate it's mesh
+    ///
+    /// A created chunk is skipped by mesh generation and never gets a mesh entity until a
+    /// tile is set in it.  An empty chunk that is never written to is kept until it's removed
+    pub fn get_or_create_chunk_lazy(&mut self, pos: IVec2) -> &mut Chunk<T> {
+        let pool = &mut self.pool;
+        self.data
+            .get_or_insert_with(pos, || pool.pop().unwrap_or_default())
+    }
+
+    /// Removes the chunk at `pos`, keeping it to be reused if there is room in the pool
+    pub(crate) fn recycle_chunk(&mut self, pos: IVec2) {
+        if let Some(mut chunk) = self.data.remove(&pos) {
+            if self.pool.len() < self.max_pool_size {
+                chunk.reset();
+                self.pool.push(chunk);
+            }
+        }
+    }
+
+    /// Returns the maximum number of emptied chunks kept to be reused
+    #[must_use]
+    pub fn pool_size(&self) -> usize {
+        self.max_pool_size
+    }
+
+    /// Sets the maximum number of emptied chunks kept to be reused
+    ///
+    /// When a chunk is dropped because it has no tiles, it is kept if there is room, and
+    /// reused the next time a chunk is created instead of initializing a new one.  Useful
+    /// when chunks are often created and dropped, such as when streaming.  Defaults to 0
+    pub fn set_pool_size(&mut self, max: usize) {
+        self.max_pool_size = max;
+        self.pool.truncate(max);
+    }
+
+    /// Runs `f` on the chunk at `pos`, creating the chunk if it doesn't exist
+    ///
+    /// Returns the value returned by `f`.  Flagging the chunk to regenerate it's mesh is left
+    /// to `f`; [`Chunk::set_raw()`] followed by a single [`Chunk::regenerate_mesh()`] avoids
+    /// tracking each change separately
+    pub fn with_chunk_mut<R>(&mut self, pos: IVec2, f: impl FnOnce(&mut Chunk<T>) -> R) -> R {
+        f(self.get_or_create_chunk(pos))
+    }
+
+    /// Returns the chunk containing the tile at `world`, the position of the tile in that
+    /// chunk, and the tile if it exists
+    ///
+    /// Returns [`None`] if the chunk doesn't exist.  The tile is [`None`] outside of the
+    /// [bounds](Self::bounds()) of this, the same as with [`get()`](Self::get())
+    #[must_use]
+    pub fn get_resolved(&self, world: IVec2) -> Option<(&Chunk<T>, ChunkPos, Option<&T>)> {
+        let pos = TilemapPos::from(world);
+        let chunk = self.get_chunk(pos.chunk)?;
+        let tile = chunk[pos.tile].as_ref().filter(|_| self.in_bounds(pos));
+        Some((chunk, pos.tile, tile))
+    }
+
+    /// Splits the position of a tile in the tilemap into the position of it's chunk and it's
+    /// position in that chunk
+    ///
+    /// The same as converting `world` to a [`TilemapPos`]
+    #[must_use]
+    pub fn resolve(world: IVec2) -> (IVec2, ChunkPos) {
+        let pos = TilemapPos::from(world);
+        (pos.chunk, pos.tile)
     }
 
     /// Returns a reference to the tile at the position in this tilemap if it exists
+    ///
+    /// Returns [`None`] outside of the [bounds](Self::bounds()) of this
     #[must_use]
     pub fn get(&self, pos: TilemapPos) -> Option<&T> {
+        if !self.in_bounds(pos) {
+            return None;
+        }
         self.get_chunk(pos.chunk)
             .and_then(|chunk| chunk[pos.tile].as_ref())
     }
 
     /// Returns a mutable reference to the tile at the position in this tilemap if it exists
     ///
+    /// Returns [`None`] outside of the [bounds](Self::bounds()) of this.
     /// If mutating the tile slot results in a change that requires
     /// regenerating the chunk mesh, call [`regenerate_mesh()`](Chunk::regenerate_mesh())
     /// on the chunk
     #[must_use]
     pub fn get_mut(&mut self, pos: TilemapPos) -> Option<&mut T> {
+        if !self.in_bounds(pos) {
+            return None;
+        }
         self.get_chunk_mut(pos.chunk)
             .and_then(|chunk| chunk[pos.tile].as_mut())
     }
 
-    /// Sets the tile at `pos`, returning it's previous value
+    /// Returns a reference to the tile at the position in this tilemap, without checking that
+    /// it exists
     ///
-    /// Tells the chunk the tile is in to regenerate it's mesh the next time it's displayed
-    pub fn set(&mut self, pos: TilemapPos, tile: impl Into<T>) -> Option<T> {
-        self.get_or_create_chunk(pos.chunk)
-            .set(pos.tile, tile.into())
+    /// Skips the checks done by [`get()`](Self::get()), including bounds checks.  For
+    /// inner loops over positions known to hold tiles
+    ///
+    /// # Safety
+    ///
+    /// The chunk containing `pos` must exist and have a tile at `pos`; otherwise the
+    /// behavior is undefined
+    #[must_use]
+    pub unsafe fn get_unchecked(&self, pos: TilemapPos) -> &T {
+        unsafe {
+            self.data.get(&pos.chunk).unwrap_unchecked()[pos.tile]
+                .as_ref()
+                .unwrap_unchecked()
+        }
     }
 
-    /// Removes the tile at pos and returns it
+    /// Returns a mutable reference to the tile at the position in this tilemap, without
+    /// checking that it exists
     ///
-    /// Tells the chunk the tile is in to regenerate it's mesh the next time it's displayed
-    pub fn remove(&mut self, pos: TilemapPos) -> Option<T> {
-        self.get_chunk_mut(pos.chunk)
-            .and_then(|chunk| chunk.remove(pos.tile))
+    /// Skips the checks done by [`get_mut()`](Self::get_mut()), including bounds checks.
+    /// If mutating the tile results in a change that requires regenerating the chunk mesh,
+    /// call [`regenerate_mesh()`](Chunk::regenerate_mesh()) on the chunk
+    ///
+    /// # Safety
+    ///
+    /// The chunk containing `pos` must exist and have a tile at `pos`; otherwise the
+    /// behavior is undefined
+    #[must_use]
+    pub unsafe fn get_unchecked_mut(&mut self, pos: TilemapPos) -> &mut T {
+        unsafe {
+            self.data.get_mut(&pos.chunk).unwrap_unchecked()[pos.tile]
+                .as_mut()
+                .unwrap_unchecked()
+        }
     }
 
-    /// Returns a reference to the handle for the material this uses
-    pub fn material(&self) -> &Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material> {
-        &self.material
+    /// Returns a mutable reference to the tile at `pos` and a reference to it's neighbor
+    /// in `dir`, if the tile at `pos` exists
+    ///
+    /// The neighbor may be in a different chunk.
+    /// If mutating the tile results in a change that requires regenerating the chunk mesh,
+    /// call [`regenerate_mesh()`](Chunk::regenerate_mesh()) on the chunk
+    #[must_use]
+    pub fn get_with_neighbor_mut(
+        &mut self,
+        pos: TilemapPos,
+        dir: Direction,
+    ) -> Option<(&mut T, Option<&T>)> {
+        let neighbor_pos = TilemapPos::from(IVec2::from(pos) + dir.offset());
+        if neighbor_pos.chunk == pos.chunk {
+            let (tile, neighbor) = self
+                .get_chunk_mut(pos.chunk)?
+                .get_pair_mut(pos.tile, neighbor_pos.tile);
+            return tile.as_mut().map(|tile| (tile, neighbor.as_ref()));
+        }
+        if !self.data.contains_key(&neighbor_pos.chunk) {
+            return self.get_mut(pos).map(|tile| (tile, None));
+        }
+        let [chunk, neighbor_chunk] = self.data.get_pair_mut(&pos.chunk, &neighbor_pos.chunk)?;
+        let neighbor = neighbor_chunk[neighbor_pos.tile].as_ref();
+        chunk[pos.tile].as_mut().map(|tile| (tile, neighbor))
     }
 
-    /// Returns an iterator over all chunks in this
-    pub fn iter_chunks(&self) -> impl Iterator<Item = &Chunk<T>> {
-        self.data.values()
+    /// Returns a mutable reference to the tile at the position in this tilemap,
+    /// inserting the default tile if there isn't one
+    ///
+    /// Returns [`None`] without inserting anything if `pos` is outside of the
+    /// [bounds](Self::bounds()) of this.  Only tells the chunk the tile is in, and chunks
+    /// sharing an edge with it, to regenerate their meshes if a tile was inserted.  If mutating
+    /// the tile results in a change that requires regenerating the chunk mesh, call
+    /// [`regenerate_mesh()`](Chunk::regenerate_mesh()) on the chunk
+    pub fn get_mut_or_default(&mut self, pos: TilemapPos) -> Option<&mut T>
+    where
+        T: Default,
+    {
+        if !self.in_bounds(pos) {
+            return None;
+        }
+        if !self.get_or_create_chunk(pos.chunk).is_set(pos.tile) {
+            self.regenerate_neighbor_chunks([pos]);
+            let chunk = self.get_chunk_mut(pos.chunk).unwrap();
+            chunk.set_raw(pos.tile, Some(T::default()));
+            chunk.regenerate_mesh();
+        }
+        self.get_chunk_mut(pos.chunk).unwrap()[pos.tile].as_mut()
     }
 
-    /// Returns an iterator over all chunks in this that allows modifying each
-    pub fn iter_chunks_mut(&mut self) -> impl Iterator<Item = &mut Chunk<T>> {
-        self.data.values_mut()
+    /// Sets the tile at `pos`, returning it's previous value
+    ///
+    /// Tells the chunk the tile is in to regenerate it's mesh the next time it's displayed,
+    /// unless the previous tile is [visually equal](Tile::visually_eq).  If the tile is on
+    /// the edge of it's chunk, the chunks sharing that edge are told to as well, since their
+    /// tiles get it as a neighbor.  Does nothing and returns [`None`] outside of the
+    /// [bounds](Self::bounds()) of this; use [`try_set()`](Self::try_set()) to detect this
+    pub fn set(&mut self, pos: TilemapPos, tile: impl Into<T>) -> Option<T> {
+        self.try_set(pos, tile).unwrap_or(None)
     }
 
-    /// Returns an iterator over all chunks in this and their positions
-    pub fn iter_chunk_positions(&self) -> impl Iterator<Item = (&IVec2, &Chunk<T>)> {
-        self.data.iter()
+    /// Sets the tile at `pos`, returning it's previous value, or an error if `pos` is outside
+    /// of the [bounds](Self::bounds()) of this
+    ///
+    /// Tells the chunk the tile is in to regenerate it's mesh the next time it's displayed,
+    /// unless the previous tile is [visually equal](Tile::visually_eq).  Chunks sharing an
+    /// edge with the tile are told to as well, the same as with [`set()`](Self::set())
+    pub fn try_set(
+        &mut self,
+        pos: TilemapPos,
+        tile: impl Into<T>,
+    ) -> Result<Option<T>, OutOfBounds> {
+        if !self.in_bounds(pos) {
+            return Err(OutOfBounds(pos));
+        }
+        let tile = tile.into();
+        let hook_tile = self.hooks_enabled.then(|| tile.clone());
+        let chunk = self.get_or_create_chunk(pos.chunk);
+        let changed = !matches!(&chunk[pos.tile], Some(old) if old.visually_eq(&tile));
+        let old = chunk.set(pos.tile, tile);
+        if changed {
+            self.regenerate_neighbor_chunks([pos]);
+        }
+        if let Some(tile) = hook_tile {
+            // Removed first, so hooks for the old tile don't undo the hooks for the new one
+            if let Some(old) = &old {
+                self.pending_hooks
+                    .push(HookEvent::Removed(pos, old.clone()));
+            }
+            self.pending_hooks.push(HookEvent::Set(pos, tile));
+        }
+        Ok(old)
     }
 
-    /// Returns an iterator over all chunks in this and their positions
-    /// that allows modifying each chunk
-    pub fn iter_chunk_positions_mut(&mut self) -> impl Iterator<Item = (&IVec2, &mut Chunk<T>)> {
-        self.data.iter_mut()
+    /// Returns the area tiles can be set in, or [`None`] if this is unbounded
+    #[must_use]
+    pub fn bounds(&self) -> Option<IRect> {
+        self.bounds
     }
 
-    /// Returns an iterator over all tiles in this
-    pub fn iter(&self) -> impl Iterator<Item = &T> {
-        self.iter_chunks().flat_map(Chunk::iter_tiles)
+    /// Limits the area tiles can be set in, in tile coordinates
+    ///
+    /// Outside of `bounds`, [`set()`](Self::set()) is ignored, [`try_set()`](Self::try_set())
+    /// returns an error and [`get()`](Self::get()) returns [`None`], preventing accidental
+    /// growth of fixed-size maps.  Tiles already outside of `bounds` are kept, but can't be
+    /// accessed through those methods.  [`None`] (the default) removes the limit
+    pub fn set_bounds(&mut self, bounds: Option<IRect>) {
+        self.bounds = bounds;
     }
 
-    /// Returns an iterator over all tiles in this that allows modifying each value
-    ///
-    /// If mutating the tile slot results in a change that requires
-    /// regenerating the chunk mesh, call [`Chunk::regenerate_mesh()`] on that chunk.
-    /// For an iterator that returns the chunk a tile is in as well,
-    /// use [`Self::iter_positions_mut()`]
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
-        self.iter_chunks_mut().flat_map(Chunk::iter_tiles_mut)
+    /// Returns `true` if `pos` is inside the [bounds](Self::bounds()) of this
+    #[must_use]
+    pub fn in_bounds(&self, pos: TilemapPos) -> bool {
+        !matches!(self.bounds, Some(bounds) if !bounds.contains(IVec2::from(pos)))
     }
 
-    /// Returns an iterator over all tiles in this and their positions
-    pub fn iter_positions(&self) -> impl Iterator<Item = (TilemapPos, &T)> {
-        self.iter_chunk_positions().flat_map(|(chunk_pos, chunk)| {
-            chunk.iter_tile_positions().map(move |(tile_pos, tile)| {
-                (
-                    TilemapPos {
-                        chunk: *chunk_pos,
-                        tile: tile_pos,
-                    },
-                    tile,
-                )
-            })
-        })
+    /// The positions in `rect` that are inside the [bounds](Self::bounds()) of this
+    fn clamp_to_bounds(&self, rect: IRect) -> IRect {
+        match self.bounds {
+            Some(bounds) => rect.intersect(bounds),
+            None => rect,
+        }
     }
 
-    /// Returns an iterator over all tiles in this and their positions
-    /// that allows modifying each tile
+    /// Removes the tile at pos and returns it
     ///
-    /// If mutating the tile slot results in a change that requires
-    /// regenerating the chunk mesh, call [`Chunk::regenerate_mesh()`] on that chunk.
-    pub fn iter_positions_mut(&mut self) -> impl Iterator<Item = (TilemapPos, &mut T)> {
-        self.iter_chunk_positions_mut()
-            .flat_map(|(chunk_pos, chunk)| {
-                chunk
-                    .iter_tile_positions_mut()
-                    .map(move |(tile_pos, tile)| {
-                        (
-                            TilemapPos {
-                                chunk: *chunk_pos,
-                                tile: tile_pos,
-                            },
-                            tile,
-                        )
-                    })
-            })
+    /// Tells the chunk the tile is in to regenerate it's mesh the next time it's displayed.
+    /// Chunks sharing an edge with the tile are told to as well, the same as with
+    /// [`set()`](Self::set())
+    pub fn remove(&mut self, pos: TilemapPos) -> Option<T> {
+        let old = self
+            .get_chunk_mut(pos.chunk)
+            .and_then(|chunk| chunk.remove(pos.tile));
+        if old.is_some() {
+            self.regenerate_neighbor_chunks([pos]);
+        }
+        if let (true, Some(old)) = (self.hooks_enabled, &old) {
+            self.pending_hooks
+                .push(HookEvent::Removed(pos, old.clone()));
+        }
+        old
     }
 
-    pub(crate) fn new(
-        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
-    ) -> Self {
-        Tilemap {
-            data: HashMap::default(),
-            material,
+    /// Tells the chunks next to the chunks containing `positions` to regenerate their meshes
+    /// the next time they're displayed, if any of `positions` are on the edge shared with them
+    ///
+    /// Tiles in those chunks get the tiles at `positions` as neighbors during mesh generation.
+    /// Positions not on the edge of their chunk are skipped, so bulk writers only need to
+    /// collect the [edge positions](on_chunk_edge) they changed
+    pub(crate) fn regenerate_neighbor_chunks(
+        &mut self,
+        positions: impl IntoIterator<Item = TilemapPos>,
+    ) {
+        let mut neighbors = HashSet::default();
+        for pos in positions.into_iter().filter(|pos| on_chunk_edge(pos.tile)) {
+            for direction in Direction::ALL {
+                let neighbor = TilemapPos::from(IVec2::from(pos) + direction.offset());
+                if neighbor.chunk != pos.chunk {
+                    neighbors.insert(neighbor.chunk);
+                }
+            }
+        }
+        for chunk_pos in neighbors {
+            if let Some(chunk) = self.get_chunk_mut(chunk_pos) {
+                chunk.regenerate_mesh();
+            }
         }
     }
-}
-
-/// A position in a tilemap
-///
-/// Stored as the chunk the position is in and which tile the position is
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct TilemapPos {
-    /// The chunk the position is in
-    pub chunk: IVec2,
-    /// The tile in the chunk the position is
-    pub tile: ChunkPos,
-}
-
-impl TilemapPos {
-    /// Tile (0, 0) in chunk (0, 0)
-    pub const ZERO: Self = TilemapPos {
-        chunk: IVec2::ZERO,
-        tile: ChunkPos::ZERO,
-    };
-}
 
-impl From<IVec2> for TilemapPos {
+    /// Returns `true` if [`Tile::on_set`] and [`Tile::on_remove`] are called for tiles set
+    /// and removed through this
     #[must_use]
-    fn from(v: IVec2) -> Self {
-        TilemapPos {
-            chunk: v / IVec2::splat(CHUNK_SIZE as i32),
-            tile: ChunkPos::new(
-                v.x as u8 & (CHUNK_SIZE as u8 - 1),
-                v.y as u8 & (CHUNK_SIZE as u8 - 1),
-            ),
-        }
+    pub fn hooks_enabled(&self) -> bool {
+        self.hooks_enabled
     }
-}
 
-impl From<TilemapPos> for IVec2 {
-    #[must_use]
-    #[inline]
-    fn from(v: TilemapPos) -> Self {
-        v.chunk * (CHUNK_SIZE as i32) + v.tile.as_ivec2()
+    /// Sets whether [`Tile::on_set`] and [`Tile::on_remove`] are called for tiles set and
+    /// removed through this
+    ///
+    /// When enabled, [`set()`](Self::set()), [`try_set()`](Self::try_set()) and
+    /// [`remove()`](Self::remove()) record a copy of each tile set or removed, and the hooks
+    /// are called with them before meshes are next generated.  Tiles changed through chunks
+    /// or other methods don't call the hooks.  Defaults to `false`
+    pub fn set_hooks_enabled(&mut self, enabled: bool) {
+        self.hooks_enabled = enabled;
+        if !enabled {
+            self.pending_hooks.clear();
+        }
     }
-}
 
-impl Add for TilemapPos {
-    type Output = Self;
+    /// Moves the tile at `from` to `to`, or returns an error if there is no tile at `from`, there
+    /// is already a tile at `to`, or `to` is outside of the [bounds](Self::bounds()) of this
+    ///
+    /// Tells the chunks both positions are in to regenerate their meshes the next time
+    /// they're displayed.  Moving a tile to it's own position does nothing.  Metadata
+    /// attached to `from` is moved with the tile, replacing any metadata attached to `to`.
+    /// With hooks [enabled](Self::set_hooks_enabled()), the move is treated as removing the
+    /// tile from `from` and setting it at `to`
+    pub fn move_tile(&mut self, from: TilemapPos, to: TilemapPos) -> Result<(), MoveError> {
+        if from != to && self.get(to).is_some() {
+            return Err(MoveError::Occupied(to));
+        }
+        self.move_tile_overwrite(from, to).map(|_| ())
+    }
 
-    #[must_use]
-    fn add(self, rhs: Self) -> Self::Output {
-        let mut chunk = self.chunk + rhs.chunk;
-        let (tile, carry) = self.tile.overflowing_add(rhs.tile);
-        if carry.x {
-            chunk.x += 1
+    /// Moves the tile at `from` to `to`, returning the tile previously at `to`, or returns an
+    /// error if there is no tile at `from` or `to` is outside of the [bounds](Self::bounds())
+    /// of this
+    ///
+    /// Tells the chunks both positions are in to regenerate their meshes the next time
+    /// they're displayed.  Moving a tile to it's own position does nothing.  Metadata
+    /// attached to `from` is moved with the tile, replacing any metadata attached to `to`.
+    /// With hooks [enabled](Self::set_hooks_enabled()), the move is treated as removing the
+    /// tile from `from` and setting it at `to`, so the tile previously at `to` is removed too
+    pub fn move_tile_overwrite(
+        &mut self,
+        from: TilemapPos,
+        to: TilemapPos,
+    ) -> Result<Option<T>, MoveError> {
+        if self.get(from).is_none() {
+            return Err(MoveError::Empty(from));
         }
-        if carry.y {
-            chunk.y += 1
+        if !self.in_bounds(to) {
+            return Err(MoveError::OutOfBounds(to));
         }
-        TilemapPos { chunk, tile }
+        if from == to {
+            return Ok(None);
+        }
+        let tile = self.remove(from).unwrap();
+        match self.metadata.remove(&from) {
+            Some(meta) => self.metadata.insert(to, meta),
+            None => self.metadata.remove(&to),
+        };
+        Ok(self.try_set(to, tile).unwrap())
     }
-}
 
-impl AddAssign for TilemapPos {
-    fn add_assign(&mut self, rhs: Self) {
-        let (tile, carry) = self.tile.overflowing_add(rhs.tile);
-        self.tile = tile;
-        self.chunk += rhs.chunk;
-        if carry.x {
-            self.chunk.x += 1
+    /// Moves every tile in the chunk at `from` to the same position in the chunk at `to`, or
+    /// returns an error if the chunk at `from` has no tiles, or `overwrite` is `false` and the
+    /// chunk at `to` has tiles
+    ///
+    /// With `overwrite`, the tiles previously in the chunk at `to` are dropped.  Tells both
+    /// chunks, and chunks sharing an edge with a moved tile, to regenerate their meshes the
+    /// next time they're displayed, so the chunk at `from` is dropped and it's mesh despawned
+    /// then.  Moving a chunk to it's own position does nothing.  Metadata and chunk settings
+    /// such as visual offsets are not moved, and bounds are not checked
+    pub fn relocate_chunk(
+        &mut self,
+        from: IVec2,
+        to: IVec2,
+        overwrite: bool,
+    ) -> Result<(), ChunkMoveError> {
+        if !matches!(self.get_chunk(from), Some(chunk) if !chunk.is_empty()) {
+            return Err(ChunkMoveError::Empty(from));
         }
-        if carry.y {
-            self.chunk.y += 1
+        if from == to {
+            return Ok(());
+        }
+        if !overwrite && matches!(self.get_chunk(to), Some(chunk) if !chunk.is_empty()) {
+            return Err(ChunkMoveError::Occupied(to));
         }
+        self.get_or_create_chunk(to);
+        let [from_chunk, to_chunk] = self.data.get_pair_mut(&from, &to).unwrap();
+        let mut edges = Vec::new();
+        for ((tile, from_slot), to_slot) in from_chunk.iter_positions_mut().zip(to_chunk.iter_mut())
+        {
+            if on_chunk_edge(tile) {
+                if from_slot.is_some() {
+                    edges.push(TilemapPos { chunk: from, tile });
+                }
+                if from_slot.is_some() || to_slot.is_some() {
+                    edges.push(TilemapPos { chunk: to, tile });
+                }
+            }
+            *to_slot = from_slot.take();
+        }
+        from_chunk.regenerate_mesh();
+        to_chunk.regenerate_mesh();
+        self.regenerate_neighbor_chunks(edges);
+        Ok(())
     }
-}
 
-impl Sub for TilemapPos {
-    type Output = Self;
+    /// Removes the tile at pos and the metadata attached to pos, returning both
+    ///
+    /// Tells the chunk the tile is in to regenerate it's mesh the next time it's displayed
+    pub fn remove_with_meta(&mut self, pos: TilemapPos) -> (Option<T>, Option<M>) {
+        (self.remove(pos), self.remove_meta(pos))
+    }
 
+    /// Returns a reference to the metadata attached to `pos` if there is any
     #[must_use]
-    fn sub(self, rhs: Self) -> Self::Output {
-        let mut chunk = self.chunk - rhs.chunk;
-        let (tile, carry) = self.tile.overflowing_sub(rhs.tile);
-        if carry.x {
-            chunk.x -= 1;
-        }
-        if carry.y {
-            chunk.y -= 1;
+    pub fn get_meta(&self, pos: TilemapPos) -> Option<&M> {
+        self.metadata.get(&pos)
+    }
+
+    /// Returns a mutable reference to the metadata attached to `pos` if there is any
+    #[must_use]
+    pub fn get_meta_mut(&mut self, pos: TilemapPos) -> Option<&mut M> {
+        self.metadata.get_mut(&pos)
+    }
+
+    /// Attaches metadata to `pos`, returning the metadata previously attached
+    ///
+    /// Metadata is stored separately from tiles, so this can be used whether or not
+    /// there is a tile at `pos`, and never requires regenerating a chunk mesh.  Only useful
+    /// on tilemaps created with [`with_metadata()`](Tilemap::with_metadata())
+    pub fn set_meta(&mut self, pos: TilemapPos, meta: M) -> Option<M> {
+        self.metadata.insert(pos, meta)
+    }
+
+    /// Removes the metadata attached to `pos` and returns it
+    pub fn remove_meta(&mut self, pos: TilemapPos) -> Option<M> {
+        self.metadata.remove(&pos)
+    }
+
+    /// Removes all tiles in the rectangle with corners `min` and `max` (inclusive)
+    ///
+    /// Tells each chunk that lost a tile to regenerate it's mesh the next time it's displayed.
+    /// Chunks sharing an edge with a removed tile are told to as well, the same as with
+    /// [`set()`](Self::set()).  Chunks left empty are dropped when their mesh is regenerated.
+    /// Positions outside of the [bounds](Self::bounds()) of this are skipped
+    pub fn clear_region(&mut self, min: IVec2, max: IVec2) {
+        let bounds = self.clamp_to_bounds(IRect::from_corners(min, max));
+        let mut edges = Vec::new();
+        for chunk_pos in bounds.chunks().iter_positions() {
+            if let Some(chunk) = self.get_chunk_mut(chunk_pos) {
+                let mut removed = false;
+                for pos in bounds
+                    .intersect(IRect::from_chunk(chunk_pos))
+                    .iter_positions()
+                    .map(TilemapPos::from)
+                {
+                    if chunk.set_raw(pos.tile, None).is_some() {
+                        removed = true;
+                        if on_chunk_edge(pos.tile) {
+                            edges.push(pos);
+                        }
+                    }
+                }
+                if removed {
+                    chunk.regenerate_mesh();
+                }
+            }
         }
-        TilemapPos { chunk, tile }
+        self.regenerate_neighbor_chunks(edges);
     }
-}
 
-impl SubAssign for TilemapPos {
-    fn sub_assign(&mut self, rhs: Self) {
-        let (tile, carry) = self.tile.overflowing_sub(rhs.tile);
-        self.tile = tile;
-        self.chunk -= rhs.chunk;
-        if carry.x {
-            self.chunk.x -= 1
+    /// Sets every tile within `radius` tiles of `center`
+    ///
+    /// A tile is filled if the squared distance between it and `center` is at most
+    /// `radius * radius`, so a radius of 0 fills only `center` and a radius of 1 fills a plus
+    /// shape.  Distances are measured in tile coordinates, regardless of
+    /// [layout](Self::layout()).  Only fills inside the [bounds](Self::bounds()) of this.
+    /// Tells each chunk filled to regenerate it's mesh the next time it's displayed.  Chunks
+    /// sharing an edge with a changed tile are told to as well, the same as with
+    /// [`set()`](Self::set())
+    pub fn fill_circle(&mut self, center: IVec2, radius: i32, tile: impl Into<T> + Clone) {
+        if radius < 0 {
+            return;
         }
-        if carry.y {
-            self.chunk.y -= 1
+        let bounds = self.clamp_to_bounds(IRect::new(
+            center - IVec2::splat(radius),
+            center + IVec2::splat(radius),
+        ));
+        let radius_squared = radius as i64 * radius as i64;
+        let mut edges = Vec::new();
+        for chunk_pos in bounds.chunks().iter_positions() {
+            let positions: Vec<IVec2> = bounds
+                .intersect(IRect::from_chunk(chunk_pos))
+                .iter_positions()
+                .filter(|pos| {
+                    let offset = *pos - center;
+                    offset.x as i64 * offset.x as i64 + offset.y as i64 * offset.y as i64
+                        <= radius_squared
+                })
+                .collect();
+            if !positions.is_empty() {
+                let chunk = self.get_or_create_chunk(chunk_pos);
+                for pos in positions.into_iter().map(TilemapPos::from) {
+                    chunk.set_raw(pos.tile, Some(tile.clone().into()));
+                    if on_chunk_edge(pos.tile) {
+                        edges.push(pos);
+                    }
+                }
+                chunk.regenerate_mesh();
+            }
         }
+        self.regenerate_neighbor_chunks(edges);
     }
-}
 
-impl Add<ChunkPos> for TilemapPos {
-    type Output = Self;
+    /// Sets every tile along the path through `points`, with a square brush `thickness` tiles
+    /// wide
+    ///
+    /// Consecutive points are connected with straight lines, so a single point fills only the
+    /// brush around it and no points fill nothing.  With an even thickness the brush extends
+    /// further in the positive directions.  Only fills inside the [bounds](Self::bounds()) of
+    /// this.  Tells each chunk filled to regenerate it's mesh the next time it's displayed.
+    /// Chunks sharing an edge with a changed tile are told to as well, the same as with
+    /// [`set()`](Self::set())
+    pub fn paint_path(&mut self, points: &[IVec2], thickness: u32, tile: impl Into<T> + Clone) {
+        if thickness == 0 {
+            return;
+        }
+        let centers = points
+            .windows(2)
+            .flat_map(|segment| line_positions(segment[0], segment[1]))
+            .chain(points.first().copied());
+        let brush_min = -IVec2::splat((thickness as i32 - 1) / 2);
+        let brush = IRect::new(brush_min, brush_min + IVec2::splat(thickness as i32 - 1));
 
-    #[must_use]
-    fn add(self, rhs: ChunkPos) -> Self::Output {
-        let mut chunk = self.chunk;
-        let (tile, carry) = self.tile.overflowing_add(rhs);
-        if carry.x {
-            chunk.x += 1
+        let mut by_chunk: HashMap<IVec2, Vec<ChunkPos>> = HashMap::default();
+        for center in centers {
+            for offset in brush.iter_positions() {
+                let pos = TilemapPos::from(center + offset);
+                if self.in_bounds(pos) {
+                    by_chunk.entry(pos.chunk).or_default().push(pos.tile);
+                }
+            }
         }
-        if carry.y {
-            chunk.y += 1
+        let mut edges = Vec::new();
+        for (chunk_pos, mut tiles) in by_chunk {
+            // Overlapping brushes would otherwise set the same tile many times
+            tiles.sort_unstable_by_key(|tile_pos| tile_pos.as_index());
+            tiles.dedup();
+            let chunk = self.get_or_create_chunk(chunk_pos);
+            for tile_pos in tiles {
+                chunk.set_raw(tile_pos, Some(tile.clone().into()));
+                if on_chunk_edge(tile_pos) {
+                    edges.push(TilemapPos {
+                        chunk: chunk_pos,
+                        tile: tile_pos,
+                    });
+                }
+            }
+            chunk.regenerate_mesh();
         }
-        TilemapPos { chunk, tile }
+        self.regenerate_neighbor_chunks(edges);
     }
-}
 
-impl AddAssign<ChunkPos> for TilemapPos {
-    fn add_assign(&mut self, rhs: ChunkPos) {
-        let (tile, carry) = self.tile.overflowing_add(rhs);
-        self.tile = tile;
-        if carry.x {
-            self.chunk.x += 1
+    /// Sets every tile inside the polygon with corners `vertices`
+    ///
+    /// Vertices are in tile coordinates, with tile (x, y) covering the square from (x, y) to
+    /// (x + 1, y + 1), and a tile is filled if it's center is inside the polygon.  Uses the
+    /// even-odd rule, so concave polygons are filled normally, and areas of self-intersecting
+    /// polygons enclosed an even number of times are left unchanged.  Fewer than 3 vertices
+    /// fill nothing.  Vertices aren't adjusted for [layout](Self::layout()).  Only fills inside
+    /// the [bounds](Self::bounds()) of this.  Tells each chunk filled to regenerate it's mesh
+    /// the next time it's displayed.  Chunks sharing an edge with a changed tile are told to
+    /// as well, the same as with [`set()`](Self::set())
+    pub fn fill_polygon(&mut self, vertices: &[IVec2], tile: impl Into<T> + Clone) {
+        if vertices.len() < 3 {
+            return;
         }
-        if carry.y {
-            self.chunk.y += 1
+        let min_y = vertices.iter().map(|vertex| vertex.y).min().unwrap();
+        let max_y = vertices.iter().map(|vertex| vertex.y).max().unwrap();
+        let edges = || vertices.iter().zip(vertices.iter().cycle().skip(1));
+
+        let mut by_chunk: HashMap<IVec2, Vec<ChunkPos>> = HashMap::default();
+        let mut crossings = Vec::new();
+        for y in min_y..max_y {
+            // Vertices are on whole coordinates, so no vertex is ever on the scanline
+            let scan_y = y as f32 + 0.5;
+            crossings.clear();
+            crossings.extend(
+                edges()
+                    .filter(|(a, b)| (a.y as f32 <= scan_y) != (b.y as f32 <= scan_y))
+                    .map(|(a, b)| {
+                        let (a, b) = (a.as_vec2(), b.as_vec2());
+                        a.x + (scan_y - a.y) * (b.x - a.x) / (b.y - a.y)
+                    }),
+            );
+            crossings.sort_by(f32::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0] - 0.5).ceil() as i32;
+                let end = (span[1] - 0.5).ceil() as i32;
+                for x in start..end {
+                    let pos = TilemapPos::from(IVec2::new(x, y));
+                    if self.in_bounds(pos) {
+                        by_chunk.entry(pos.chunk).or_default().push(pos.tile);
+                    }
+                }
+            }
+        }
+        let mut edges = Vec::new();
+        for (chunk_pos, tiles) in by_chunk {
+            let chunk = self.get_or_create_chunk(chunk_pos);
+            for tile_pos in tiles {
+                chunk.set_raw(tile_pos, Some(tile.clone().into()));
+                if on_chunk_edge(tile_pos) {
+                    edges.push(TilemapPos {
+                        chunk: chunk_pos,
+                        tile: tile_pos,
+                    });
+                }
+            }
+            chunk.regenerate_mesh();
         }
+        self.regenerate_neighbor_chunks(edges);
     }
-}
 
-impl Sub<ChunkPos> for TilemapPos {
-    type Output = Self;
+    /// Calls `f` with every position in `bounds`, setting the tile at that position to
+    /// the returned tile
+    ///
+    /// Positions `f` returns [`None`] for are left unchanged, and positions outside of the
+    /// [bounds](Self::bounds()) of this are skipped.  Positions are visited one chunk at a
+    /// time, so `f` should not depend on the order it is called in.
+    /// Tells each chunk a tile is set in to regenerate it's mesh the next time it's displayed.
+    /// Chunks sharing an edge with a changed tile are told to as well, the same as with
+    /// [`set()`](Self::set())
+    pub fn generate_region(&mut self, bounds: IRect, mut f: impl FnMut(IVec2) -> Option<T>) {
+        let bounds = self.clamp_to_bounds(bounds);
+        let mut edges = Vec::new();
+        for chunk_pos in bounds.chunks().iter_positions() {
+            let mut changed = false;
+            for pos in bounds
+                .intersect(IRect::from_chunk(chunk_pos))
+                .iter_positions()
+            {
+                if let Some(tile) = f(pos) {
+                    let pos = TilemapPos::from(pos);
+                    self.get_or_create_chunk(chunk_pos)
+                        .set_raw(pos.tile, Some(tile));
+                    changed = true;
+                    if on_chunk_edge(pos.tile) {
+                        edges.push(pos);
+                    }
+                }
+            }
+            if changed {
+                self.get_or_create_chunk(chunk_pos).regenerate_mesh();
+            }
+        }
+        self.regenerate_neighbor_chunks(edges);
+    }
+
+    /// Samples `noise` at every position in `bounds`, setting the tile at that position to the
+    /// tile `f` returns for the position and sampled value
+    ///
+    /// `noise` is sampled at the coordinates of each tile, not it's world position, so
+    /// scaling the input (for example, `|pos| perlin(pos / 16.0)`) is left to `noise`.
+    /// Otherwise the same as [`generate_region()`](Self::generate_region())
+    pub fn apply_noise(
+        &mut self,
+        bounds: IRect,
+        f: impl Fn(IVec2, f32) -> Option<T>,
+        noise: impl Fn(Vec2) -> f32,
+    ) {
+        self.generate_region(bounds, |pos| f(pos, noise(pos.as_vec2())))
+    }
+
+    /// Returns a reference to the handle for the material this uses
+    pub fn material(&self) -> &Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material> {
+        &self.material
+    }
+
+    /// Sets the material this uses
+    ///
+    /// Existing chunk meshes are switched to `material` the next time meshes are generated,
+    /// without regenerating them
+    pub fn set_material(
+        &mut self,
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+    ) {
+        self.material = material;
+        self.update_material = true;
+    }
 
+    /// Returns a reference to the handle for the material tiles in mesh `layer` use
+    ///
+    /// Layers without their own material use [the material of this](Self::material()).
+    /// See [`Tile::mesh_layer`]
     #[must_use]
-    fn sub(self, rhs: ChunkPos) -> Self::Output {
-        let mut chunk = self.chunk;
-        let (tile, carry) = self.tile.overflowing_sub(rhs);
-        if carry.x {
-            chunk.x -= 1
+    pub fn layer_material(
+        &self,
+        layer: u8,
+    ) -> &Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material> {
+        match layer {
+            0 => &self.material,
+            _ => self.layer_materials.get(&layer).unwrap_or(&self.material),
         }
-        if carry.y {
-            chunk.y -= 1
+    }
+
+    /// Sets the material tiles in mesh `layer` use
+    ///
+    /// Existing meshes of the layer are switched to `material` the next time meshes are
+    /// generated, without regenerating them.  Setting the material of layer 0 is the same as
+    /// calling [`set_material()`](Self::set_material())
+    pub fn set_layer_material(
+        &mut self,
+        layer: u8,
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+    ) {
+        match layer {
+            0 => self.material = material,
+            _ => {
+                self.layer_materials.insert(layer, material);
+            }
         }
-        TilemapPos { chunk, tile }
+        self.update_material = true;
     }
-}
 
-impl SubAssign<ChunkPos> for TilemapPos {
-    fn sub_assign(&mut self, rhs: ChunkPos) {
-        let (tile, carry) = self.tile.overflowing_sub(rhs);
-        self.tile = tile;
-        if carry.x {
-            self.chunk.x -= 1
+    /// Returns the maximum number of chunk meshes regenerated each frame
+    #[must_use]
+    pub fn max_regens_per_frame(&self) -> usize {
+        self.max_regens_per_frame
+    }
+
+    /// Sets the maximum number of chunk meshes regenerated each frame
+    ///
+    /// Chunks that don't fit in the budget stay flagged and are regenerated in later
+    /// frames, prioritizing chunks closest to the camera
+    pub fn set_max_regens_per_frame(&mut self, max_regens_per_frame: usize) {
+        self.max_regens_per_frame = max_regens_per_frame;
+    }
+
+    /// Returns the camera distances at which chunks switch to their next level of detail
+    #[must_use]
+    pub fn lod_distances(&self) -> &[f32] {
+        &self.lod_distances
+    }
+
+    /// Sets the camera distances at which chunks switch to their next level of detail
+    ///
+    /// Chunks further than `distances[n]` from the camera display their level of detail
+    /// `n + 1`, or their least detailed level if they don't have that many.  `distances` are
+    /// sorted, and are in world units.  Defaults to none, so chunks always display their most
+    /// detailed mesh.  See [`MeshBuilder::finish_with_lods()`]
+    pub fn set_lod_distances(&mut self, mut distances: Vec<f32>) {
+        distances.sort_by(f32::total_cmp);
+        self.lod_distances = distances;
+    }
+
+    /// Returns the level of detail the chunk at `chunk` should display with the camera at
+    /// `camera`, based on the [LOD distances](Self::set_lod_distances()) of this
+    ///
+    /// `camera` is relative to the origin of this.  Distance is measured to the center of the
+    /// chunk.  Not limited to the number of levels of detail the chunk has
+    #[must_use]
+    pub fn chunk_lod_level(&self, chunk: IVec2, camera: Vec2) -> usize {
+        let visual_offset = self
+            .get_chunk(chunk)
+            .map_or(Vec2::ZERO, Chunk::visual_offset);
+        let center = self
+            .layout
+            .tile_offset(chunk * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2)
+            + visual_offset;
+        let distance = center.distance(camera);
+        self.lod_distances
+            .iter()
+            .take_while(|threshold| distance > **threshold)
+            .count()
+    }
+
+    /// Returns how the tiles in this are arranged
+    #[must_use]
+    pub fn layout(&self) -> TileLayout {
+        self.layout
+    }
+
+    /// Returns the z coordinate chunk meshes are displayed at
+    #[must_use]
+    pub fn z(&self) -> f32 {
+        self.z
+    }
+
+    /// Sets the z coordinate chunk meshes are displayed at
+    ///
+    /// Doesn't require regenerating any meshes.  Defaults to 0
+    pub fn set_z(&mut self, z: f32) {
+        self.z = z;
+        for chunk in self.iter_chunks_mut() {
+            chunk.update_transform = true;
         }
-        if carry.y {
-            self.chunk.y -= 1
+    }
+
+    /// Sets how the tiles in this are arranged
+    ///
+    /// Tells every chunk to regenerate it's mesh the next time it's displayed
+    pub fn set_layout(&mut self, layout: TileLayout) {
+        self.layout = layout;
+        for chunk in self.iter_chunks_mut() {
+            chunk.regenerate_mesh();
+            chunk.update_transform = true;
         }
     }
-}
 
-impl Add<IVec2> for TilemapPos {
-    type Output = Self;
+    /// Returns an iterator over the positions of the neighbors of `pos`,
+    /// according to the [layout](Self::layout()) of this
+    ///
+    /// See [`TileLayout::neighbor_offsets()`] for the order neighbors are returned in
+    pub fn neighbor_positions(&self, pos: TilemapPos) -> impl Iterator<Item = TilemapPos> {
+        self.layout.neighbors(pos)
+    }
 
-    /// `self` + `rhs` chunks
-    fn add(self, rhs: IVec2) -> Self::Output {
-        TilemapPos {
-            chunk: self.chunk + rhs,
-            tile: self.tile,
+    /// Returns the position of the tile covering the point `world`, according to the
+    /// [layout](Self::layout()) of this
+    ///
+    /// `world` is relative to the origin of the tilemap
+    #[must_use]
+    pub fn world_to_tile(&self, world: Vec2) -> TilemapPos {
+        self.layout.world_to_tile(world).into()
+    }
+
+    /// Returns the transform the mesh of the chunk at `chunk` is displayed with
+    ///
+    /// Useful for placing entities aligned with the mesh of a chunk.  Includes the
+    /// [visual offset](Chunk::visual_offset()) of the chunk if it exists
+    #[must_use]
+    pub fn chunk_world_transform(&self, chunk: IVec2) -> Transform {
+        let visual_offset = self
+            .get_chunk(chunk)
+            .map_or(Vec2::ZERO, Chunk::visual_offset);
+        crate::chunk_transform(self.layout, self.z, chunk, visual_offset)
+    }
+
+    /// Returns an estimate of the number of bytes this uses
+    ///
+    /// Includes the storage reserved for chunks, but not memory owned by the tiles themselves
+    /// or by mesh carry data
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        // Each hashmap slot stores the key, the chunk, and a control byte
+        mem::size_of::<Self>()
+            + self.data.capacity() * (mem::size_of::<(IVec2, Chunk<T>)>() + 1)
+            + self.pool.capacity() * mem::size_of::<Chunk<T>>()
+    }
+
+    /// Reserves space for at least `additional` more chunks
+    ///
+    /// Doesn't change any tiles.  The space is kept when chunks are dropped
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional)
+    }
+
+    /// Creates every chunk overlapping `bounds` that doesn't already exist
+    ///
+    /// Doesn't change any tiles, and the new chunks aren't flagged to regenerate their
+    /// meshes.  Useful before filling a large area, so chunks aren't created one at a time
+    pub fn reserve_region(&mut self, bounds: IRect) {
+        if bounds.is_empty() {
+            return;
+        }
+        let chunks = bounds.chunks();
+        let size = chunks.size();
+        self.reserve((size.x * size.y) as usize);
+        for chunk_pos in chunks.iter_positions() {
+            self.get_or_create_chunk(chunk_pos);
         }
     }
-}
 
-impl AddAssign<IVec2> for TilemapPos {
-    /// `self` += `rhs` chunks
-    fn add_assign(&mut self, rhs: IVec2) {
-        self.chunk += rhs
+    /// Returns an iterator over all chunks in this
+    pub fn iter_chunks(&self) -> impl Iterator<Item = &Chunk<T>> {
+        self.data.values()
     }
-}
 
-impl Sub<IVec2> for TilemapPos {
-    type Output = Self;
+    /// Returns an iterator over all chunks in this that allows modifying each
+    pub fn iter_chunks_mut(&mut self) -> impl Iterator<Item = &mut Chunk<T>> {
+        self.data.values_mut()
+    }
 
-    /// `self` - `rhs` chunks
-    fn sub(self, rhs: IVec2) -> Self::Output {
-        TilemapPos {
-            chunk: self.chunk - rhs,
-            tile: self.tile,
+    /// Returns an iterator over all chunks in this and their positions
+    pub fn iter_chunk_positions(&self) -> impl Iterator<Item = (&IVec2, &Chunk<T>)> {
+        self.data.iter()
+    }
+
+    /// Returns an iterator over all chunks in this and their positions, yielding positions
+    /// by value
+    pub fn iter_chunk_positions_copied(&self) -> impl Iterator<Item = (IVec2, &Chunk<T>)> {
+        self.data.iter().map(|(pos, chunk)| (*pos, chunk))
+    }
+
+    /// Returns an iterator over all chunks in this and their positions
+    /// that allows modifying each chunk
+    pub fn iter_chunk_positions_mut(&mut self) -> impl Iterator<Item = (&IVec2, &mut Chunk<T>)> {
+        self.data.iter_mut()
+    }
+
+    /// Runs `f` on every chunk in this and it's position, spreading chunks across the
+    /// threads of bevy's [`ComputeTaskPool`]
+    ///
+    /// Chunks don't reference each other, so each call gets exclusive access to it's chunk.
+    /// Blocks until every chunk has been processed.  If `f` makes a change that requires
+    /// regenerating the chunk mesh, call [`Chunk::regenerate_mesh()`] on the chunk
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`ComputeTaskPool`] hasn't been initialized, which bevy's `TaskPoolPlugin`
+    /// (part of `DefaultPlugins` and `MinimalPlugins`) does
+    pub fn iter_chunks_mut_par(&mut self, f: impl Fn(IVec2, &mut Chunk<T>) + Send + Sync) {
+        let mut chunks: Vec<(&IVec2, &mut Chunk<T>)> = self.data.iter_mut().collect();
+        let task_pool = ComputeTaskPool::get();
+        chunks.par_splat_map_mut(task_pool, None, |batch| {
+            for (chunk_pos, chunk) in batch {
+                f(**chunk_pos, chunk);
+            }
+        });
+    }
+
+    /// Returns an iterator over the positions of all chunks in this
+    pub fn chunk_positions(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.data.keys().copied()
+    }
+
+    /// Returns an iterator over the positions of all chunks in this next to a position with
+    /// no chunk
+    ///
+    /// Only the 4 chunks sharing an edge with a chunk are checked.  Useful for finding where
+    /// to generate next when expanding a world
+    pub fn frontier_chunks(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.chunk_positions().filter(|pos| {
+            Direction::CARDINAL
+                .into_iter()
+                .any(|direction| !self.data.contains_key(&(*pos + direction.offset())))
+        })
+    }
+
+    /// Returns an iterator over the positions of all chunks that will regenerate their mesh
+    ///
+    /// Systems running before
+    /// [`RenderLabel::MeshGeneration`](crate::RenderLabel::MeshGeneration) can use this to see
+    /// which chunks changed since meshes were last generated.  Chunks waiting on their
+    /// [`min_regen_interval()`](Chunk::min_regen_interval()) or on
+    /// [`max_regens_per_frame()`](Self::max_regens_per_frame()) are included
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.data
+            .iter()
+            .filter(|(_, chunk)| chunk.regenerate_mesh)
+            .map(|(pos, _)| *pos)
+    }
+
+    /// Returns an iterator over all chunks that will regenerate their mesh and their positions,
+    /// allowing modifying each chunk
+    ///
+    /// For systems generating meshes instead of [`TilemapPlugin`](crate::TilemapPlugin).
+    /// Doesn't clear the flags; call [`cancel_regeneration()`](Self::cancel_regeneration())
+    /// once the meshes are generated
+    pub fn iter_dirty_chunks_mut(&mut self) -> impl Iterator<Item = (IVec2, &mut Chunk<T>)> {
+        self.data
+            .iter_mut()
+            .filter(|(_, chunk)| chunk.regenerate_mesh)
+            .map(|(pos, chunk)| (*pos, chunk))
+    }
+
+    /// Returns the number of chunks that will regenerate their mesh
+    #[must_use]
+    pub fn count_chunks_dirty(&self) -> usize {
+        self.dirty_chunks().count()
+    }
+
+    /// Returns the number of chunk meshes regenerated by the mesh generation system
+    #[must_use]
+    pub fn regen_stats(&self) -> RegenStats {
+        self.regen_stats
+    }
+
+    /// Tells every chunk in this to regenerate it's mesh the next time it's displayed
+    pub fn regenerate_all(&mut self) {
+        self.data.values_mut().for_each(Chunk::regenerate_mesh);
+    }
+
+    /// Stops every chunk in this from regenerating it's mesh until it is told to again
+    ///
+    /// Only use this when the current meshes are known to match the tiles, such as after
+    /// replacing them manually; otherwise they will be out of date.  Chunks left empty are
+    /// only dropped when regenerating their mesh, so they are kept until then
+    pub fn cancel_regeneration(&mut self) {
+        for chunk in self.data.values_mut() {
+            chunk.regenerate_mesh = false;
+            chunk.dirty_tiles = DirtyTiles::Clean;
         }
     }
-}
 
-impl SubAssign<IVec2> for TilemapPos {
-    /// `self` -= `rhs` chunks
-    fn sub_assign(&mut self, rhs: IVec2) {
-        self.chunk -= rhs
+    /// Generates the meshes of every chunk flagged to regenerate it's mesh and returns them
+    /// with the positions of their chunks, without using the ECS
+    ///
+    /// For using meshes outside of the Bevy renderer, such as when exporting.  Mesh entities
+    /// are not updated and the regeneration flags are cleared, so don't use this on a tilemap
+    /// displayed by [`TilemapPlugin`](crate::TilemapPlugin).  Chunks left empty are dropped
+    /// unless they have a mesh entity, in which case they are left flagged and no mesh is
+    /// returned for them.  Only the most detailed mesh of each chunk is returned; see
+    /// [`MeshBuilder::finish_with_lods()`].  Tiles outside
+    /// [mesh layer](crate::tile::Tile::mesh_layer) 0 aren't included
+    pub fn build_all_meshes(&mut self) -> Vec<(IVec2, Mesh)> {
+        let dirty: Vec<_> = self.dirty_chunks().collect();
+        let mut meshes = Vec::with_capacity(dirty.len());
+        for chunk_pos in dirty {
+            match crate::build_chunk_mesh(self, chunk_pos) {
+                Some(lods) => {
+                    let chunk = self.data.get_mut(&chunk_pos).unwrap();
+                    chunk.regenerate_mesh = false;
+                    chunk.dirty_tiles = DirtyTiles::Clean;
+                    meshes.push((chunk_pos, lods.into_iter().next().unwrap()));
+                }
+                None if self.data.get(&chunk_pos).unwrap().mesh_entity.is_none() => {
+                    self.recycle_chunk(chunk_pos)
+                }
+                None => {}
+            }
+        }
+        meshes
+    }
+
+    /// Returns an iterator over the tiles in the chunk at `chunk` and their positions in the
+    /// chunk
+    ///
+    /// Empty if the chunk doesn't exist
+    pub fn tiles_in_chunk(&self, chunk: IVec2) -> impl Iterator<Item = (ChunkPos, &T)> {
+        self.get_chunk(chunk)
+            .into_iter()
+            .flat_map(Chunk::iter_tile_positions)
+    }
+
+    /// Returns an iterator over all tiles in this
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.iter_chunks().flat_map(Chunk::iter_tiles)
+    }
+
+    /// Returns an iterator over all tiles in this that allows modifying each value
+    ///
+    /// If mutating the tile slot results in a change that requires
+    /// regenerating the chunk mesh, call [`Chunk::regenerate_mesh()`] on that chunk.
+    /// For an iterator that returns the chunk a tile is in as well,
+    /// use [`Self::iter_positions_mut()`]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.iter_chunks_mut().flat_map(Chunk::iter_tiles_mut)
+    }
+
+    /// Returns an iterator over all tiles in this and their positions
+    pub fn iter_positions(&self) -> impl Iterator<Item = (TilemapPos, &T)> {
+        self.iter_chunk_positions().flat_map(|(chunk_pos, chunk)| {
+            chunk.iter_tile_positions().map(move |(tile_pos, tile)| {
+                (
+                    TilemapPos {
+                        chunk: *chunk_pos,
+                        tile: tile_pos,
+                    },
+                    tile,
+                )
+            })
+        })
+    }
+
+    /// Returns an iterator over all tiles in this and their positions
+    /// that allows modifying each tile
+    ///
+    /// If mutating the tile slot results in a change that requires
+    /// regenerating the chunk mesh, call [`Chunk::regenerate_mesh()`] on that chunk.
+    pub fn iter_positions_mut(&mut self) -> impl Iterator<Item = (TilemapPos, &mut T)> {
+        self.iter_chunk_positions_mut()
+            .flat_map(|(chunk_pos, chunk)| {
+                chunk
+                    .iter_tile_positions_mut()
+                    .map(move |(tile_pos, tile)| {
+                        (
+                            TilemapPos {
+                                chunk: *chunk_pos,
+                                tile: tile_pos,
+                            },
+                            tile,
+                        )
+                    })
+            })
+    }
+
+    /// Removes every tile at a position where `stencil` has a tile
+    ///
+    /// Tells every chunk tiles are removed from to regenerate it's mesh the next time it's
+    /// displayed.  Chunks sharing an edge with a removed tile are told to as well, the same as
+    /// with [`set()`](Self::set()).  Metadata is not removed
+    pub fn subtract<U: Tile, N>(&mut self, stencil: &Tilemap<U, N>) {
+        let mut edges = Vec::new();
+        for (chunk_pos, stencil_chunk) in stencil.iter_chunk_positions() {
+            if let Some(chunk) = self.get_chunk_mut(*chunk_pos) {
+                chunk.retain(|tile, _| {
+                    let keep = !stencil_chunk.is_set(tile);
+                    if !keep && on_chunk_edge(tile) {
+                        edges.push(TilemapPos {
+                            chunk: *chunk_pos,
+                            tile,
+                        });
+                    }
+                    keep
+                });
+            }
+        }
+        self.regenerate_neighbor_chunks(edges);
+    }
+
+    /// Calls `f` on every tile in this and it's position, allowing modifying each tile
+    ///
+    /// Tells a chunk to regenerate it's mesh the next time it's displayed only if `f` returned
+    /// [`EditResult::VisualChange`] for at least one tile in it, or for a tile sharing an edge
+    /// with it
+    pub fn edit_all(&mut self, mut f: impl FnMut(TilemapPos, &mut T) -> EditResult) {
+        let mut edges = Vec::new();
+        for (chunk_pos, chunk) in self.data.iter_mut() {
+            let mut changed = false;
+            for (tile_pos, tile) in chunk.iter_tile_positions_mut() {
+                let pos = TilemapPos {
+                    chunk: *chunk_pos,
+                    tile: tile_pos,
+                };
+                if f(pos, tile) == EditResult::VisualChange {
+                    changed = true;
+                    if on_chunk_edge(tile_pos) {
+                        edges.push(pos);
+                    }
+                }
+            }
+            if changed {
+                chunk.regenerate_mesh();
+            }
+        }
+        self.regenerate_neighbor_chunks(edges);
+    }
+
+    /// Returns an iterator removing every tile from this and yielding it by value with it's
+    /// position
+    ///
+    /// Tiles are removed as they are yielded, so dropping the iterator early leaves the
+    /// remaining tiles in this.  Tells every chunk to regenerate it's mesh the next time it's
+    /// displayed, since every chunk loses tiles or neighbors; chunks left empty, and their
+    /// mesh entities, are dropped then.  Metadata is not removed
+    pub fn drain(&mut self) -> impl Iterator<Item = (TilemapPos, T)> + '_ {
+        self.regenerate_all();
+        self.data.iter_mut().flat_map(|(chunk_pos, chunk)| {
+            chunk
+                .iter_positions_mut()
+                .filter_map(move |(tile_pos, slot)| {
+                    slot.take().map(|tile| {
+                        (
+                            TilemapPos {
+                                chunk: *chunk_pos,
+                                tile: tile_pos,
+                            },
+                            tile,
+                        )
+                    })
+                })
+        })
+    }
+
+    /// Moves every tile and it's metadata `by` tiles
+    ///
+    /// Tells every chunk to regenerate it's mesh the next time it's displayed, so chunks
+    /// gaining new neighbors are regenerated too.  If `by` is a multiple of [`CHUNK_SIZE`]
+    /// chunks are moved whole, which is much faster than moving
+    /// tiles one by one.  Bounds are not moved or checked
+    pub fn shift(&mut self, by: IVec2) {
+        let chunk_size = CHUNK_SIZE as i32;
+        if by.x % chunk_size == 0 && by.y % chunk_size == 0 {
+            let chunk_offset = by / chunk_size;
+            self.data = mem::take(&mut self.data)
+                .into_chunks()
+                .map(|(chunk_pos, mut chunk)| {
+                    chunk.regenerate_mesh();
+                    chunk.update_transform = true;
+                    (chunk_pos + chunk_offset, chunk)
+                })
+                .collect();
+        } else {
+            let tiles: Vec<_> = self.drain().collect();
+            for (pos, tile) in tiles {
+                let pos = TilemapPos::from(IVec2::from(pos) + by);
+                let chunk = self.get_or_create_chunk(pos.chunk);
+                chunk.set_raw(pos.tile, Some(tile));
+                chunk.regenerate_mesh();
+            }
+        }
+        self.metadata = mem::take(&mut self.metadata)
+            .into_iter()
+            .map(|(pos, metadata)| (TilemapPos::from(IVec2::from(pos) + by), metadata))
+            .collect();
+    }
+
+    /// Returns an iterator over the tile slots in row `y` with x coordinates in `x_range`,
+    /// and their positions
+    ///
+    /// Iterates from left to right, looking each chunk up only once
+    pub fn iter_row(
+        &self,
+        y: i32,
+        x_range: Range<i32>,
+    ) -> impl Iterator<Item = (TilemapPos, Option<&T>)> {
+        self.iter_along(x_range.map(move |x| IVec2::new(x, y)))
+    }
+
+    /// Returns an iterator over the tile slots in column `x` with y coordinates in `y_range`,
+    /// and their positions
+    ///
+    /// Iterates from bottom to top, looking each chunk up only once
+    pub fn iter_column(
+        &self,
+        x: i32,
+        y_range: Range<i32>,
+    ) -> impl Iterator<Item = (TilemapPos, Option<&T>)> {
+        self.iter_along(y_range.map(move |y| IVec2::new(x, y)))
+    }
+
+    /// Returns an iterator over the tile slots in `bounds` and their positions, in row-major
+    /// order starting from the bottom left
+    fn iter_rows(&self, bounds: IRect) -> impl Iterator<Item = (TilemapPos, Option<&T>)> {
+        (bounds.min.y..=bounds.max.y).flat_map(move |y| {
+            self.iter_along((bounds.min.x..=bounds.max.x).map(move |x| IVec2::new(x, y)))
+        })
+    }
+
+    /// Returns an iterator over the tile slots at `positions`, reusing the last chunk looked
+    /// up while consecutive positions are in the same chunk
+    fn iter_along(
+        &self,
+        positions: impl Iterator<Item = IVec2>,
+    ) -> impl Iterator<Item = (TilemapPos, Option<&T>)> {
+        let mut last_chunk: Option<(IVec2, Option<&Chunk<T>>)> = None;
+        positions.map(move |pos| {
+            let pos = TilemapPos::from(pos);
+            let chunk = match last_chunk {
+                Some((chunk_pos, chunk)) if chunk_pos == pos.chunk => chunk,
+                _ => {
+                    let chunk = self.get_chunk(pos.chunk);
+                    last_chunk = Some((pos.chunk, chunk));
+                    chunk
+                }
+            };
+            let tile = chunk
+                .filter(|_| self.in_bounds(pos))
+                .and_then(|chunk| chunk[pos.tile].as_ref());
+            (pos, tile)
+        })
+    }
+
+    /// Returns a tile matching `pred` and it's position if there is one
+    ///
+    /// If multiple tiles match, which is returned is unspecified and may differ between runs.
+    /// For a reproducible result use [`Self::find_sorted()`]
+    pub fn find(&self, pred: impl Fn(&T) -> bool) -> Option<(TilemapPos, &T)> {
+        self.find_all(pred).next()
+    }
+
+    /// Returns an iterator over all tiles matching `pred` and their positions
+    ///
+    /// Iterates in an unspecified order
+    pub fn find_all(&self, pred: impl Fn(&T) -> bool) -> impl Iterator<Item = (TilemapPos, &T)> {
+        self.iter_positions().filter(move |(_, tile)| pred(tile))
+    }
+
+    /// Returns the tile matching `pred` with the lowest y position and it's position,
+    /// using the lowest x position to break ties
+    ///
+    /// Slower than [`Self::find()`] because every tile must be checked, but always returns
+    /// the same tile for the same tilemap
+    pub fn find_sorted(&self, pred: impl Fn(&T) -> bool) -> Option<(TilemapPos, &T)> {
+        self.find_all(pred).min_by_key(|(pos, _)| {
+            let IVec2 { x, y } = (*pos).into();
+            (y, x)
+        })
+    }
+
+    /// Returns an iterator over all positions in `bounds` that don't have a tile
+    ///
+    /// Positions in chunks that don't exist are empty, and are returned without
+    /// creating the chunk
+    pub fn iter_empty_in(&self, bounds: IRect) -> impl Iterator<Item = TilemapPos> + '_ {
+        bounds.chunks().iter_positions().flat_map(move |chunk_pos| {
+            let chunk = self.get_chunk(chunk_pos);
+            bounds
+                .intersect(IRect::from_chunk(chunk_pos))
+                .iter_positions()
+                .map(TilemapPos::from)
+                .filter(move |pos| !matches!(chunk, Some(chunk) if chunk.is_set(pos.tile)))
+        })
+    }
+
+    /// Returns the edges of every tile in `bounds` that is a member of a region but has a
+    /// neighbor that isn't
+    ///
+    /// Each edge is given as the [cardinal](Direction::CARDINAL) direction from the tile to
+    /// the neighbor across it.
+    /// `member` is called with the contents of a position to decide whether it is in the
+    /// region.  Neighbors outside of `bounds` are checked too, so a region touching the side
+    /// of `bounds` has edges there only if the tiles past it aren't members.  Neighbors are
+    /// found on a square grid, regardless of [layout](Self::layout())
+    pub fn region_border(
+        &self,
+        member: impl Fn(Option<&T>) -> bool,
+        bounds: IRect,
+    ) -> Vec<(TilemapPos, Direction)> {
+        let mut border = Vec::new();
+        for pos in bounds.iter_positions() {
+            let tile_pos = TilemapPos::from(pos);
+            if member(self.get(tile_pos)) {
+                for direction in Direction::CARDINAL {
+                    if !member(self.get(TilemapPos::from(pos + direction.offset()))) {
+                        border.push((tile_pos, direction));
+                    }
+                }
+            }
+        }
+        border
+    }
+
+    /// Returns an iterator over all tiles overlapping the axis-aligned box from `min` to `max`
+    /// and their positions
+    ///
+    /// `min` and `max` are relative to the origin of the tilemap, in tile units on a square
+    /// grid.  Tiles only partially covered by the box are included, but tiles only touching
+    /// it's edge are not
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (TilemapPos, &T)> {
+        let min = min.floor().as_ivec2();
+        let bounds = IRect::new(min, (max.ceil().as_ivec2() - IVec2::ONE).max(min));
+        bounds
+            .chunks()
+            .iter_positions()
+            .filter_map(|chunk_pos| self.get_chunk(chunk_pos).map(|chunk| (chunk_pos, chunk)))
+            .flat_map(move |(chunk_pos, chunk)| {
+                bounds
+                    .intersect(IRect::from_chunk(chunk_pos))
+                    .iter_positions()
+                    .map(TilemapPos::from)
+                    .filter_map(move |pos| chunk[pos.tile].as_ref().map(|tile| (pos, tile)))
+            })
+    }
+
+    /// Returns an iterator over every tile slot a ray passes through and their positions,
+    /// in the order the ray reaches them
+    ///
+    /// `origin` is relative to the origin of the tilemap and `dir` is the direction of the ray,
+    /// both in tile units on a square grid.  Starts with the tile containing `origin` and stops
+    /// once the ray has travelled `max_dist` units.  Empty tile slots are included.  A ray
+    /// passing exactly through a corner of a tile only passes through one of the tiles beside
+    /// that corner
+    pub fn ray_tiles(
+        &self,
+        origin: Vec2,
+        dir: Vec2,
+        max_dist: f32,
+    ) -> impl Iterator<Item = (TilemapPos, Option<&T>)> {
+        let dir = dir.normalize_or_zero();
+        let step = IVec2::new(
+            (dir.x > 0.0) as i32 - (dir.x < 0.0) as i32,
+            (dir.y > 0.0) as i32 - (dir.y < 0.0) as i32,
+        );
+        // Distance along the ray to cross one tile, and to reach the next tile, on each axis
+        let t_delta = dir.recip().abs();
+        let mut cell = origin.floor().as_ivec2();
+        let next_edge = cell.as_vec2() + step.max(IVec2::ZERO).as_vec2();
+        let mut t_max = Vec2::select(
+            dir.cmpeq(Vec2::ZERO),
+            Vec2::splat(f32::INFINITY),
+            (next_edge - origin) / dir,
+        );
+
+        let mut done = false;
+        self.iter_along(iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let current = cell;
+            if t_max.x < t_max.y {
+                done = t_max.x > max_dist;
+                cell.x += step.x;
+                t_max.x += t_delta.x;
+            } else {
+                // Both are infinite if the ray has no direction
+                done = t_max.y > max_dist || t_max.y.is_infinite();
+                cell.y += step.y;
+                t_max.y += t_delta.y;
+            }
+            Some(current)
+        }))
+    }
+
+    /// Returns the tile matching `pred` closest to `center` and it's position, if there is one
+    /// within `max_radius`
+    ///
+    /// `center` is relative to the origin of the tilemap, in tile units on a square grid, and
+    /// distances are measured to the centers of tiles.  Searches in rings of tiles outward from
+    /// `center`, stopping as soon as no further tile can be closer or the rings are past every
+    /// chunk.  Returns [`None`] if `max_radius` is NaN or infinite
+    #[must_use]
+    pub fn nearest(
+        &self,
+        center: Vec2,
+        pred: impl Fn(&T) -> bool,
+        max_radius: f32,
+    ) -> Option<(TilemapPos, &T)> {
+        if !max_radius.is_finite() || !center.is_finite() {
+            return None;
+        }
+        let center_tile = center.floor().as_ivec2();
+        // Rings further out than this contain no chunks
+        let last_ring = self
+            .iter_chunk_positions()
+            .map(|(chunk_pos, _)| {
+                let rect = IRect::from_chunk(*chunk_pos);
+                let center = center_tile.as_dvec2();
+                (rect.min.as_dvec2() - center)
+                    .abs()
+                    .max((rect.max.as_dvec2() - center).abs())
+                    .max_element()
+            })
+            .reduce(f64::max)?;
+        let mut nearest: Option<(f32, TilemapPos, &T)> = None;
+        for ring in 0.. {
+            // Every tile in this ring is at least this far from `center`
+            let min_dist = ring as f32 - 0.5;
+            if min_dist > max_radius
+                || ring as f64 > last_ring
+                || matches!(nearest, Some((dist_sq, ..)) if dist_sq <= min_dist * min_dist)
+            {
+                break;
+            }
+
+            let (x_range, y_range) = (
+                center_tile.x - ring..center_tile.x + ring + 1,
+                center_tile.y - ring + 1..center_tile.y + ring,
+            );
+            let ring_tiles: Box<dyn Iterator<Item = (TilemapPos, Option<&T>)>> = if ring == 0 {
+                Box::new(self.iter_row(center_tile.y, x_range))
+            } else {
+                Box::new(
+                    self.iter_row(center_tile.y - ring, x_range.clone())
+                        .chain(self.iter_row(center_tile.y + ring, x_range))
+                        .chain(self.iter_column(center_tile.x - ring, y_range.clone()))
+                        .chain(self.iter_column(center_tile.x + ring, y_range)),
+                )
+            };
+            for (pos, tile) in ring_tiles {
+                let tile = match tile {
+                    Some(tile) if pred(tile) => tile,
+                    _ => continue,
+                };
+                let dist_sq =
+                    (IVec2::from(pos).as_vec2() + Vec2::splat(0.5)).distance_squared(center);
+                if dist_sq <= max_radius * max_radius
+                    && !matches!(nearest, Some((nearest_dist_sq, ..)) if nearest_dist_sq <= dist_sq)
+                {
+                    nearest = Some((dist_sq, pos, tile));
+                }
+            }
+        }
+        nearest.map(|(_, pos, tile)| (pos, tile))
+    }
+
+    /// Returns the opacity of every tile slot in `bounds`, in row-major order
+    ///
+    /// `opacity` is called with each tile, or [`None`] for empty tile slots, and should
+    /// return a value from 0 (transparent) to 1 (opaque).  Useful for feeding lighting
+    /// shaders and shadow passes
+    #[must_use]
+    pub fn occlusion_grid(&self, bounds: IRect, opacity: impl Fn(Option<&T>) -> f32) -> Vec<f32> {
+        self.iter_rows(bounds)
+            .map(|(_, tile)| opacity(tile))
+            .collect()
+    }
+
+    /// Returns whether every tile slot in `bounds` [blocks light](Tile::blocks_light), in
+    /// row-major order
+    ///
+    /// Empty tile slots don't block light.  For lighting plugins that don't know the tile
+    /// type; see [`occlusion_grid()`](Self::occlusion_grid()) for partial opacity
+    #[must_use]
+    pub fn build_light_mask(&self, bounds: IRect) -> Vec<bool> {
+        self.iter_rows(bounds)
+            .map(|(_, tile)| matches!(tile, Some(tile) if tile.blocks_light()))
+            .collect()
+    }
+
+    /// Returns the number of tiles in `bounds` matching `pred`
+    ///
+    /// Only chunks overlapping `bounds` are checked
+    #[must_use]
+    pub fn count_in_region(&self, bounds: IRect, pred: impl Fn(&T) -> bool) -> usize {
+        bounds
+            .chunks()
+            .iter_positions()
+            .filter_map(|chunk_pos| self.get_chunk(chunk_pos).map(|chunk| (chunk_pos, chunk)))
+            .map(|(chunk_pos, chunk)| {
+                bounds
+                    .intersect(IRect::from_chunk(chunk_pos))
+                    .iter_positions()
+                    .filter_map(|pos| chunk[TilemapPos::from(pos).tile].as_ref())
+                    .filter(|tile| pred(tile))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Places a random tile at each empty position in `bounds` with probability `density`
+    ///
+    /// Tiles are chosen from `weights`, each with a chance proportional to it's weight.
+    /// Does nothing if `weights` is empty or has no positive weights.  Tells every chunk a
+    /// tile is placed in to regenerate it's mesh the next time it's displayed
+    #[cfg(feature = "rand")]
+    pub fn scatter(
+        &mut self,
+        bounds: IRect,
+        density: f32,
+        weights: &[(T, f32)],
+        rng: &mut impl rand::Rng,
+    ) {
+        use rand::distributions::{Distribution, WeightedIndex};
+
+        let distribution = match WeightedIndex::new(weights.iter().map(|(_, weight)| *weight)) {
+            Ok(distribution) => distribution,
+            Err(_) => return,
+        };
+        let empty: Vec<TilemapPos> = self.iter_empty_in(bounds).collect();
+        for pos in empty {
+            if rng.gen::<f32>() < density {
+                let tile = weights[distribution.sample(rng)].0.clone();
+                self.set(pos, tile);
+            }
+        }
+    }
+
+    /// Returns an iterator over the positions of every chunk visible to a camera,
+    /// whether or not the chunk exists
+    ///
+    /// `camera_transform` and `projection` are the components of the camera.
+    /// Doesn't account for chunk [visual offsets](Chunk::visual_offset())
+    pub fn chunks_in_view(
+        &self,
+        camera_transform: &GlobalTransform,
+        projection: &OrthographicProjection,
+    ) -> impl Iterator<Item = IVec2> {
+        let corners = [
+            Vec2::new(projection.left, projection.bottom),
+            Vec2::new(projection.right, projection.bottom),
+            Vec2::new(projection.left, projection.top),
+            Vec2::new(projection.right, projection.top),
+        ]
+        .map(|corner| {
+            camera_transform
+                .mul_vec3((corner * projection.scale).extend(0.0))
+                .truncate()
+        });
+        let tiles = corners.map(|corner| self.layout.world_to_tile(corner));
+        let min = tiles.into_iter().reduce(IVec2::min).unwrap() - IVec2::ONE;
+        let max = tiles.into_iter().reduce(IVec2::max).unwrap() + IVec2::ONE;
+        IRect::new(min, max).chunks().iter_positions()
+    }
+
+    /// Returns a copy of the tiles in this, which can later be restored with
+    /// [`restore()`](Self::restore())
+    #[must_use]
+    pub fn snapshot(&self) -> TilemapSnapshot<T> {
+        TilemapSnapshot {
+            data: self.data.clone(),
+        }
+    }
+
+    /// Replaces the tiles in this with the tiles in `snapshot`
+    ///
+    /// Tells every chunk to regenerate it's mesh the next time it's displayed
+    pub fn restore(&mut self, snapshot: &TilemapSnapshot<T>) {
+        for (pos, chunk) in self.data.iter_mut() {
+            match snapshot.data.get(pos) {
+                Some(snapshot_chunk) => {
+                    for (slot, tile) in chunk.iter_mut().zip(snapshot_chunk.iter()) {
+                        slot.clone_from(tile);
+                    }
+                }
+                None => chunk.iter_mut().for_each(|slot| *slot = None),
+            }
+            chunk.regenerate_mesh();
+        }
+        for (pos, snapshot_chunk) in snapshot.data.iter() {
+            if !self.data.contains_key(pos) {
+                self.data.insert(*pos, snapshot_chunk.clone());
+            }
+        }
+    }
+
+    /// Checks that the internal state of this is consistent, returning a description
+    /// of the first problem found if it isn't
+    ///
+    /// Intended for finding bugs in this crate and in code using it.  As long as
+    /// [`Chunk::regenerate_mesh()`] is called after every change that requires it,
+    /// this should never return an error.  Checks that need to look at every tile slot
+    /// are only made in debug builds
+    pub fn validate(&self) -> Result<(), String> {
+        let mut mesh_entities = HashMap::default();
+        for (pos, chunk) in self.iter_chunk_positions() {
+            if !chunk.regenerate_mesh && chunk.dirty_tiles != DirtyTiles::Clean {
+                return Err(format!(
+                    "chunk {pos} has changed tiles, but isn't flagged to regenerate it's mesh"
+                ));
+            }
+            if let (true, Some(cached)) = (cfg!(debug_assertions), chunk.cached_tile_count()) {
+                let count = chunk.iter_tiles().count();
+                if cached != count {
+                    return Err(format!(
+                        "chunk {pos} has {count} tiles, but it's cached tile count is {cached}"
+                    ));
+                }
+            }
+            if !chunk.lod_meshes.is_empty() && chunk.lod_level >= chunk.lod_meshes.len() {
+                return Err(format!(
+                    "chunk {pos} displays level of detail {}, but only has {}",
+                    chunk.lod_level,
+                    chunk.lod_meshes.len()
+                ));
+            }
+            match chunk.mesh_entity {
+                Some(entity) => {
+                    if let Some(other) = mesh_entities.insert(entity, *pos) {
+                        return Err(format!(
+                            "chunks {pos} and {other} share mesh entity {entity:?}"
+                        ));
+                    }
+                    if cfg!(debug_assertions) && !chunk.regenerate_mesh && chunk.is_empty() {
+                        return Err(format!(
+                            "chunk {pos} has a mesh entity but no tiles, and isn't flagged to regenerate it's mesh"
+                        ));
+                    }
+                }
+                None => {
+                    if !chunk.sublayer_entities.is_empty() || !chunk.lod_meshes.is_empty() {
+                        return Err(format!(
+                            "chunk {pos} has no mesh entity, but has sublayer entities or meshes"
+                        ));
+                    }
+                }
+            }
+        }
+        for (i, chunk) in self.pool.iter().enumerate() {
+            if !chunk.is_reset() || (cfg!(debug_assertions) && !chunk.is_empty()) {
+                return Err(format!("pooled chunk {i} wasn't reset"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates an empty tilemap whose chunk meshes use `material`, which can attach metadata
+    /// of type `M` to positions
+    ///
+    /// Otherwise the same as [`new()`](Tilemap::new()).  See [`set_meta()`](Self::set_meta())
+    #[must_use]
+    pub fn with_metadata(
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+    ) -> Self {
+        Self::empty(0, material)
+    }
+
+    /// Creates a tilemap whose chunk meshes use `material`, containing the tiles in `grid`
+    ///
+    /// `grid` is a list of rows, with the first row at `origin` and each following row above
+    /// the last.  Errors if the rows are not all the same length.  Every chunk is flagged to
+    /// generate it's mesh the next time it's displayed.  No metadata is attached; see
+    /// [`with_metadata()`](Self::with_metadata())
+    pub fn from_grid(
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+        origin: IVec2,
+        grid: &[&[Option<T>]],
+    ) -> Result<Self, RaggedGrid> {
+        let width = grid.first().map_or(0, |row| row.len());
+        if let Some(row) = grid.iter().position(|row| row.len() != width) {
+            return Err(RaggedGrid(row));
+        }
+        let mut tilemap = Self::empty(0, material);
+        for (y, row) in grid.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if let Some(tile) = tile {
+                    let pos = TilemapPos::from(origin + IVec2::new(x as i32, y as i32));
+                    tilemap.set(pos, tile.clone());
+                }
+            }
+        }
+        Ok(tilemap)
+    }
+
+    fn empty(
+        capacity: usize,
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+    ) -> Self {
+        Tilemap {
+            data: ChunkMap::with_capacity(capacity),
+            material,
+            update_material: false,
+            layer_materials: HashMap::default(),
+            max_regens_per_frame: usize::MAX,
+            lod_distances: Vec::new(),
+            layout: TileLayout::default(),
+            z: 0.0,
+            metadata: HashMap::default(),
+            pool: Vec::new(),
+            max_pool_size: 0,
+            bounds: None,
+            regen_stats: RegenStats::default(),
+            hooks_enabled: false,
+            pending_hooks: Vec::new(),
+        }
+    }
+}
+
+impl<T: Tile> Tilemap<T> {
+    /// Creates an empty tilemap whose chunk meshes use `material`
+    ///
+    /// [`TilemapPlugin`](crate::TilemapPlugin) creates one at startup, unless one has
+    /// already been inserted as a resource.  Use this to build a tilemap before then
+    #[must_use]
+    pub fn new(material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>) -> Self {
+        Self::with_capacity_and_material(0, material)
+    }
+
+    /// Creates an empty tilemap with room for at least `capacity` chunks, whose chunk meshes
+    /// use `material`
+    ///
+    /// See [`new()`](Self::new())
+    #[must_use]
+    pub fn with_capacity_and_material(
+        capacity: usize,
+        material: Handle<<<T as Tile>::MeshBuilder as MeshBuilder>::Material>,
+    ) -> Self {
+        Self::empty(capacity, material)
+    }
+}
+
+/// A copy of the tiles in a [`Tilemap`], created by [`Tilemap::snapshot()`]
+///
+/// Doesn't include the state of chunk meshes
+#[derive(Debug, Clone)]
+pub struct TilemapSnapshot<T: Tile> {
+    data: ChunkMap<T>,
+}
+
+impl<T: Tile + PartialEq, M> Tilemap<T, M> {
+    /// Replaces the region of tile slots connected to `start` that match the slot at `start`
+    /// with `tile`, staying inside `bounds`
+    ///
+    /// Slots are connected if they share an edge, or with [`Connectivity::Eight`] also a
+    /// corner, regardless of [layout](Self::layout()).  `bounds` caps the fill, since filling
+    /// empty slots in an unbounded tilemap would never end; with either connectivity no slot
+    /// outside of `bounds` is filled or stepped through, so regions only connected outside of
+    /// `bounds` are not filled.  Only fills inside the [bounds](Self::bounds()) of this.
+    /// Tells every chunk filled to regenerate it's mesh the next time it's displayed.  Chunks
+    /// sharing an edge with a changed tile are told to as well, the same as with
+    /// [`set()`](Self::set())
+    pub fn flood_fill(
+        &mut self,
+        start: TilemapPos,
+        tile: T,
+        bounds: IRect,
+        connectivity: Connectivity,
+    ) {
+        let target = self
+            .get_chunk(start.chunk)
+            .and_then(|c| c[start.tile].clone());
+        if target.as_ref() == Some(&tile) {
+            return;
+        }
+        let mut stack = vec![IVec2::from(start)];
+        let mut edges = Vec::new();
+        while let Some(pos) = stack.pop() {
+            let tile_pos = TilemapPos::from(pos);
+            if !bounds.contains(pos)
+                || !self.in_bounds(tile_pos)
+                || self
+                    .get_chunk(tile_pos.chunk)
+                    .and_then(|c| c[tile_pos.tile].as_ref())
+                    != target.as_ref()
+            {
+                continue;
+            }
+            let chunk = self.get_or_create_chunk(tile_pos.chunk);
+            chunk.set_raw(tile_pos.tile, Some(tile.clone()));
+            chunk.regenerate_mesh();
+            if on_chunk_edge(tile_pos.tile) {
+                edges.push(tile_pos);
+            }
+            stack.extend(connectivity.offsets().map(|offset| pos + offset));
+        }
+        self.regenerate_neighbor_chunks(edges);
+    }
+
+    /// Replaces every tile equal to `from` with `to`
+    ///
+    /// Only tells chunks that had a matching tile, and chunks sharing an edge with a replaced
+    /// tile, to regenerate their mesh the next time they're displayed
+    pub fn replace_all(&mut self, from: &T, to: T) {
+        let mut edges = Vec::new();
+        for (chunk_pos, chunk) in self.data.iter_mut() {
+            let mut replaced = false;
+            for (tile_pos, tile) in chunk
+                .iter_tile_positions_mut()
+                .filter(|(_, tile)| *tile == from)
+            {
+                tile.clone_from(&to);
+                replaced = true;
+                if on_chunk_edge(tile_pos) {
+                    edges.push(TilemapPos {
+                        chunk: *chunk_pos,
+                        tile: tile_pos,
+                    });
+                }
+            }
+            if replaced {
+                chunk.regenerate_mesh();
+            }
+        }
+        self.regenerate_neighbor_chunks(edges);
+    }
+
+    /// Returns every position where this and `other` have different tiles, with the tile in
+    /// this and the tile in `other`
+    ///
+    /// Chunks that exist in only one of the tilemaps are compared as if they were empty in
+    /// the other.  Positions are returned grouped by chunk, in no particular order
+    #[must_use]
+    pub fn diff<N>(&self, other: &Tilemap<T, N>) -> Vec<(TilemapPos, Option<T>, Option<T>)> {
+        let mut differences = Vec::new();
+        let chunks = self.chunk_positions().chain(
+            other
+                .chunk_positions()
+                .filter(|pos| !self.data.contains_key(pos)),
+        );
+        for chunk_pos in chunks {
+            let (old, new) = (self.get_chunk(chunk_pos), other.get_chunk(chunk_pos));
+            for tile in ChunkPos::iter_positions() {
+                let old = old.and_then(|chunk| chunk[tile].as_ref());
+                let new = new.and_then(|chunk| chunk[tile].as_ref());
+                if old != new {
+                    differences.push((
+                        TilemapPos {
+                            chunk: chunk_pos,
+                            tile,
+                        },
+                        old.cloned(),
+                        new.cloned(),
+                    ));
+                }
+            }
+        }
+        differences
+    }
+}
+
+impl<T: Tile + PartialEq, M> PartialEq for Tilemap<T, M> {
+    /// Compares the tiles in the tilemaps, ignoring materials and the state of chunk meshes
+    ///
+    /// A chunk with no tiles is equal to a chunk that doesn't exist
+    fn eq(&self, other: &Self) -> bool {
+        self.iter_chunk_positions()
+            .all(|(pos, chunk)| match other.get_chunk(*pos) {
+                Some(other_chunk) => chunk == other_chunk,
+                None => chunk.is_empty(),
+            })
+            && other
+                .iter_chunk_positions()
+                .all(|(pos, chunk)| self.data.contains_key(pos) || chunk.is_empty())
+    }
+}
+
+impl<T: Tile + Eq, M> Eq for Tilemap<T, M> {}
+
+/// A position in a tilemap
+///
+/// Stored as the chunk the position is in and which tile the position is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapPos {
+    /// The chunk the position is in
+    pub chunk: IVec2,
+    /// The tile in the chunk the position is
+    pub tile: ChunkPos,
+}
+
+impl TilemapPos {
+    /// Tile (0, 0) in chunk (0, 0)
+    pub const ZERO: Self = TilemapPos {
+        chunk: IVec2::ZERO,
+        tile: ChunkPos::ZERO,
+    };
+
+    /// Packs this into a single integer, for use as a compact key
+    ///
+    /// The x coordinate of the tile in the tilemap is stored in the high 32 bits and the y
+    /// coordinate in the low 32 bits.  Positions too far from the origin for their coordinates
+    /// to fit in an [`IVec2`] wrap around
+    #[must_use]
+    pub fn pack(self) -> u64 {
+        let chunk_size = CHUNK_SIZE as i32;
+        let x = self
+            .chunk
+            .x
+            .wrapping_mul(chunk_size)
+            .wrapping_add(self.tile.x() as i32);
+        let y = self
+            .chunk
+            .y
+            .wrapping_mul(chunk_size)
+            .wrapping_add(self.tile.y() as i32);
+        (x as u32 as u64) << 32 | y as u32 as u64
+    }
+
+    /// Unpacks a position packed with [`pack()`](Self::pack())
+    #[must_use]
+    pub fn unpack(packed: u64) -> Self {
+        IVec2::new((packed >> 32) as u32 as i32, packed as u32 as i32).into()
+    }
+}
+
+impl fmt::Display for TilemapPos {
+    /// Formats this as the position of the tile in the tilemap; `(x, y)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let IVec2 { x, y } = (*self).into();
+        write!(f, "({x}, {y})")
+    }
+}
+
+impl From<IVec2> for TilemapPos {
+    #[must_use]
+    fn from(v: IVec2) -> Self {
+        TilemapPos {
+            chunk: IVec2::new(
+                v.x.div_euclid(CHUNK_SIZE as i32),
+                v.y.div_euclid(CHUNK_SIZE as i32),
+            ),
+            tile: ChunkPos::new(
+                v.x as u8 & (CHUNK_SIZE as u8 - 1),
+                v.y as u8 & (CHUNK_SIZE as u8 - 1),
+            ),
+        }
+    }
+}
+
+impl From<TilemapPos> for IVec2 {
+    #[must_use]
+    #[inline]
+    fn from(v: TilemapPos) -> Self {
+        v.chunk * (CHUNK_SIZE as i32) + v.tile.as_ivec2()
+    }
+}
+
+impl Add for TilemapPos {
+    type Output = Self;
+
+    #[must_use]
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut chunk = self.chunk + rhs.chunk;
+        let (tile, carry) = self.tile.overflowing_add(rhs.tile);
+        if carry.x {
+            chunk.x += 1
+        }
+        if carry.y {
+            chunk.y += 1
+        }
+        TilemapPos { chunk, tile }
+    }
+}
+
+impl AddAssign for TilemapPos {
+    fn add_assign(&mut self, rhs: Self) {
+        let (tile, carry) = self.tile.overflowing_add(rhs.tile);
+        self.tile = tile;
+        self.chunk += rhs.chunk;
+        if carry.x {
+            self.chunk.x += 1
+        }
+        if carry.y {
+            self.chunk.y += 1
+        }
+    }
+}
+
+impl Sub for TilemapPos {
+    type Output = Self;
+
+    #[must_use]
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut chunk = self.chunk - rhs.chunk;
+        let (tile, carry) = self.tile.overflowing_sub(rhs.tile);
+        if carry.x {
+            chunk.x -= 1;
+        }
+        if carry.y {
+            chunk.y -= 1;
+        }
+        TilemapPos { chunk, tile }
+    }
+}
+
+impl SubAssign for TilemapPos {
+    fn sub_assign(&mut self, rhs: Self) {
+        let (tile, carry) = self.tile.overflowing_sub(rhs.tile);
+        self.tile = tile;
+        self.chunk -= rhs.chunk;
+        if carry.x {
+            self.chunk.x -= 1
+        }
+        if carry.y {
+            self.chunk.y -= 1
+        }
+    }
+}
+
+impl Add<ChunkPos> for TilemapPos {
+    type Output = Self;
+
+    #[must_use]
+    fn add(self, rhs: ChunkPos) -> Self::Output {
+        let mut chunk = self.chunk;
+        let (tile, carry) = self.tile.overflowing_add(rhs);
+        if carry.x {
+            chunk.x += 1
+        }
+        if carry.y {
+            chunk.y += 1
+        }
+        TilemapPos { chunk, tile }
+    }
+}
+
+impl AddAssign<ChunkPos> for TilemapPos {
+    fn add_assign(&mut self, rhs: ChunkPos) {
+        let (tile, carry) = self.tile.overflowing_add(rhs);
+        self.tile = tile;
+        if carry.x {
+            self.chunk.x += 1
+        }
+        if carry.y {
+            self.chunk.y += 1
+        }
+    }
+}
+
+impl Sub<ChunkPos> for TilemapPos {
+    type Output = Self;
+
+    #[must_use]
+    fn sub(self, rhs: ChunkPos) -> Self::Output {
+        let mut chunk = self.chunk;
+        let (tile, carry) = self.tile.overflowing_sub(rhs);
+        if carry.x {
+            chunk.x -= 1
+        }
+        if carry.y {
+            chunk.y -= 1
+        }
+        TilemapPos { chunk, tile }
+    }
+}
+
+impl SubAssign<ChunkPos> for TilemapPos {
+    fn sub_assign(&mut self, rhs: ChunkPos) {
+        let (tile, carry) = self.tile.overflowing_sub(rhs);
+        self.tile = tile;
+        if carry.x {
+            self.chunk.x -= 1
+        }
+        if carry.y {
+            self.chunk.y -= 1
+        }
+    }
+}
+
+impl Add<IVec2> for TilemapPos {
+    type Output = Self;
+
+    /// `self` + `rhs` chunks
+    fn add(self, rhs: IVec2) -> Self::Output {
+        TilemapPos {
+            chunk: self.chunk + rhs,
+            tile: self.tile,
+        }
+    }
+}
+
+impl AddAssign<IVec2> for TilemapPos {
+    /// `self` += `rhs` chunks
+    fn add_assign(&mut self, rhs: IVec2) {
+        self.chunk += rhs
+    }
+}
+
+impl Sub<IVec2> for TilemapPos {
+    type Output = Self;
+
+    /// `self` - `rhs` chunks
+    fn sub(self, rhs: IVec2) -> Self::Output {
+        TilemapPos {
+            chunk: self.chunk - rhs,
+            tile: self.tile,
+        }
+    }
+}
+
+impl SubAssign<IVec2> for TilemapPos {
+    /// `self` -= `rhs` chunks
+    fn sub_assign(&mut self, rhs: IVec2) {
+        self.chunk -= rhs
+    }
+}
+
+/// Whether an edit made by [`Tilemap::edit_all()`] changed the mesh of a tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditResult {
+    /// The tile wasn't changed, or the change doesn't affect it's mesh
+    Unchanged,
+    /// The mesh of the tile must be regenerated
+    VisualChange,
+}
+
+/// The error when creating a [`Tilemap`] from a grid with rows of different lengths;
+/// holds the index of the first row with a different length than the first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaggedGrid(pub usize);
+
+impl fmt::Display for RaggedGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "row {} of the grid has a different length than row 0",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for RaggedGrid {}
+
+/// The reasons moving a tile with [`Tilemap::move_tile()`] can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    /// There is no tile at this position to move
+    Empty(TilemapPos),
+    /// There is already a tile at this position
+    Occupied(TilemapPos),
+    /// This position is outside of the bounds of the tilemap
+    OutOfBounds(TilemapPos),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::Empty(pos) => write!(f, "no tile to move at {pos}"),
+            MoveError::Occupied(pos) => write!(f, "tile position {pos} is already occupied"),
+            MoveError::OutOfBounds(pos) => {
+                write!(f, "tile position {pos} is outside of the tilemap bounds")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+/// The reasons moving a chunk with [`Tilemap::relocate_chunk()`] can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkMoveError {
+    /// There are no tiles in the chunk at this position to move
+    Empty(IVec2),
+    /// There are already tiles in the chunk at this position
+    Occupied(IVec2),
+}
+
+impl fmt::Display for ChunkMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkMoveError::Empty(pos) => write!(f, "no tiles to move in chunk {pos}"),
+            ChunkMoveError::Occupied(pos) => write!(f, "chunk {pos} is already occupied"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkMoveError {}
+
+/// The number of chunk meshes regenerated, returned by [`Tilemap::regen_stats()`]
+///
+/// Useful for detecting chunks that are accidentally regenerated every frame.  Patching the
+/// mesh of a chunk after a single tile changes counts as a regeneration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RegenStats {
+    /// Chunk meshes regenerated the last time meshes were generated
+    pub last_frame: usize,
+    /// Chunk meshes regenerated since the tilemap was created
+    pub total: u64,
+}
+
+impl RegenStats {
+    /// Records that `count` chunk meshes were regenerated this frame
+    pub(crate) fn record_frame(&mut self, count: usize) {
+        self.last_frame = count;
+        self.total += count as u64;
+    }
+}
+
+/// Which tiles count as connected, for [`Tilemap::flood_fill()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Connectivity {
+    /// Tiles sharing an edge are connected
+    #[default]
+    Four,
+    /// Tiles sharing an edge or a corner are connected
+    Eight,
+}
+
+impl Connectivity {
+    /// Returns an iterator over the offsets from a tile to the tiles connected to it
+    pub fn offsets(self) -> impl Iterator<Item = IVec2> {
+        Direction::ALL
+            .into_iter()
+            .filter(move |dir| self == Connectivity::Eight || dir.is_cardinal())
+            .map(Direction::offset)
+    }
+}
+
+/// The error returned when trying to set a tile outside of the
+/// [bounds](Tilemap::bounds()) of a [`Tilemap`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds(pub TilemapPos);
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "tile position {} is outside of the tilemap bounds",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+/// Returns `true` if the tile at `pos` is on the edge of it's chunk, so tiles in other chunks
+/// have it as a neighbor
+pub(crate) fn on_chunk_edge(pos: ChunkPos) -> bool {
+    let last = CHUNK_SIZE as u8 - 1;
+    pos.x() == 0 || pos.y() == 0 || pos.x() == last || pos.y() == last
+}
+
+/// The positions on the line from `from` to `to`, including both ends
+///
+/// Uses Bresenham's line algorithm, so consecutive positions share an edge or a corner
+fn line_positions(from: IVec2, to: IVec2) -> impl Iterator<Item = IVec2> {
+    let delta = (to - from).abs() * IVec2::new(1, -1);
+    let step = (to - from).signum();
+    let mut pos = from;
+    let mut error = delta.x + delta.y;
+    let mut done = false;
+    iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let current = pos;
+        done = pos == to;
+        let error2 = error * 2;
+        if error2 >= delta.y {
+            error += delta.y;
+            pos.x += step.x;
+        }
+        if error2 <= delta.x {
+            error += delta.x;
+            pos.y += step.y;
+        }
+        Some(current)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{brush::Brush, history::EditHistory, test_util::*};
+
+    fn dirty_chunks(tilemap: &Tilemap<TestTile>) -> Vec<IVec2> {
+        let mut dirty: Vec<_> = tilemap.dirty_chunks().collect();
+        dirty.sort_by_key(|pos| (pos.x, pos.y));
+        dirty
+    }
+
+    /// A tilemap with a tile in each of the 9 chunks around the origin, none flagged to
+    /// regenerate it's mesh
+    fn clean_chunk_grid() -> Tilemap<TestTile> {
+        let mut tilemap = test_tilemap();
+        for chunk in IRect::new(IVec2::NEG_ONE, IVec2::ONE).iter_positions() {
+            tilemap.set(
+                TilemapPos {
+                    chunk,
+                    tile: ChunkPos::new(16, 16),
+                },
+                TestTile(0),
+            );
+        }
+        tilemap.cancel_regeneration();
+        tilemap
+    }
+
+    #[test]
+    fn metadata_does_not_flag_chunks() {
+        let mut tilemap = Tilemap::<TestTile, &str>::with_metadata(Handle::default());
+        tilemap.set(pos(3, 4), TestTile(0));
+        tilemap.cancel_regeneration();
+
+        assert_eq!(tilemap.set_meta(pos(3, 4), "door"), None);
+        assert_eq!(tilemap.set_meta(pos(-7, 2), "trap"), None);
+        assert_eq!(tilemap.get_meta(pos(3, 4)), Some(&"door"));
+        assert_eq!(tilemap.dirty_chunks().count(), 0);
+
+        assert_eq!(
+            tilemap.remove_with_meta(pos(3, 4)),
+            (Some(TestTile(0)), Some("door"))
+        );
+        assert_eq!(tilemap.get_meta(pos(-7, 2)), Some(&"trap"));
+    }
+
+    #[test]
+    fn grid_tilemaps_can_hold_metadata() {
+        let mut tilemap = Tilemap::<TestTile, &str>::from_grid(
+            Handle::default(),
+            IVec2::new(-1, 0),
+            &[&[Some(TestTile(1)), None]],
+        )
+        .unwrap();
+        tilemap.set_meta(pos(-1, 0), "door");
+        assert_eq!(
+            tilemap.remove_with_meta(pos(-1, 0)),
+            (Some(TestTile(1)), Some("door"))
+        );
+        assert_eq!(
+            Tilemap::<TestTile, &str>::resolve(IVec2::new(-1, 0)),
+            (IVec2::new(-1, 0), ChunkPos::new(31, 0))
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_writes_are_ignored() {
+        let mut tilemap = test_tilemap();
+        tilemap.set_bounds(Some(IRect::new(IVec2::ZERO, IVec2::splat(9))));
+        assert_eq!(
+            tilemap.try_set(pos(10, 0), TestTile(0)),
+            Err(OutOfBounds(pos(10, 0)))
+        );
+        assert_eq!(tilemap.set(pos(-1, 3), TestTile(0)), None);
+
+        tilemap.fill_circle(IVec2::ZERO, 40, TestTile(1));
+        tilemap.paint_path(&[IVec2::new(-50, 5), IVec2::new(50, 5)], 3, TestTile(2));
+        tilemap.fill_polygon(
+            &[IVec2::new(-20, -20), IVec2::new(20, -20), IVec2::new(0, 20)],
+            TestTile(3),
+        );
+        tilemap.generate_region(IRect::new(IVec2::splat(-40), IVec2::splat(40)), |_| {
+            Some(TestTile(4))
+        });
+        assert_eq!(tilemap.get_mut_or_default(pos(-1, -1)), None);
+        assert!(tilemap
+            .iter_positions()
+            .all(|(pos, _)| IRect::new(IVec2::ZERO, IVec2::splat(9)).contains(pos.into())));
+        assert_eq!(tilemap.iter().count(), 100);
+        assert_eq!(tilemap.iter_chunks().count(), 1);
+
+        // Tiles left outside of the bounds when they shrink aren't cleared
+        tilemap.set_bounds(Some(IRect::new(IVec2::ZERO, IVec2::splat(4))));
+        tilemap.clear_region(IVec2::splat(-10), IVec2::splat(10));
+        assert_eq!(tilemap.get(pos(0, 0)), None);
+        tilemap.set_bounds(None);
+        assert_eq!(tilemap.get(pos(5, 5)), Some(&TestTile(4)));
+        assert_eq!(tilemap.iter().count(), 75);
+    }
+
+    #[test]
+    fn get_mut_or_default_only_flags_inserts() {
+        let mut tilemap = clean_chunk_grid();
+        assert_eq!(
+            tilemap.get_mut_or_default(pos(3, 3)),
+            Some(&mut TestTile(0))
+        );
+        assert_eq!(tilemap.get(pos(3, 3)), Some(&TestTile(0)));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO]);
+
+        tilemap.cancel_regeneration();
+        tilemap.get_mut_or_default(pos(3, 3)).unwrap().0 = 5;
+        assert_eq!(tilemap.get(pos(3, 3)), Some(&TestTile(5)));
+        assert_eq!(dirty_chunks(&tilemap), []);
+        assert_eq!(tilemap.iter().count(), 10);
+    }
+
+    #[test]
+    fn clear_region_spans_chunks() {
+        let mut tilemap = test_tilemap();
+        tilemap.generate_region(IRect::new(IVec2::new(28, 0), IVec2::new(35, 3)), |_| {
+            Some(TestTile(1))
+        });
+        tilemap.cancel_regeneration();
+        tilemap.clear_region(IVec2::new(34, 2), IVec2::new(30, 1));
+
+        let mut left: Vec<_> = tilemap
+            .iter_positions()
+            .map(|(pos, _)| IVec2::from(pos))
+            .collect();
+        left.sort_by_key(|pos| (pos.y, pos.x));
+        let expected: Vec<_> = IRect::new(IVec2::new(28, 0), IVec2::new(35, 3))
+            .iter_positions()
+            .filter(|pos| !IRect::new(IVec2::new(30, 1), IVec2::new(34, 2)).contains(*pos))
+            .collect();
+        assert_eq!(left, expected);
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO, IVec2::X]);
+    }
+
+    #[test]
+    fn equality_compares_tiles_only() {
+        let mut a = test_tilemap::<TestTile>();
+        a.set(pos(1, 2), TestTile(1));
+        a.set(pos(-40, 7), TestTile(2));
+        let mut b = test_tilemap::<TestTile>();
+        b.set(pos(-40, 7), TestTile(2));
+        b.set(pos(1, 2), TestTile(1));
+        b.set(pos(100, 100), TestTile(3));
+        b.remove(pos(100, 100));
+        b.cancel_regeneration();
+        assert_eq!(a, b);
+
+        b.set(pos(1, 2), TestTile(4));
+        assert_ne!(a, b);
+        b.set(pos(1, 2), TestTile(1));
+        b.set(pos(1, 3), TestTile(1));
+        assert_ne!(a, b);
+        assert_ne!(b, a);
+    }
+
+    #[test]
+    fn restore_undoes_edits_since_snapshot() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(1, 2), TestTile(1));
+        tilemap.set(pos(40, 2), TestTile(2));
+        let snapshot = tilemap.snapshot();
+
+        tilemap.set(pos(1, 2), TestTile(3));
+        tilemap.remove(pos(40, 2));
+        tilemap.set(pos(-70, 0), TestTile(4));
+        tilemap.cancel_regeneration();
+        tilemap.restore(&snapshot);
+
+        let mut expected = test_tilemap::<TestTile>();
+        expected.set(pos(1, 2), TestTile(1));
+        expected.set(pos(40, 2), TestTile(2));
+        assert_eq!(tilemap, expected);
+        assert_eq!(
+            tilemap.count_chunks_dirty(),
+            tilemap.chunk_positions().count()
+        );
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn scatter_is_reproducible_with_a_seed() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let bounds = IRect::new(IVec2::splat(-20), IVec2::splat(20));
+        let scatter = |seed| {
+            let mut tilemap = test_tilemap::<TestTile>();
+            tilemap.set(pos(0, 0), TestTile(9));
+            tilemap.scatter(
+                bounds,
+                0.5,
+                &[(TestTile(1), 1.0), (TestTile(2), 3.0)],
+                &mut StdRng::seed_from_u64(seed),
+            );
+            tilemap
+        };
+        let tilemap = scatter(7);
+        assert_eq!(tilemap, scatter(7));
+        assert_ne!(tilemap, scatter(8));
+        assert_eq!(tilemap.get(pos(0, 0)), Some(&TestTile(9)));
+        assert!(tilemap.iter().count() > 1);
+        assert!(tilemap
+            .iter_positions()
+            .all(|(pos, _)| bounds.contains(pos.into())));
+    }
+
+    #[test]
+    fn chunks_in_view_cover_camera() {
+        let tilemap = test_tilemap::<TestTile>();
+        let camera = GlobalTransform::from_xyz(100.0, 0.0, 0.0);
+        let projection = OrthographicProjection {
+            left: -20.0,
+            right: 20.0,
+            bottom: -10.0,
+            top: 10.0,
+            ..default()
+        };
+        let mut chunks: Vec<_> = tilemap.chunks_in_view(&camera, &projection).collect();
+        chunks.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(
+            chunks,
+            [
+                IVec2::new(2, -1),
+                IVec2::new(2, 0),
+                IVec2::new(3, -1),
+                IVec2::new(3, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_finds_inconsistent_chunks() {
+        let mut tilemap = clean_chunk_grid();
+        assert_eq!(tilemap.validate(), Ok(()));
+
+        tilemap.set(pos(3, 3), TestTile(1));
+        assert_eq!(tilemap.validate(), Ok(()));
+        tilemap.get_chunk_mut(IVec2::ZERO).unwrap().regenerate_mesh = false;
+        assert!(tilemap.validate().is_err());
+
+        let mut tilemap = clean_chunk_grid();
+        tilemap.get_chunk_mut(IVec2::ZERO).unwrap().lod_level = 1;
+        tilemap.get_chunk_mut(IVec2::ZERO).unwrap().lod_meshes = vec![Handle::default()];
+        assert!(tilemap.validate().is_err());
+
+        let mut tilemap = clean_chunk_grid();
+        *tilemap
+            .get_chunk_mut(IVec2::ZERO)
+            .unwrap()
+            .tile_count
+            .get_mut() = 2;
+        assert!(tilemap.validate().is_err() || !cfg!(debug_assertions));
+        tilemap.get_chunk_mut(IVec2::ZERO).unwrap()[ChunkPos::ZERO] = None;
+        assert_eq!(tilemap.validate(), Ok(()));
+    }
+
+    #[test]
+    fn find_returns_matching_tile() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(3, 4), TestTile(1));
+        tilemap.set(pos(-50, 9), TestTile(2));
+        assert_eq!(
+            tilemap.find(|tile| tile.0 == 2),
+            Some((pos(-50, 9), &TestTile(2)))
+        );
+        assert_eq!(tilemap.find(|tile| tile.0 == 3), None);
+        assert_eq!(test_tilemap::<TestTile>().find(|_| true), None);
+    }
+
+    #[test]
+    fn nearest_stops_without_a_match() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(3, 4), TestTile(1));
+        tilemap.set(pos(-2, 1), TestTile(2));
+        let nearest = |radius| {
+            tilemap
+                .nearest(Vec2::new(0.5, 0.5), |tile| tile.0 == 1, radius)
+                .map(|(pos, _)| pos)
+        };
+        assert_eq!(nearest(10.0), Some(pos(3, 4)));
+        assert_eq!(nearest(2.0), None);
+        assert_eq!(nearest(f32::INFINITY), None);
+        assert_eq!(nearest(f32::NAN), None);
+        assert_eq!(
+            tilemap.nearest(Vec2::ZERO, |tile| tile.0 == 3, f32::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn set_on_edge_flags_neighbor_chunks() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(31, 5), TestTile(1));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO, IVec2::X]);
+
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(0, 0), TestTile(1));
+        assert_eq!(
+            dirty_chunks(&tilemap),
+            [
+                IVec2::new(-1, -1),
+                IVec2::new(-1, 0),
+                IVec2::new(0, -1),
+                IVec2::ZERO
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn bulk_writes_on_edge_flag_neighbor_chunks() {
+        let edge = IVec2::new(31, 5);
+        let edge_rect = IRect::new(edge, edge);
+        let with_edge_tile = |tile: TestTile| {
+            let mut tilemap = clean_chunk_grid();
+            tilemap.set(edge.into(), tile);
+            tilemap.cancel_regeneration();
+            tilemap
+        };
+        let mut brush = Brush::new(1, 1);
+        brush.set(UVec2::ZERO, Some(TestTile(1)));
+        let mut source = clean_chunk_grid();
+        source.set(edge.into(), TestTile(1));
+        let bytes = source.region_to_network_bytes(edge_rect, |tile| tile.0 as u16);
+
+        let writers: [(&str, Box<dyn Fn() -> Tilemap<TestTile>>); 12] = [
+            (
+                "clear_region",
+                Box::new(|| {
+                    let mut tilemap = with_edge_tile(TestTile(1));
+                    tilemap.clear_region(edge, edge);
+                    tilemap
+                }),
+            ),
+            (
+                "fill_circle",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.fill_circle(edge, 0, TestTile(1));
+                    tilemap
+                }),
+            ),
+            (
+                "paint_path",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.paint_path(&[edge], 1, TestTile(1));
+                    tilemap
+                }),
+            ),
+            (
+                "fill_polygon",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.fill_polygon(&[edge, edge + IVec2::X, edge + IVec2::ONE], TestTile(1));
+                    tilemap
+                }),
+            ),
+            (
+                "generate_region",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.generate_region(edge_rect, |_| Some(TestTile(1)));
+                    tilemap
+                }),
+            ),
+            (
+                "apply_noise",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.apply_noise(edge_rect, |_, _| Some(TestTile(1)), |_| 0.0);
+                    tilemap
+                }),
+            ),
+            (
+                "flood_fill",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.flood_fill(edge.into(), TestTile(1), edge_rect, Connectivity::Four);
+                    tilemap
+                }),
+            ),
+            (
+                "replace_all",
+                Box::new(|| {
+                    let mut tilemap = with_edge_tile(TestTile(2));
+                    tilemap.replace_all(&TestTile(2), TestTile(1));
+                    tilemap
+                }),
+            ),
+            (
+                "stamp",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap.stamp(edge.into(), &brush, true);
+                    tilemap
+                }),
+            ),
+            (
+                "apply_edit",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    let mut history = EditHistory::default();
+                    tilemap.apply_edit(&mut history, [(edge.into(), Some(TestTile(1)))]);
+                    tilemap
+                }),
+            ),
+            (
+                "undo",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    let mut history = EditHistory::default();
+                    tilemap.apply_edit(&mut history, [(edge.into(), Some(TestTile(1)))]);
+                    tilemap.cancel_regeneration();
+                    history.undo(&mut tilemap);
+                    tilemap
+                }),
+            ),
+            (
+                "apply_network_bytes",
+                Box::new(|| {
+                    let mut tilemap = clean_chunk_grid();
+                    tilemap
+                        .apply_network_bytes(&bytes, |id| Some(TestTile(id as u8)))
+                        .unwrap();
+                    tilemap
+                }),
+            ),
+        ];
+        for (name, write) in writers {
+            assert_eq!(dirty_chunks(&write()), [IVec2::ZERO, IVec2::X], "{name}");
+        }
+
+        let mut tilemap = with_edge_tile(TestTile(1));
+        tilemap
+            .relocate_chunk(IVec2::ZERO, IVec2::new(3, 0), false)
+            .unwrap();
+        assert_eq!(
+            dirty_chunks(&tilemap),
+            [IVec2::ZERO, IVec2::X, IVec2::new(3, 0)]
+        );
+
+        let mut tilemap = with_edge_tile(TestTile(1));
+        tilemap.drain().for_each(drop);
+        assert_eq!(tilemap.count_chunks_dirty(), 9);
+        let mut tilemap = with_edge_tile(TestTile(1));
+        tilemap.shift(IVec2::X);
+        assert_eq!(
+            tilemap.count_chunks_dirty(),
+            tilemap.chunk_positions().count()
+        );
+    }
+
+    #[test]
+    fn set_inside_chunk_flags_only_it() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(5, 5), TestTile(1));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO]);
+    }
+
+    #[test]
+    fn remove_on_edge_flags_neighbor_chunks() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(5, 31), TestTile(1));
+        tilemap.cancel_regeneration();
+        tilemap.remove(pos(5, 31));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO, IVec2::Y]);
+
+        tilemap.cancel_regeneration();
+        tilemap.remove(pos(5, 31));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO]);
+    }
+
+    /// The hook events waiting in `tilemap`, as whether each is a set and it's tile
+    fn pending_hooks<M>(tilemap: &Tilemap<TestTile, M>) -> Vec<(bool, TilemapPos, TestTile)> {
+        tilemap
+            .pending_hooks
+            .iter()
+            .map(|event| match event {
+                HookEvent::Set(pos, tile) => (true, *pos, *tile),
+                HookEvent::Removed(pos, tile) => (false, *pos, *tile),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replacing_a_tile_removes_before_setting() {
+        let mut tilemap = test_tilemap();
+        tilemap.set_hooks_enabled(true);
+        tilemap.set(pos(1, 2), TestTile(1));
+        tilemap.set(pos(1, 2), TestTile(2));
+        assert_eq!(
+            pending_hooks(&tilemap),
+            [
+                (true, pos(1, 2), TestTile(1)),
+                (false, pos(1, 2), TestTile(1)),
+                (true, pos(1, 2), TestTile(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn move_overwrite_runs_hooks_and_moves_metadata() {
+        let mut tilemap = Tilemap::<TestTile, u8>::with_metadata(Handle::default());
+        tilemap.set(pos(0, 0), TestTile(1));
+        tilemap.set(pos(40, 0), TestTile(2));
+        tilemap.set_meta(pos(0, 0), 7);
+        tilemap.set_meta(pos(40, 0), 9);
+        tilemap.set_hooks_enabled(true);
+
+        let old = tilemap.move_tile_overwrite(pos(0, 0), pos(40, 0));
+        assert_eq!(old, Ok(Some(TestTile(2))));
+        assert_eq!(tilemap.get(pos(40, 0)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get_meta(pos(0, 0)), None);
+        assert_eq!(tilemap.get_meta(pos(40, 0)), Some(&7));
+        assert_eq!(
+            pending_hooks(&tilemap),
+            [
+                (false, pos(0, 0), TestTile(1)),
+                (false, pos(40, 0), TestTile(2)),
+                (true, pos(40, 0), TestTile(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_positions_span_missing_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(31, 0), TestTile(1));
+        tilemap.set(pos(30, 1), TestTile(1));
+        let bounds = IRect::new(IVec2::new(30, 0), IVec2::new(33, 1));
+        let mut empty: Vec<_> = tilemap.iter_empty_in(bounds).map(IVec2::from).collect();
+        empty.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(
+            empty,
+            [
+                IVec2::new(30, 0),
+                IVec2::new(31, 1),
+                IVec2::new(32, 0),
+                IVec2::new(32, 1),
+                IVec2::new(33, 0),
+                IVec2::new(33, 1),
+            ]
+        );
+        assert_eq!(tilemap.chunk_positions().count(), 1);
+    }
+
+    #[test]
+    fn region_border_uses_cardinal_directions() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(0, 0), TestTile(1));
+        tilemap.set(pos(1, 0), TestTile(1));
+        let mut border =
+            tilemap.region_border(|tile| tile.is_some(), IRect::new(IVec2::ZERO, IVec2::ONE));
+        border.sort_by_key(|(pos, direction)| (pos.tile.as_index(), *direction as usize));
+        assert_eq!(
+            border,
+            [
+                (pos(0, 0), Direction::North),
+                (pos(0, 0), Direction::South),
+                (pos(0, 0), Direction::West),
+                (pos(1, 0), Direction::North),
+                (pos(1, 0), Direction::East),
+                (pos(1, 0), Direction::South),
+            ]
+        );
+    }
+
+    #[test]
+    fn frontier_skips_surrounded_chunks() {
+        let tilemap = clean_chunk_grid();
+        let frontier: Vec<_> = tilemap.frontier_chunks().collect();
+        assert_eq!(frontier.len(), 8);
+        assert!(!frontier.contains(&IVec2::ZERO));
+    }
+
+    #[test]
+    fn display_shows_tile_coordinates() {
+        assert_eq!(pos(3, 40).to_string(), "(3, 40)");
+        assert_eq!(pos(-1, -33).to_string(), "(-1, -33)");
+        assert_eq!(TilemapPos::ZERO.to_string(), "(0, 0)");
+    }
+
+    #[test]
+    fn pack_round_trips() {
+        for pos in [
+            pos(0, 0),
+            pos(5, -7),
+            pos(-33, 64),
+            pos(i32::MIN, i32::MAX),
+            pos(i32::MAX, -1),
+        ] {
+            assert_eq!(TilemapPos::unpack(pos.pack()), pos);
+        }
+        assert_ne!(pos(-1, 0).pack(), pos(0, -1).pack());
+    }
+
+    #[test]
+    fn pack_wraps_far_positions() {
+        let far = TilemapPos {
+            chunk: IVec2::new(i32::MAX, i32::MIN),
+            tile: ChunkPos::new(3, 4),
+        };
+        let wrapped = IVec2::new(
+            i32::MAX.wrapping_mul(CHUNK_SIZE as i32) + 3,
+            i32::MIN.wrapping_mul(CHUNK_SIZE as i32) + 4,
+        );
+        assert_eq!(TilemapPos::unpack(far.pack()), TilemapPos::from(wrapped));
+    }
+
+    #[test]
+    fn fill_circle_radii() {
+        let filled = |tilemap: &Tilemap<TestTile>| {
+            let mut positions: Vec<_> = tilemap
+                .iter_positions()
+                .map(|(pos, _)| IVec2::from(pos))
+                .collect();
+            positions.sort_by_key(|pos| (pos.x, pos.y));
+            positions
+        };
+
+        let mut tilemap = test_tilemap();
+        tilemap.fill_circle(IVec2::new(5, 5), 0, TestTile(1));
+        assert_eq!(filled(&tilemap), [IVec2::new(5, 5)]);
+
+        let mut tilemap = test_tilemap();
+        tilemap.fill_circle(IVec2::new(5, 5), 1, TestTile(1));
+        assert_eq!(
+            filled(&tilemap),
+            [
+                IVec2::new(4, 5),
+                IVec2::new(5, 4),
+                IVec2::new(5, 5),
+                IVec2::new(5, 6),
+                IVec2::new(6, 5),
+            ]
+        );
+
+        let mut tilemap = test_tilemap();
+        tilemap.fill_circle(IVec2::ZERO, 3, TestTile(1));
+        assert_eq!(filled(&tilemap).len(), 29);
+        assert_eq!(tilemap.chunk_positions().count(), 4);
+        assert_eq!(tilemap.get(pos(-3, 0)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(pos(2, -2)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(pos(-2, -2)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(pos(-3, -1)), None);
+        assert_eq!(
+            dirty_chunks(&tilemap),
+            [
+                IVec2::new(-1, -1),
+                IVec2::new(-1, 0),
+                IVec2::new(0, -1),
+                IVec2::ZERO
+            ]
+        );
+    }
+
+    #[test]
+    fn par_iteration_visits_every_chunk() {
+        ComputeTaskPool::init(bevy::tasks::TaskPool::default);
+        let mut tilemap = clean_chunk_grid();
+        tilemap.iter_chunks_mut_par(|_, chunk| {
+            for tile in chunk.iter_tiles_mut() {
+                tile.0 += 1;
+            }
+        });
+        for chunk in IRect::new(IVec2::NEG_ONE, IVec2::ONE).iter_positions() {
+            let pos = TilemapPos {
+                chunk,
+                tile: ChunkPos::new(16, 16),
+            };
+            assert_eq!(tilemap.get(pos), Some(&TestTile(1)));
+        }
+    }
+
+    #[test]
+    fn get_with_neighbor_mut_crosses_chunks() {
+        let mut tilemap = test_tilemap();
+        tilemap.set(pos(5, 5), TestTile(1));
+        tilemap.set(pos(5, 6), TestTile(2));
+        tilemap.set(pos(31, 0), TestTile(3));
+        tilemap.set(pos(32, 0), TestTile(4));
+
+        let (tile, neighbor) = tilemap
+            .get_with_neighbor_mut(pos(5, 5), Direction::North)
+            .unwrap();
+        assert_eq!(neighbor, Some(&TestTile(2)));
+        tile.0 = 10;
+        assert_eq!(tilemap.get(pos(5, 5)), Some(&TestTile(10)));
+
+        let (tile, neighbor) = tilemap
+            .get_with_neighbor_mut(pos(31, 0), Direction::East)
+            .unwrap();
+        assert_eq!(neighbor, Some(&TestTile(4)));
+        tile.0 = 30;
+        assert_eq!(tilemap.get(pos(31, 0)), Some(&TestTile(30)));
+
+        let (_, neighbor) = tilemap
+            .get_with_neighbor_mut(pos(32, 0), Direction::West)
+            .unwrap();
+        assert_eq!(neighbor, Some(&TestTile(30)));
+        let (_, neighbor) = tilemap
+            .get_with_neighbor_mut(pos(31, 0), Direction::South)
+            .unwrap();
+        assert_eq!(neighbor, None);
+        assert!(tilemap
+            .get_with_neighbor_mut(pos(5, 6), Direction::North)
+            .is_some());
+        assert!(tilemap
+            .get_with_neighbor_mut(pos(6, 6), Direction::North)
+            .is_none());
+    }
+
+    #[test]
+    fn chunk_positions_match_inserted_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        for pos in [pos(0, 0), pos(5, 5), pos(-1, 40), pos(100, -33)] {
+            tilemap.set(pos, TestTile(0));
+        }
+        let positions: HashSet<IVec2> = tilemap.chunk_positions().collect();
+        assert_eq!(
+            positions,
+            HashSet::from_iter([IVec2::ZERO, IVec2::new(-1, 1), IVec2::new(3, -2)])
+        );
+        assert_eq!(tilemap.chunk_positions().count(), 3);
+    }
+
+    #[test]
+    fn with_chunk_mut_creates_chunk() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        let previous = tilemap.with_chunk_mut(IVec2::new(2, -1), |chunk| {
+            chunk.set_raw(ChunkPos::new(1, 2), Some(TestTile(5)));
+            chunk.set_raw(ChunkPos::new(3, 4), Some(TestTile(6)));
+            chunk.regenerate_mesh();
+            chunk.set_raw(ChunkPos::new(1, 2), Some(TestTile(7)))
+        });
+        assert_eq!(previous, Some(TestTile(5)));
+        assert_eq!(tilemap.get(pos(65, -30)), Some(&TestTile(7)));
+        assert_eq!(tilemap.get(pos(67, -28)), Some(&TestTile(6)));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::new(2, -1)]);
+
+        let count = tilemap.with_chunk_mut(IVec2::new(2, -1), |chunk| chunk.tile_count());
+        assert_eq!(count, 2);
+        assert_eq!(tilemap.chunk_positions().count(), 1);
+    }
+
+    #[test]
+    fn query_aabb_straddles_tiles_and_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        for pos in IRect::new(IVec2::new(29, -3), IVec2::new(34, 2)).iter_positions() {
+            tilemap.set(pos.into(), TestTile(0));
+        }
+        let query = |min: Vec2, max: Vec2| {
+            let mut positions: Vec<_> = tilemap
+                .query_aabb(min, max)
+                .map(|(pos, _)| IVec2::from(pos))
+                .collect();
+            positions.sort_by_key(|pos| (pos.x, pos.y));
+            positions
+        };
+
+        assert_eq!(
+            query(Vec2::new(31.5, -0.5), Vec2::new(32.5, 1.0)),
+            [
+                IVec2::new(31, -1),
+                IVec2::new(31, 0),
+                IVec2::new(32, -1),
+                IVec2::new(32, 0),
+            ]
+        );
+        assert_eq!(
+            query(Vec2::new(30.0, 0.0), Vec2::new(32.0, 1.0)),
+            [IVec2::new(30, 0), IVec2::new(31, 0)]
+        );
+        assert_eq!(
+            query(Vec2::new(33.2, 1.2), Vec2::new(40.0, 40.0)),
+            [
+                IVec2::new(33, 1),
+                IVec2::new(33, 2),
+                IVec2::new(34, 1),
+                IVec2::new(34, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_across_missing_chunks() {
+        let mut old = test_tilemap::<TestTile>();
+        let mut new = test_tilemap::<TestTile>();
+        old.set(pos(0, 0), TestTile(1));
+        new.set(pos(0, 0), TestTile(1));
+        old.set(pos(40, 0), TestTile(2));
+        new.set(pos(-1, 0), TestTile(3));
+
+        let mut diff = old.diff(&new);
+        diff.sort_by_key(|(pos, ..)| IVec2::from(*pos).x);
+        assert_eq!(
+            diff,
+            [
+                (pos(-1, 0), None, Some(TestTile(3))),
+                (pos(40, 0), Some(TestTile(2)), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn pool_reuses_recycled_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set_pool_size(1);
+        assert_eq!(tilemap.pool_size(), 1);
+
+        let chunk = tilemap.get_or_create_chunk(IVec2::ZERO);
+        chunk.set(ChunkPos::new(3, 3), TestTile(1));
+        chunk.sublayer_entities.reserve(8);
+        let capacity = chunk.sublayer_entities.capacity();
+        tilemap.recycle_chunk(IVec2::ZERO);
+        tilemap.set(pos(3, 0), TestTile(2));
+        tilemap.recycle_chunk(IVec2::ZERO);
+        assert_eq!(tilemap.pool.len(), 1, "the pool should be full");
+
+        let chunk = tilemap.get_or_create_chunk(IVec2::new(4, 4));
+        assert_eq!(chunk.sublayer_entities.capacity(), capacity);
+        assert!(chunk.is_reset());
+        assert!(chunk.is_empty());
+        assert!(tilemap.pool.is_empty());
+        assert_eq!(tilemap.get(pos(3, 3)), None);
+
+        tilemap.recycle_chunk(IVec2::new(4, 4));
+        tilemap.set_pool_size(0);
+        assert!(tilemap.pool.is_empty());
+    }
+
+    #[test]
+    fn count_in_region_spans_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        for x in 28..36 {
+            tilemap.set(pos(x, 0), TestTile((x % 2) as u8));
+        }
+        tilemap.set(pos(30, 5), TestTile(0));
+
+        let bounds = IRect::new(IVec2::new(30, -1), IVec2::new(33, 1));
+        assert_eq!(tilemap.count_in_region(bounds, |_| true), 4);
+        assert_eq!(tilemap.count_in_region(bounds, |tile| tile.0 == 0), 2);
+        assert_eq!(tilemap.count_in_region(bounds, |tile| tile.0 == 2), 0);
+        let all = IRect::new(IVec2::new(-100, -100), IVec2::new(100, 100));
+        assert_eq!(tilemap.count_in_region(all, |tile| tile.0 == 0), 5);
+    }
+
+    #[test]
+    fn generate_region_checkerboard() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(0, 0), TestTile(9));
+        let bounds = IRect::new(IVec2::new(-2, -3), IVec2::new(2, 3));
+        tilemap.generate_region(bounds, |pos| {
+            ((pos.x + pos.y).rem_euclid(2) == 0).then_some(TestTile(1))
+        });
+
+        for pos in bounds.iter_positions() {
+            let expected = match (pos.x + pos.y).rem_euclid(2) {
+                0 => Some(&TestTile(1)),
+                _ => None,
+            };
+            assert_eq!(tilemap.get(pos.into()), expected, "at {pos}");
+        }
+        assert_eq!(tilemap.iter().count(), 17);
+        assert_eq!(tilemap.get(pos(3, 3)), None);
+        assert_eq!(
+            dirty_chunks(&tilemap),
+            [
+                IVec2::new(-1, -1),
+                IVec2::new(-1, 0),
+                IVec2::new(0, -1),
+                IVec2::ZERO
+            ]
+        );
+    }
+
+    #[test]
+    fn replace_all_flags_only_chunks_with_matches() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(5, 5), TestTile(1));
+        tilemap.set(pos(-20, 40), TestTile(1));
+        tilemap.set(pos(40, 40), TestTile(2));
+        tilemap.cancel_regeneration();
+
+        tilemap.replace_all(&TestTile(1), TestTile(3));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::new(-1, 1), IVec2::ZERO]);
+        assert_eq!(tilemap.get(pos(5, 5)), Some(&TestTile(3)));
+        assert_eq!(tilemap.get(pos(-20, 40)), Some(&TestTile(3)));
+        assert_eq!(tilemap.get(pos(40, 40)), Some(&TestTile(2)));
+
+        tilemap.cancel_regeneration();
+        tilemap.replace_all(&TestTile(1), TestTile(4));
+        assert_eq!(dirty_chunks(&tilemap), []);
+    }
+
+    #[test]
+    fn row_and_column_cross_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(30, 2), TestTile(1));
+        tilemap.set(pos(32, 2), TestTile(2));
+        tilemap.set(pos(-1, -33), TestTile(3));
+        tilemap.set(pos(-1, -31), TestTile(4));
+
+        let row: Vec<_> = tilemap
+            .iter_row(2, 30..34)
+            .map(|(pos, tile)| (IVec2::from(pos).x, tile.map(|tile| tile.0)))
+            .collect();
+        assert_eq!(row, [(30, Some(1)), (31, None), (32, Some(2)), (33, None)]);
+
+        let column: Vec<_> = tilemap
+            .iter_column(-1, -34..-30)
+            .map(|(pos, tile)| (IVec2::from(pos).y, tile.map(|tile| tile.0)))
+            .collect();
+        assert_eq!(
+            column,
+            [(-34, None), (-33, Some(3)), (-32, None), (-31, Some(4))]
+        );
+        assert_eq!(tilemap.iter_row(0, 5..5).count(), 0);
+    }
+
+    #[test]
+    fn shift_moves_tiles_and_metadata() {
+        let build = || {
+            let mut tilemap = Tilemap::<TestTile, &str>::with_metadata(Handle::default());
+            tilemap.set(pos(0, 0), TestTile(1));
+            tilemap.set(pos(31, -1), TestTile(2));
+            tilemap.set_meta(pos(0, 0), "start");
+            tilemap.cancel_regeneration();
+            tilemap
+        };
+
+        let mut aligned = build();
+        aligned.shift(IVec2::new(64, -32));
+        assert_eq!(aligned.get(pos(64, -32)), Some(&TestTile(1)));
+        assert_eq!(aligned.get(pos(95, -33)), Some(&TestTile(2)));
+        assert_eq!(aligned.get(pos(0, 0)), None);
+        assert_eq!(aligned.get_meta(pos(64, -32)), Some(&"start"));
+        let mut chunks: Vec<_> = aligned.chunk_positions().collect();
+        chunks.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(chunks, [IVec2::new(2, -2), IVec2::new(2, -1)]);
+        assert_eq!(aligned.dirty_chunks().count(), 2);
+
+        let mut unaligned = build();
+        unaligned.shift(IVec2::new(1, 3));
+        assert_eq!(unaligned.get(pos(1, 3)), Some(&TestTile(1)));
+        assert_eq!(unaligned.get(pos(32, 2)), Some(&TestTile(2)));
+        assert_eq!(unaligned.get(pos(0, 0)), None);
+        assert_eq!(unaligned.get_meta(pos(1, 3)), Some(&"start"));
+        assert_eq!(unaligned.iter().count(), 2);
+        // The emptied chunk is dropped when it's mesh is regenerated
+        let mut dirty: Vec<_> = unaligned.dirty_chunks().collect();
+        dirty.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(dirty, [IVec2::NEG_Y, IVec2::ZERO, IVec2::X]);
+        assert!(unaligned.get_chunk(IVec2::NEG_Y).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unchecked_access_matches_get() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        let positions = [pos(0, 0), pos(-1, -1), pos(31, 32), pos(-100, 7)];
+        for (id, pos) in (0..).zip(positions) {
+            tilemap.set(pos, TestTile(id));
+        }
+        for pos in positions {
+            // SAFETY: a tile was set at every position
+            let tile = unsafe { tilemap.get_unchecked(pos) };
+            assert_eq!(Some(tile), tilemap.get(pos));
+        }
+
+        // SAFETY: a tile was set at (31, 32)
+        unsafe { tilemap.get_unchecked_mut(pos(31, 32)) }.0 = 9;
+        assert_eq!(tilemap.get(pos(31, 32)), Some(&TestTile(9)));
+    }
+
+    #[test]
+    fn ray_tiles_axis_aligned_and_diagonal() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(31, 5), TestTile(1));
+        let ray = |origin: Vec2, dir: Vec2, max_dist| {
+            tilemap
+                .ray_tiles(origin, dir, max_dist)
+                .map(|(pos, tile)| (IVec2::from(pos), tile.map(|tile| tile.0)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ray(Vec2::new(32.5, 5.5), Vec2::NEG_X, 2.0),
+            [
+                (IVec2::new(32, 5), None),
+                (IVec2::new(31, 5), Some(1)),
+                (IVec2::new(30, 5), None),
+            ]
+        );
+        assert_eq!(
+            ray(Vec2::new(0.5, 0.5), Vec2::Y, 1.0),
+            [(IVec2::new(0, 0), None), (IVec2::new(0, 1), None)]
+        );
+        assert_eq!(
+            ray(Vec2::new(0.5, 0.25), Vec2::ONE, 2.0),
+            [
+                (IVec2::new(0, 0), None),
+                (IVec2::new(1, 0), None),
+                (IVec2::new(1, 1), None),
+            ]
+        );
+        assert_eq!(
+            ray(Vec2::new(0.5, 0.5), Vec2::ZERO, 10.0),
+            [(IVec2::new(0, 0), None)]
+        );
+    }
+
+    #[test]
+    fn get_resolved_at_negative_positions_and_boundaries() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(-1, -1), TestTile(1));
+        tilemap.set(pos(-32, 0), TestTile(2));
+        tilemap.set(pos(31, 31), TestTile(3));
+
+        let (chunk, tile_pos, tile) = tilemap.get_resolved(IVec2::new(-1, -1)).unwrap();
+        assert_eq!(
+            (tile_pos, tile),
+            (ChunkPos::new(31, 31), Some(&TestTile(1)))
+        );
+        assert_eq!(chunk.tile_count(), 1);
+
+        let (_, tile_pos, tile) = tilemap.get_resolved(IVec2::new(-32, 0)).unwrap();
+        assert_eq!((tile_pos, tile), (ChunkPos::new(0, 0), Some(&TestTile(2))));
+        let (_, tile_pos, tile) = tilemap.get_resolved(IVec2::new(-31, 0)).unwrap();
+        assert_eq!((tile_pos, tile), (ChunkPos::new(1, 0), None));
+        assert!(tilemap.get_resolved(IVec2::new(-33, 0)).is_none());
+
+        let (_, tile_pos, tile) = tilemap.get_resolved(IVec2::new(31, 31)).unwrap();
+        assert_eq!(
+            (tile_pos, tile),
+            (ChunkPos::new(31, 31), Some(&TestTile(3)))
+        );
+        assert!(tilemap.get_resolved(IVec2::new(32, 31)).is_none());
+
+        tilemap.set_bounds(Some(IRect::new(IVec2::ZERO, IVec2::splat(40))));
+        let (_, _, tile) = tilemap.get_resolved(IVec2::new(-1, -1)).unwrap();
+        assert_eq!(tile, None);
+    }
+
+    #[test]
+    fn build_all_meshes_builds_dirty_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(5, 5), TestTile(0));
+        tilemap.cancel_regeneration();
+        tilemap.set(pos(40, 5), TestTile(1));
+        tilemap.set(pos(-20, 5), TestTile(2));
+        tilemap.set(pos(5, 40), TestTile(3));
+        tilemap.remove(pos(5, 40));
+
+        let mut meshes = tilemap.build_all_meshes();
+        meshes.sort_by_key(|(pos, _)| pos.x);
+        let chunks: Vec<_> = meshes.iter().map(|(pos, _)| *pos).collect();
+        assert_eq!(chunks, [IVec2::new(-1, 0), IVec2::X]);
+        assert!(meshes.iter().all(|(_, mesh)| mesh.count_vertices() == 4));
+
+        assert_eq!(dirty_chunks(&tilemap), []);
+        assert!(
+            tilemap.get_chunk(IVec2::Y).is_none(),
+            "empty chunks are dropped"
+        );
+        assert!(tilemap.build_all_meshes().is_empty());
+    }
+
+    #[test]
+    fn iter_dirty_chunks_mut_yields_only_dirty_chunks() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(5, 5), TestTile(1));
+        tilemap.set(pos(-20, -20), TestTile(1));
+
+        let mut dirty: Vec<_> = tilemap
+            .iter_dirty_chunks_mut()
+            .map(|(chunk_pos, chunk)| {
+                chunk.set_visual_offset(Vec2::ONE);
+                chunk_pos
+            })
+            .collect();
+        dirty.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(dirty, [IVec2::NEG_ONE, IVec2::ZERO]);
+        assert_eq!(
+            tilemap
+                .iter_chunks()
+                .filter(|chunk| chunk.visual_offset() == Vec2::ONE)
+                .count(),
+            2
+        );
+        assert_eq!(dirty_chunks(&tilemap), dirty, "the flags should be kept");
+    }
+
+    #[test]
+    fn setting_a_visually_equal_tile_keeps_chunk_clean() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(5, 5), TestTile(1));
+        tilemap.set(pos(0, 0), TestTile(1));
+        tilemap.cancel_regeneration();
+
+        assert_eq!(tilemap.set(pos(5, 5), TestTile(1)), Some(TestTile(1)));
+        assert_eq!(tilemap.set(pos(0, 0), TestTile(1)), Some(TestTile(1)));
+        assert_eq!(dirty_chunks(&tilemap), []);
+
+        tilemap.set(pos(5, 5), TestTile(2));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO]);
+        assert_eq!(tilemap.get(pos(5, 5)), Some(&TestTile(2)));
+    }
+
+    #[test]
+    fn edit_all_flags_only_touched_chunks() {
+        let mut tilemap = clean_chunk_grid();
+        tilemap.set(pos(5, 5), TestTile(1));
+        tilemap.cancel_regeneration();
+
+        let mut visited = 0;
+        tilemap.edit_all(|pos, tile| {
+            visited += 1;
+            if pos == TilemapPos::from(IVec2::new(5, 5)) {
+                tile.0 = 2;
+                EditResult::VisualChange
+            } else {
+                EditResult::Unchanged
+            }
+        });
+        assert_eq!(visited, 10);
+        assert_eq!(tilemap.get(pos(5, 5)), Some(&TestTile(2)));
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO]);
+
+        tilemap.cancel_regeneration();
+        tilemap.edit_all(|_, tile| {
+            tile.0 += 1;
+            EditResult::Unchanged
+        });
+        assert_eq!(dirty_chunks(&tilemap), []);
+        assert_eq!(tilemap.get(pos(5, 5)), Some(&TestTile(3)));
+    }
+
+    #[test]
+    fn occlusion_grid_reaches_the_last_column() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        // Set through the chunk, since the neighbors of the last column can't be addressed
+        let last = pos(i32::MAX, 0);
+        tilemap.with_chunk_mut(last.chunk, |chunk| {
+            chunk.set(last.tile, TestTile(1));
+            chunk.set(ChunkPos::new(29, 1), TestTile(1));
+        });
+        let bounds = IRect::new(IVec2::new(i32::MAX - 2, 0), IVec2::new(i32::MAX, 1));
+        let grid = tilemap.occlusion_grid(bounds, |tile| tile.map_or(0.0, |_| 1.0));
+        assert_eq!(grid, [0.0, 0.0, 1.0, 1.0, 0.0, 0.0]);
+
+        let grid = tilemap.occlusion_grid(IRect::new(IVec2::ONE, IVec2::ZERO), |_| 1.0);
+        assert!(grid.is_empty());
+    }
+
+    #[test]
+    fn subtract_removes_stencil_positions() {
+        let mut tilemap = clean_chunk_grid();
+        for x in 3..8 {
+            tilemap.set(pos(x, 3), TestTile(1));
+        }
+        tilemap.cancel_regeneration();
+        let mut stencil = test_tilemap::<UnpatchedTile>();
+        stencil.set(pos(4, 3), UnpatchedTile(0));
+        stencil.set(pos(6, 3), UnpatchedTile(0));
+        stencil.set(pos(6, 4), UnpatchedTile(0));
+        stencil.set(pos(100, 100), UnpatchedTile(0));
+
+        tilemap.subtract(&stencil);
+        let row: Vec<_> = tilemap
+            .iter_row(3, 3..8)
+            .map(|(_, tile)| tile.is_some())
+            .collect();
+        assert_eq!(row, [true, false, true, false, true]);
+        assert_eq!(tilemap.iter().count(), 12);
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::ZERO]);
+        assert!(tilemap.get_chunk(IVec2::new(3, 3)).is_none());
+    }
+
+    #[test]
+    fn from_grid_places_rows_upwards() {
+        let t = |id| Some(TestTile(id));
+        let grid: [&[Option<TestTile>]; 2] = [&[t(1), None, t(3)], &[t(4), t(5), None]];
+        let tilemap =
+            Tilemap::<TestTile>::from_grid(Handle::default(), IVec2::new(30, -1), &grid).unwrap();
+        let rows: Vec<Vec<_>> = (-1..1)
+            .map(|y| {
+                tilemap
+                    .iter_row(y, 30..33)
+                    .map(|(_, tile)| tile.map(|tile| tile.0))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows, [[Some(1), None, Some(3)], [Some(4), Some(5), None]]);
+        assert_eq!(tilemap.iter().count(), 4);
+        assert_eq!(tilemap.dirty_chunks().count(), 3);
+
+        let ragged: [&[Option<TestTile>]; 3] = [&[t(1)], &[t(2)], &[t(3), None]];
+        assert_eq!(
+            Tilemap::<TestTile>::from_grid(Handle::default(), IVec2::ZERO, &ragged).err(),
+            Some(RaggedGrid(2))
+        );
+    }
+
+    #[test]
+    fn eight_connected_fill_crosses_corners() {
+        let diagonal = || {
+            let mut tilemap = test_tilemap::<TestTile>();
+            for i in -1..3 {
+                tilemap.set(pos(i, i), TestTile(2));
+            }
+            tilemap
+        };
+        let bounds = IRect::new(IVec2::splat(-5), IVec2::splat(5));
+        let filled = |tilemap: &Tilemap<TestTile>| {
+            tilemap
+                .iter_positions()
+                .filter(|(_, tile)| **tile == TestTile(5))
+                .count()
+        };
+
+        let mut four = diagonal();
+        four.flood_fill(pos(0, 0), TestTile(5), bounds, Connectivity::Four);
+        assert_eq!(filled(&four), 1);
+        assert_eq!(four.get(pos(1, 1)), Some(&TestTile(2)));
+
+        let mut eight = diagonal();
+        eight.flood_fill(pos(0, 0), TestTile(5), bounds, Connectivity::Eight);
+        assert_eq!(filled(&eight), 4);
+        assert_eq!(eight.get(pos(-1, -1)), Some(&TestTile(5)));
+        assert_eq!(eight.iter().count(), 4);
+    }
+
+    #[test]
+    fn copied_chunk_positions_match_references() {
+        let tilemap = clean_chunk_grid();
+        let mut copied: Vec<_> = tilemap
+            .iter_chunk_positions_copied()
+            .map(|(pos, chunk)| (pos, chunk.tile_count()))
+            .collect();
+        let mut referenced: Vec<_> = tilemap
+            .iter_chunk_positions()
+            .map(|(pos, chunk)| (*pos, chunk.tile_count()))
+            .collect();
+        copied.sort_by_key(|(pos, _)| (pos.x, pos.y));
+        referenced.sort_by_key(|(pos, _)| (pos.x, pos.y));
+        assert_eq!(copied, referenced);
+        assert_eq!(copied.len(), 9);
+        assert!(copied.iter().all(|(_, count)| *count == 1));
+    }
+
+    #[test]
+    fn reserve_region_creates_overlapping_chunks() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(5, 5), TestTile(1));
+        tilemap.cancel_regeneration();
+
+        tilemap.reserve_region(IRect::new(IVec2::new(-1, 0), IVec2::new(64, 31)));
+        let mut chunks: Vec<_> = tilemap.chunk_positions().collect();
+        chunks.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(
+            chunks,
+            [IVec2::new(-1, 0), IVec2::ZERO, IVec2::X, IVec2::new(2, 0)]
+        );
+        assert_eq!(tilemap.iter().count(), 1);
+        assert_eq!(dirty_chunks(&tilemap), []);
+
+        tilemap.reserve_region(IRect::new(IVec2::ONE, IVec2::ZERO));
+        assert_eq!(tilemap.chunk_positions().count(), 4);
+    }
+
+    #[test]
+    fn apply_noise_thresholds_samples() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        let bounds = IRect::new(IVec2::new(-4, 0), IVec2::new(4, 1));
+        tilemap.apply_noise(
+            bounds,
+            |_, value| (value >= 0.5).then_some(TestTile(1)),
+            |pos| pos.x / 4.0 + pos.y / 8.0,
+        );
+
+        for pos in bounds.iter_positions() {
+            let expected = (pos.x * 2 + pos.y >= 4).then_some(&TestTile(1));
+            assert_eq!(tilemap.get(pos.into()), expected, "at {pos}");
+        }
+        assert_eq!(tilemap.iter().count(), 6);
+    }
+
+    #[cfg(feature = "deterministic")]
+    #[test]
+    fn deterministic_iteration_is_sorted() {
+        let chunks = [(3, -1), (-2, 0), (0, 0), (1, -1), (-1, 2), (0, -3)].map(IVec2::from);
+        let build = |order: Vec<IVec2>| {
+            let mut tilemap = test_tilemap::<TestTile>();
+            for (i, chunk) in order.into_iter().enumerate() {
+                let pos = TilemapPos {
+                    chunk,
+                    tile: ChunkPos::new(i as u8, 5),
+                };
+                tilemap.set(pos, TestTile(i as u8));
+            }
+            tilemap
+        };
+        let forward = build(chunks.to_vec());
+        let backward = build(chunks.iter().rev().copied().collect());
+
+        let mut sorted = chunks.to_vec();
+        sorted.sort_by_key(|chunk| (chunk.y, chunk.x));
+        let positions = |tilemap: &Tilemap<TestTile>| {
+            tilemap
+                .iter_chunk_positions()
+                .map(|(pos, _)| *pos)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(positions(&forward), sorted);
+        assert_eq!(positions(&backward), sorted);
+        assert_eq!(
+            forward
+                .iter_positions()
+                .map(|(pos, _)| pos.chunk)
+                .collect::<Vec<_>>(),
+            sorted
+        );
+    }
+
+    #[test]
+    fn relocate_chunk_moves_tiles() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(3, 4), TestTile(1));
+        tilemap.set(pos(10, 20), TestTile(2));
+        tilemap.set(pos(-30, 5), TestTile(3));
+
+        let to = IVec2::new(2, -1);
+        tilemap.relocate_chunk(IVec2::ZERO, to, false).unwrap();
+        let moved = |x, y| TilemapPos {
+            chunk: to,
+            tile: ChunkPos::new(x, y),
+        };
+        assert_eq!(tilemap.get(moved(3, 4)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(moved(10, 20)), Some(&TestTile(2)));
+        assert_eq!(tilemap.get(pos(3, 4)), None);
+        assert_eq!(tilemap.get(pos(10, 20)), None);
+        assert!(tilemap.get_chunk(IVec2::ZERO).unwrap().is_empty());
+
+        assert_eq!(
+            tilemap.relocate_chunk(IVec2::ZERO, to, false),
+            Err(ChunkMoveError::Empty(IVec2::ZERO))
+        );
+        assert_eq!(
+            tilemap.relocate_chunk(to, IVec2::NEG_X, false),
+            Err(ChunkMoveError::Occupied(IVec2::NEG_X))
+        );
+        assert_eq!(tilemap.get(moved(3, 4)), Some(&TestTile(1)));
+
+        tilemap.relocate_chunk(to, IVec2::NEG_X, true).unwrap();
+        assert_eq!(tilemap.get(pos(-29, 4)), Some(&TestTile(1)));
+        assert_eq!(tilemap.get(pos(-30, 5)), None);
+        assert_eq!(tilemap.get(moved(3, 4)), None);
+    }
+
+    #[test]
+    fn paint_path_fills_an_l_shape() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        let points = [IVec2::new(-2, 0), IVec2::new(2, 0), IVec2::new(2, 3)];
+        tilemap.paint_path(&points, 1, TestTile(1));
+
+        let mut painted = tilemap
+            .iter_positions()
+            .map(|(pos, _)| IVec2::from(pos))
+            .collect::<Vec<_>>();
+        painted.sort_by_key(|pos| (pos.x, pos.y));
+        let mut expected = (-2..=2)
+            .map(|x| IVec2::new(x, 0))
+            .chain((1..=3).map(|y| IVec2::new(2, y)))
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|pos| (pos.x, pos.y));
+        assert_eq!(painted, expected);
+        assert_eq!(dirty_chunks(&tilemap), [IVec2::NEG_X, IVec2::ZERO]);
+
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.paint_path(&points, 0, TestTile(1));
+        assert_eq!(tilemap.iter().count(), 0);
+    }
+
+    #[test]
+    fn lod_level_changes_past_each_distance() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        assert_eq!(tilemap.chunk_lod_level(IVec2::ZERO, Vec2::splat(1000.0)), 0);
+
+        tilemap.set_lod_distances(vec![20.0, 10.0]);
+        assert_eq!(tilemap.lod_distances(), [10.0, 20.0]);
+        let center = tilemap
+            .layout()
+            .tile_offset(IVec2::splat(CHUNK_SIZE as i32 / 2));
+        for (distance, level) in [
+            (0.0, 0),
+            (10.0, 0),
+            (10.5, 1),
+            (20.0, 1),
+            (20.5, 2),
+            (1000.0, 2),
+        ] {
+            let camera = center + Vec2::new(0.0, distance);
+            assert_eq!(
+                tilemap.chunk_lod_level(IVec2::ZERO, camera),
+                level,
+                "at {distance}"
+            );
+        }
+        let next_center = center + tilemap.layout().tile_offset(IVec2::X * CHUNK_SIZE as i32);
+        assert_eq!(tilemap.chunk_lod_level(IVec2::X, next_center), 0);
+    }
+
+    #[test]
+    fn tiles_in_chunk_lists_only_that_chunk() {
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap.set(pos(3, 4), TestTile(1));
+        tilemap.set(pos(31, 0), TestTile(2));
+        tilemap.set(pos(32, 0), TestTile(3));
+        tilemap.set(pos(-1, 4), TestTile(4));
+
+        let mut tiles = tilemap
+            .tiles_in_chunk(IVec2::ZERO)
+            .map(|(pos, tile)| (pos, *tile))
+            .collect::<Vec<_>>();
+        tiles.sort_by_key(|(pos, _)| pos.as_index());
+        assert_eq!(
+            tiles,
+            [
+                (ChunkPos::new(31, 0), TestTile(2)),
+                (ChunkPos::new(3, 4), TestTile(1))
+            ]
+        );
+        assert_eq!(tilemap.tiles_in_chunk(IVec2::NEG_Y).count(), 0);
+
+        tilemap.get_or_create_chunk(IVec2::new(5, 5));
+        assert_eq!(tilemap.tiles_in_chunk(IVec2::new(5, 5)).count(), 0);
+    }
+
+    #[test]
+    fn fill_polygon_triangle_and_concave_arrow() {
+        let filled = |vertices: &[IVec2]| {
+            let mut tilemap = test_tilemap::<TestTile>();
+            tilemap.fill_polygon(vertices, TestTile(1));
+            let mut filled = tilemap
+                .iter_positions()
+                .map(|(pos, _)| IVec2::from(pos))
+                .collect::<Vec<_>>();
+            filled.sort_by_key(|pos| (pos.y, pos.x));
+            filled
+        };
+        let rows = |rows: &[(i32, Range<i32>)]| {
+            rows.iter()
+                .flat_map(|(y, xs)| xs.clone().map(|x| IVec2::new(x, *y)))
+                .collect::<Vec<_>>()
+        };
+
+        let triangle = [(0, 0), (4, 0), (0, 4)].map(IVec2::from);
+        assert_eq!(filled(&triangle), rows(&[(0, 0..3), (1, 0..2), (2, 0..1)]));
+
+        let arrow = [(0, 2), (4, 2), (4, 0), (8, 4), (4, 8), (4, 6), (0, 6)].map(IVec2::from);
+        assert_eq!(
+            filled(&arrow),
+            rows(&[
+                (1, 4..5),
+                (2, 0..6),
+                (3, 0..7),
+                (4, 0..7),
+                (5, 0..6),
+                (6, 4..5)
+            ])
+        );
+        assert_eq!(filled(&arrow[..2]), []);
+    }
+
+    #[test]
+    fn light_mask_marks_walls() {
+        let mut tilemap = test_tilemap::<LayeredTile>();
+        let wall = LayeredTile {
+            blocks_light: true,
+            ..default()
+        };
+        // A wall along x = 0 with floor either side, the row below left empty
+        for y in 0..3 {
+            tilemap.set(pos(-1, y), LayeredTile::default());
+            tilemap.set(pos(0, y), wall);
+            tilemap.set(pos(1, y), LayeredTile::default());
+        }
+        tilemap.set(pos(1, 1), wall);
+
+        let mask = tilemap.build_light_mask(IRect::new(IVec2::new(-1, -1), IVec2::new(1, 2)));
+        let rows: Vec<_> = mask.chunks(3).collect();
+        assert_eq!(
+            rows,
+            [
+                [false, false, false],
+                [false, true, false],
+                [false, true, true],
+                [false, true, false]
+            ]
+        );
+
+        let last = pos(i32::MAX, 0);
+        tilemap.with_chunk_mut(last.chunk, |chunk| chunk.set(last.tile, wall));
+        let mask = tilemap.build_light_mask(IRect::new(IVec2::new(i32::MAX - 1, 0), last.into()));
+        assert_eq!(mask, [false, true]);
     }
 }
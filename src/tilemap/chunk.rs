@@ -1,11 +1,15 @@
 use std::{
-    iter, mem,
+    fmt, iter, mem,
     ops::{Add, AddAssign, Index, IndexMut, Sub, SubAssign},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
 
-use bevy::prelude::*;
+use bevy::{prelude::*, render::primitives::Aabb, sprite::Rect};
+#[cfg(feature = "serde")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{rendering::MeshBuilder, tile::Tile, CHUNK_SIZE};
+use crate::{animation::TileAnimation, rendering::MeshBuilder, tile::Tile, CHUNK_SIZE};
 
 /// A chunk of tiles; [`CHUNK_SIZE`] by [`CHUNK_SIZE`]
 #[derive(Debug)]
@@ -14,6 +18,52 @@ pub struct Chunk<T: Tile> {
     pub(crate) regenerate_mesh: bool,
     pub(crate) mesh_carry_data: <<T as Tile>::MeshBuilder as MeshBuilder>::CarryData,
     pub(crate) mesh_entity: Option<Entity>,
+    /// The children of the mesh entity displaying the tiles in each mesh layer other than 0
+    pub(crate) sublayer_entities: Vec<(u8, Entity)>,
+    /// The meshes of this at each level of detail, most detailed first
+    pub(crate) lod_meshes: Vec<Handle<Mesh>>,
+    /// The index in `lod_meshes` of the mesh displayed
+    pub(crate) lod_level: usize,
+    mesh_aabb: Rect,
+    visual_offset: Vec2,
+    pub(crate) update_transform: bool,
+    min_regen_interval: Duration,
+    /// Time since startup when the mesh of this was last regenerated
+    pub(crate) last_regen: Option<Duration>,
+    pub(crate) dirty_tiles: DirtyTiles,
+    /// The animated tiles in the mesh of this
+    pub(crate) animated_tiles: Vec<AnimatedVertices>,
+    /// The number of tiles in this, or [`UNKNOWN_COUNT`] if slots were changed through
+    /// mutable references since it was last counted
+    ///
+    /// Atomic so it can be recounted through a shared reference
+    pub(crate) tile_count: AtomicUsize,
+}
+
+/// Stored as the tile count of a chunk when it has to be recounted
+const UNKNOWN_COUNT: usize = usize::MAX;
+
+/// The vertices of a tile with an animation in the mesh of a chunk
+#[derive(Debug, Clone)]
+pub(crate) struct AnimatedVertices {
+    pub(crate) animation: TileAnimation,
+    /// The index of the first vertex of the tile
+    pub(crate) first_vertex: usize,
+    /// The UVs of the vertices of the tile at frame 0
+    pub(crate) base_uvs: Vec<[f32; 2]>,
+    /// The frame the mesh shows
+    pub(crate) frame: usize,
+}
+
+/// Which tiles in a chunk changed since it's mesh was last generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DirtyTiles {
+    /// No tiles changed
+    Clean,
+    /// Only the tile at this position changed
+    One(ChunkPos),
+    /// Any number of tiles may have changed
+    All,
 }
 
 impl<T: Tile> Chunk<T> {
@@ -23,6 +73,56 @@ impl<T: Tile> Chunk<T> {
         self[pos].is_some()
     }
 
+    /// Returns `true` if there are no tiles in this
+    ///
+    /// See [`Self::tile_count()`]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tile_count() == 0
+    }
+
+    /// Returns the number of tiles in this
+    ///
+    /// The count is kept up to date by methods setting and removing tiles, so this is O(1),
+    /// unless tile slots were changed through [`IndexMut`] or a mutable iterator over slots
+    /// since this was last called
+    #[must_use]
+    pub fn tile_count(&self) -> usize {
+        match self.tile_count.load(Ordering::Relaxed) {
+            UNKNOWN_COUNT => {
+                let count = self.tiles.iter().filter(|slot| slot.is_some()).count();
+                self.tile_count.store(count, Ordering::Relaxed);
+                count
+            }
+            count => count,
+        }
+    }
+
+    /// Returns the cached number of tiles in this, or [`None`] if it has to be recounted
+    pub(crate) fn cached_tile_count(&self) -> Option<usize> {
+        match self.tile_count.load(Ordering::Relaxed) {
+            UNKNOWN_COUNT => None,
+            count => Some(count),
+        }
+    }
+
+    /// Writes `tile` to the slot at `pos`, keeping the tile count up to date
+    fn replace_slot(&mut self, pos: ChunkPos, tile: Option<T>) -> Option<T> {
+        let added = tile.is_some();
+        let old = mem::replace(&mut self.tiles[pos.as_index()], tile);
+        let count = self.tile_count.get_mut();
+        if *count != UNKNOWN_COUNT {
+            *count = *count + added as usize - old.is_some() as usize;
+        }
+        old
+    }
+
+    /// Returns the number of tiles in this matching `pred`
+    #[must_use]
+    pub fn count_matching(&self, pred: impl Fn(&T) -> bool) -> usize {
+        self.iter_tiles().filter(|tile| pred(tile)).count()
+    }
+
     /// Tells this to regenerate it's mesh the next time it is displayed
     ///
     /// Mesh regeneration is more expensive than animation, so use animation whenever
@@ -30,22 +130,251 @@ impl<T: Tile> Chunk<T> {
     #[inline]
     pub fn regenerate_mesh(&mut self) {
         self.regenerate_mesh = true;
+        self.dirty_tiles = DirtyTiles::All;
+    }
+
+    /// Tells this to update the mesh of the tile at `pos` the next time it is displayed
+    ///
+    /// If no other tiles changed, this allows the mesh to be patched instead of regenerated
+    fn tile_changed(&mut self, pos: ChunkPos) {
+        self.regenerate_mesh = true;
+        self.dirty_tiles = match self.dirty_tiles {
+            DirtyTiles::Clean => DirtyTiles::One(pos),
+            DirtyTiles::One(changed) if changed == pos => DirtyTiles::One(pos),
+            _ => DirtyTiles::All,
+        };
+    }
+
+    /// The meshes of this at each level of detail, from most to least detailed
+    ///
+    /// Empty if the mesh of this hasn't been generated.  See
+    /// [`MeshBuilder::finish_with_lods()`]
+    #[must_use]
+    pub fn lod_meshes(&self) -> &[Handle<Mesh>] {
+        &self.lod_meshes
+    }
+
+    /// The index in [`lod_meshes()`](Self::lod_meshes()) of the mesh displayed
+    ///
+    /// See [`Tilemap::set_lod_distances()`](super::Tilemap::set_lod_distances())
+    #[must_use]
+    pub fn lod_level(&self) -> usize {
+        self.lod_level
+    }
+
+    /// The area covered by the vertices of the meshes of this when they were last
+    /// generated, relative to the mesh
+    ///
+    /// Includes every level of detail and mesh layer, and is the area mesh entities are
+    /// frustum culled with.  Empty if the mesh of this hasn't been generated or has no
+    /// vertices
+    #[must_use]
+    pub fn mesh_aabb(&self) -> Rect {
+        self.mesh_aabb
+    }
+
+    /// Sets [`Self::mesh_aabb()`] to the x and y extents of `aabb`, or an empty area for
+    /// [`None`]
+    pub(crate) fn set_mesh_aabb(&mut self, aabb: Option<&Aabb>) {
+        self.mesh_aabb = aabb.map_or_else(Rect::default, |aabb| Rect {
+            min: Vec3::from(aabb.min()).truncate(),
+            max: Vec3::from(aabb.max()).truncate(),
+        });
+    }
+
+    /// The offset the mesh of this is displayed at, relative to it's position in the tilemap
+    #[must_use]
+    pub fn visual_offset(&self) -> Vec2 {
+        self.visual_offset
+    }
+
+    /// Sets the offset the mesh of this is displayed at, relative to it's position
+    /// in the tilemap
+    ///
+    /// Only moves the mesh; the position of the chunk in the tilemap is unchanged.
+    /// Doesn't require regenerating the mesh of this
+    pub fn set_visual_offset(&mut self, offset: Vec2) {
+        self.visual_offset = offset;
+        self.update_transform = true;
+    }
+
+    /// The minimum time between regenerations of the mesh of this
+    #[must_use]
+    pub fn min_regen_interval(&self) -> Duration {
+        self.min_regen_interval
+    }
+
+    /// Sets the minimum time between regenerations of the mesh of this
+    ///
+    /// If this is told to regenerate it's mesh sooner than `interval` after it was last
+    /// regenerated, it stays flagged and is regenerated once `interval` has passed.
+    /// Useful for chunks with tiles that change often but only need to be displayed
+    /// occasionally.  Defaults to [`Duration::ZERO`]
+    pub fn set_min_regen_interval(&mut self, interval: Duration) {
+        self.min_regen_interval = interval;
+    }
+
+    /// Returns `true` if this is flagged to regenerate it's mesh and enough time has passed
+    /// since it's last regeneration
+    ///
+    /// `now` is the time since startup
+    pub(crate) fn ready_to_regenerate(&self, now: Duration) -> bool {
+        let elapsed = self.last_regen.map(|last| now.saturating_sub(last));
+        self.regenerate_mesh
+            && !matches!(elapsed, Some(elapsed) if elapsed < self.min_regen_interval)
     }
 
     /// Sets the tile at `pos`, returning it's previous value
     ///
-    /// Tells this to regenerate it's mesh the next time it is displayed
+    /// Tells this to regenerate it's mesh the next time it is displayed, unless the previous
+    /// tile is [visually equal](Tile::visually_eq) to the new one
     pub fn set(&mut self, pos: ChunkPos, tile: impl Into<T>) -> Option<T> {
-        self.regenerate_mesh();
-        mem::replace(&mut self[pos], Some(tile.into()))
+        let tile = tile.into();
+        if !matches!(&self[pos], Some(old) if old.visually_eq(&tile)) {
+            self.tile_changed(pos);
+        }
+        self.replace_slot(pos, Some(tile))
+    }
+
+    /// Sets the tile slot at `pos`, returning it's previous value
+    ///
+    /// Unlike [`Self::set()`] and [`Self::remove()`], this does NOT tell this to regenerate
+    /// it's mesh.  Useful when writing many tiles at once, such as when loading a chunk.
+    /// The caller is responsible for calling [`Self::regenerate_mesh()`] once done;
+    /// otherwise the mesh of this will not match it's tiles
+    pub fn set_raw(&mut self, pos: ChunkPos, tile: Option<T>) -> Option<T> {
+        self.replace_slot(pos, tile)
+    }
+
+    /// Creates a chunk with the result of `f` on every tile in this, at the same positions
+    ///
+    /// The new chunk has the same visual offset and minimum regeneration interval as this,
+    /// but no mesh, so it is flagged to regenerate it's mesh the next time it is displayed
+    #[must_use]
+    pub fn map<U: Tile>(&self, f: impl Fn(&T) -> U) -> Chunk<U> {
+        let mut chunk = Chunk::<U>::default();
+        for (pos, tile) in self.iter_tile_positions() {
+            chunk.set_raw(pos, Some(f(tile)));
+        }
+        chunk.visual_offset = self.visual_offset;
+        chunk.min_regen_interval = self.min_regen_interval;
+        chunk.regenerate_mesh();
+        chunk
+    }
+
+    /// Converts this into it's tile slots, in row-major order
+    ///
+    /// The mesh of this is not despawned
+    #[must_use]
+    pub fn into_vec(self) -> Vec<Option<T>> {
+        self.tiles.into()
+    }
+
+    /// Creates a chunk from it's tile slots, in row-major order
+    ///
+    /// Errors if `tiles` doesn't have exactly [`CHUNK_SIZE`] * [`CHUNK_SIZE`] slots.
+    /// The chunk has no mesh, so it is flagged to regenerate it's mesh the next time it
+    /// is displayed
+    pub fn from_vec(tiles: Vec<Option<T>>) -> Result<Self, WrongLength> {
+        let tiles = tiles
+            .try_into()
+            .map_err(|tiles: Vec<_>| WrongLength(tiles.len()))?;
+        let mut chunk = Chunk {
+            tiles,
+            tile_count: AtomicUsize::new(UNKNOWN_COUNT),
+            ..default()
+        };
+        chunk.regenerate_mesh();
+        Ok(chunk)
+    }
+
+    /// Returns this to the state of a newly created chunk, for reuse
+    pub(crate) fn reset(&mut self) {
+        self.tiles.iter_mut().for_each(|slot| *slot = None);
+        *self.tile_count.get_mut() = 0;
+        self.regenerate_mesh = false;
+        self.mesh_carry_data = Default::default();
+        self.mesh_entity = None;
+        self.sublayer_entities.clear();
+        self.lod_meshes.clear();
+        self.lod_level = 0;
+        self.mesh_aabb = Rect::default();
+        self.visual_offset = Vec2::ZERO;
+        self.update_transform = false;
+        self.min_regen_interval = Duration::ZERO;
+        self.last_regen = None;
+        self.dirty_tiles = DirtyTiles::Clean;
+        self.animated_tiles.clear();
+    }
+
+    /// Returns `true` if the settings and mesh state of this are the same as after
+    /// [`reset()`](Self::reset()), without checking the tiles
+    pub(crate) fn is_reset(&self) -> bool {
+        !self.regenerate_mesh
+            && self.mesh_entity.is_none()
+            && self.sublayer_entities.is_empty()
+            && self.lod_meshes.is_empty()
+            && self.lod_level == 0
+            && self.mesh_aabb.min == Vec2::ZERO
+            && self.mesh_aabb.max == Vec2::ZERO
+            && self.visual_offset == Vec2::ZERO
+            && !self.update_transform
+            && self.min_regen_interval == Duration::ZERO
+            && self.last_regen.is_none()
+            && self.dirty_tiles == DirtyTiles::Clean
+            && self.animated_tiles.is_empty()
+    }
+
+    /// Returns a mutable reference to the tile slot at `pos` and a reference to the tile slot
+    /// at `other`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pos` and `other` are the same
+    pub(crate) fn get_pair_mut(
+        &mut self,
+        pos: ChunkPos,
+        other: ChunkPos,
+    ) -> (&mut Option<T>, &Option<T>) {
+        let (pos, other) = (pos.as_index(), other.as_index());
+        assert_ne!(pos, other, "positions must be different");
+        *self.tile_count.get_mut() = UNKNOWN_COUNT;
+        if pos < other {
+            let (low, high) = self.tiles.split_at_mut(other);
+            (&mut low[pos], &high[0])
+        } else {
+            let (low, high) = self.tiles.split_at_mut(pos);
+            (&mut high[0], &low[other])
+        }
     }
 
     /// Removes the tile at `pos`, returning it's previous value
     ///
     /// Tells this to regenerate it's mesh the next time it is displayed
     pub fn remove(&mut self, pos: ChunkPos) -> Option<T> {
-        self.regenerate_mesh();
-        mem::take(&mut self[pos])
+        self.tile_changed(pos);
+        self.replace_slot(pos, None)
+    }
+
+    /// Removes every tile for which `f` returns `false`
+    ///
+    /// Tells this to regenerate it's mesh the next time it is displayed if any tiles
+    /// were removed
+    pub fn retain(&mut self, mut f: impl FnMut(ChunkPos, &T) -> bool) {
+        let mut removed = 0;
+        for (pos, slot) in ChunkPos::iter_positions().zip(self.tiles.iter_mut()) {
+            if matches!(slot, Some(tile) if !f(pos, tile)) {
+                *slot = None;
+                removed += 1;
+            }
+        }
+        let count = self.tile_count.get_mut();
+        if *count != UNKNOWN_COUNT {
+            *count -= removed;
+        }
+        if removed > 0 {
+            self.regenerate_mesh();
+        }
     }
 
     /// Returns an iterator over all tile slots in this
@@ -61,6 +390,7 @@ impl<T: Tile> Chunk<T> {
     /// If mutating the tile slot results in a change that requires
     /// regenerating the chunk mesh, call [`Self::regenerate_mesh()`]
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Option<T>> + ExactSizeIterator {
+        *self.tile_count.get_mut() = UNKNOWN_COUNT;
         self.tiles.iter_mut()
     }
 
@@ -74,7 +404,7 @@ impl<T: Tile> Chunk<T> {
     /// If mutating the tile slot results in a change that requires
     /// regenerating the chunk mesh, call [`Self::regenerate_mesh()`]
     pub fn iter_tiles_mut(&mut self) -> impl Iterator<Item = &mut T> {
-        self.iter_mut().filter_map(|t| t.as_mut())
+        self.tiles.iter_mut().filter_map(|t| t.as_mut())
     }
 
     /// Returns an iterator over all tile slots in this and their position
@@ -87,6 +417,17 @@ impl<T: Tile> Chunk<T> {
         ChunkPos::iter_positions().zip(self.iter())
     }
 
+    /// Returns an iterator over all tile slots in this and their position
+    ///
+    /// Iterates in column-major order: (0, 0), (0, 1), (0, 2), and so on.
+    /// For row-major order, use [`Self::iter_positions()`]
+    pub fn iter_positions_col_major(&self) -> impl Iterator<Item = (ChunkPos, &Option<T>)> {
+        ChunkPos::iter_positions().map(|pos| {
+            let pos = ChunkPos(pos.1, pos.0);
+            (pos, &self[pos])
+        })
+    }
+
     /// Returns an iterator over all tile slots in this and their position
     /// that allows modifying each tile slot
     ///
@@ -112,9 +453,23 @@ impl<T: Tile> Chunk<T> {
     /// If mutating the tile slot results in a change that requires
     /// regenerating the chunk mesh, call [`Self::regenerate_mesh()`]
     pub fn iter_tile_positions_mut(&mut self) -> impl Iterator<Item = (ChunkPos, &mut T)> {
-        self.iter_positions_mut()
+        ChunkPos::iter_positions()
+            .zip(self.tiles.iter_mut())
             .filter_map(|(pos, slot)| slot.as_mut().map(|tile| (pos, tile)))
     }
+
+    /// Returns an iterator over all the set tiles in this and their indices
+    /// that allows mutating each tile
+    ///
+    /// Indices are the same as those returned by [`ChunkPos::as_index()`].
+    /// If mutating the tile slot results in a change that requires
+    /// regenerating the chunk mesh, call [`Self::regenerate_mesh()`]
+    pub fn iter_tiles_mut_indexed(&mut self) -> impl Iterator<Item = (usize, &mut T)> {
+        self.tiles
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.as_mut().map(|tile| (index, tile)))
+    }
 }
 
 impl<T: Tile> Default for Chunk<T> {
@@ -124,10 +479,56 @@ impl<T: Tile> Default for Chunk<T> {
             regenerate_mesh: false,
             mesh_carry_data: <<T as Tile>::MeshBuilder as MeshBuilder>::CarryData::default(),
             mesh_entity: None,
+            sublayer_entities: Vec::new(),
+            lod_meshes: Vec::new(),
+            lod_level: 0,
+            mesh_aabb: Rect::default(),
+            visual_offset: Vec2::ZERO,
+            update_transform: false,
+            min_regen_interval: Duration::ZERO,
+            last_regen: None,
+            dirty_tiles: DirtyTiles::Clean,
+            animated_tiles: Vec::new(),
+            tile_count: AtomicUsize::new(0),
         }
     }
 }
 
+impl<T: Tile> Clone for Chunk<T> {
+    /// Clones the tiles in this
+    ///
+    /// The clone has no mesh, so it is flagged to regenerate it's mesh
+    /// the next time it is displayed
+    fn clone(&self) -> Self {
+        Chunk {
+            tiles: self.tiles.clone(),
+            regenerate_mesh: true,
+            mesh_carry_data: <<T as Tile>::MeshBuilder as MeshBuilder>::CarryData::default(),
+            mesh_entity: None,
+            sublayer_entities: Vec::new(),
+            lod_meshes: Vec::new(),
+            lod_level: 0,
+            mesh_aabb: Rect::default(),
+            visual_offset: self.visual_offset,
+            update_transform: false,
+            min_regen_interval: self.min_regen_interval,
+            last_regen: None,
+            dirty_tiles: DirtyTiles::All,
+            animated_tiles: Vec::new(),
+            tile_count: AtomicUsize::new(self.tile_count.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<T: Tile + PartialEq> PartialEq for Chunk<T> {
+    /// Compares the tiles in the chunks, ignoring the state of their meshes
+    fn eq(&self, other: &Self) -> bool {
+        self.tiles == other.tiles
+    }
+}
+
+impl<T: Tile + Eq> Eq for Chunk<T> {}
+
 impl<T: Tile> Index<ChunkPos> for Chunk<T> {
     type Output = Option<T>;
 
@@ -145,10 +546,29 @@ impl<T: Tile> IndexMut<ChunkPos> for Chunk<T> {
     /// regenerating the chunk mesh, call [`Self::regenerate_mesh()`]
     #[must_use]
     fn index_mut(&mut self, index: ChunkPos) -> &mut Self::Output {
+        *self.tile_count.get_mut() = UNKNOWN_COUNT;
         &mut self.tiles[index.as_index()]
     }
 }
 
+/// The error when creating a [`Chunk`] from a number of tile slots other than
+/// [`CHUNK_SIZE`] * [`CHUNK_SIZE`]; holds the number of slots given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongLength(pub usize);
+
+impl fmt::Display for WrongLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} tile slots in a chunk, found {}",
+            CHUNK_SIZE * CHUNK_SIZE,
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for WrongLength {}
+
 /// A position in a chunk
 ///
 /// Gaurenteed to be between 0 and [`CHUNK_SIZE`]
@@ -238,6 +658,18 @@ impl ChunkPos {
         x as usize + y as usize * CHUNK_SIZE
     }
 
+    /// The position at `index` in a [`CHUNK_SIZE`]x[`CHUNK_SIZE`] array (row-major)
+    ///
+    /// The inverse of [`as_index()`](Self::as_index()).  Returns [`None`] if `index` is
+    /// outside of the array
+    #[must_use]
+    pub fn from_index(index: usize) -> Option<Self> {
+        (index < CHUNK_SIZE * CHUNK_SIZE).then_some(ChunkPos(
+            (index % CHUNK_SIZE) as u8,
+            (index / CHUNK_SIZE) as u8,
+        ))
+    }
+
     /// This as an [`IVec2`]
     #[must_use]
     pub fn as_ivec2(self) -> IVec2 {
@@ -262,6 +694,24 @@ impl ChunkPos {
         )
     }
 
+    /// `self` + `rhs`, or [`None`] if the result is outside of the chunk
+    #[must_use]
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        ChunkPos::try_new(
+            self.x().checked_add(rhs.x())?,
+            self.y().checked_add(rhs.y())?,
+        )
+    }
+
+    /// `self` - `rhs`, or [`None`] if the result is outside of the chunk
+    #[must_use]
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        ChunkPos::try_new(
+            self.x().checked_sub(rhs.x())?,
+            self.y().checked_sub(rhs.y())?,
+        )
+    }
+
     /// `self` + `rhs`
     ///
     /// Returns the result of the addition wrapped around [`CHUNK_SIZE`],
@@ -307,6 +757,35 @@ impl ChunkPos {
     }
 }
 
+impl fmt::Display for ChunkPos {
+    /// Formats this as `(x, y)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x(), self.y())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for ChunkPos {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tup().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ChunkPos {
+    /// Deserializes a [`ChunkPos`] from a tuple of it's x and y coordinates
+    ///
+    /// Errors if either coordinate is >= [`CHUNK_SIZE`]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (x, y) = <(u8, u8)>::deserialize(deserializer)?;
+        ChunkPos::try_new(x, y).ok_or_else(|| {
+            D::Error::custom(format_args!(
+                "chunk position ({x}, {y}) is outside of a chunk of size {CHUNK_SIZE}"
+            ))
+        })
+    }
+}
+
 impl TryFrom<IVec2> for ChunkPos {
     type Error = ();
 
@@ -363,3 +842,285 @@ impl SubAssign for ChunkPos {
         assert!(self.0 < CHUNK_SIZE as u8 && self.1 < CHUNK_SIZE as u8);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn tile_count_follows_edits() {
+        let mut chunk = Chunk::<TestTile>::default();
+        assert!(chunk.is_empty());
+
+        chunk.set(ChunkPos::new(1, 2), TestTile(0));
+        chunk.set(ChunkPos::new(1, 2), TestTile(1));
+        chunk.set_raw(ChunkPos::new(3, 4), Some(TestTile(2)));
+        assert_eq!(chunk.tile_count(), 2);
+        assert!(!chunk.is_empty());
+
+        chunk.retain(|pos, _| pos == ChunkPos::new(1, 2));
+        assert_eq!(chunk.tile_count(), 1);
+
+        chunk[ChunkPos::new(5, 5)] = Some(TestTile(3));
+        assert_eq!(chunk.tile_count(), 2);
+
+        chunk.remove(ChunkPos::new(1, 2));
+        chunk.remove(ChunkPos::new(1, 2));
+        assert_eq!(chunk.tile_count(), 1);
+
+        chunk.reset();
+        assert!(chunk.is_empty());
+        let mut tiles = vec![None; CHUNK_SIZE * CHUNK_SIZE];
+        tiles[0] = Some(TestTile(0));
+        assert_eq!(Chunk::from_vec(tiles).unwrap().tile_count(), 1);
+    }
+
+    #[test]
+    fn clones_have_equal_tiles_and_no_mesh() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).set(pos(1, 2), TestTile(1));
+        tilemap::<TestTile>(&mut app).set(pos(30, 4), TestTile(2));
+        app.update();
+        let tilemap = tilemap::<TestTile>(&mut app);
+        let chunk = tilemap.get_chunk(IVec2::ZERO).unwrap();
+        assert!(chunk.mesh_entity.is_some());
+
+        let clone = chunk.clone();
+        assert_eq!(&clone, chunk);
+        assert_eq!(clone.tile_count(), 2);
+        assert_eq!(clone.mesh_entity, None);
+        assert!(clone.lod_meshes().is_empty());
+        assert!(clone.regenerate_mesh);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips_and_rejects_out_of_range() {
+        use crate::tilemap::TilemapPos;
+
+        let pos = pos(-70, 31);
+        let json = serde_json::to_string(&pos).unwrap();
+        assert_eq!(serde_json::from_str::<TilemapPos>(&json).unwrap(), pos);
+        assert_eq!(
+            serde_json::from_str::<ChunkPos>("[31, 0]").unwrap(),
+            ChunkPos::new(31, 0)
+        );
+        assert!(serde_json::from_str::<ChunkPos>("[32, 0]").is_err());
+        assert!(
+            serde_json::from_str::<TilemapPos>(r#"{"chunk": [0, 0], "tile": [0, 255]}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn indexed_tiles_match_positions() {
+        let mut chunk = Chunk::<TestTile>::default();
+        for pos in [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(5, 1),
+            ChunkPos::new(31, 31),
+        ] {
+            chunk.set(pos, TestTile(pos.x()));
+        }
+        let indices: Vec<_> = chunk
+            .iter_tiles_mut_indexed()
+            .map(|(index, tile)| {
+                assert_eq!(ChunkPos::from_index(index).unwrap().x(), tile.0);
+                index
+            })
+            .collect();
+        assert_eq!(
+            indices,
+            [
+                ChunkPos::new(0, 0).as_index(),
+                ChunkPos::new(5, 1).as_index(),
+                ChunkPos::new(31, 31).as_index()
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_writes_wait_for_regenerate_mesh() {
+        let mut app = test_app::<TestTile, _>();
+        tilemap::<TestTile>(&mut app).with_chunk_mut(IVec2::ZERO, |chunk| {
+            chunk.set_raw(ChunkPos::new(0, 0), Some(TestTile(1)));
+            chunk.set_raw(ChunkPos::new(1, 0), Some(TestTile(2)));
+            assert!(!chunk.regenerate_mesh);
+        });
+        app.update();
+        assert!(chunk_mesh::<TestTile>(&app, IVec2::ZERO).is_none());
+
+        tilemap::<TestTile>(&mut app)
+            .get_chunk_mut(IVec2::ZERO)
+            .unwrap()
+            .regenerate_mesh();
+        app.update();
+        let (positions, uvs, _) = mesh_data(chunk_mesh::<TestTile>(&app, IVec2::ZERO).unwrap());
+        assert_eq!(positions.len(), 8);
+        assert_eq!(uvs[0], [1.0, 0.0]);
+        assert_eq!(uvs[4], [2.0, 0.0]);
+    }
+
+    #[test]
+    fn index_round_trips() {
+        for pos in [ChunkPos::ZERO, ChunkPos::new(31, 0), ChunkPos::new(7, 19)] {
+            assert_eq!(ChunkPos::from_index(pos.as_index()), Some(pos));
+        }
+        assert_eq!(
+            ChunkPos::from_index(CHUNK_SIZE * CHUNK_SIZE - 1),
+            Some(ChunkPos::new(31, 31))
+        );
+        assert_eq!(ChunkPos::from_index(CHUNK_SIZE * CHUNK_SIZE), None);
+        assert_eq!(ChunkPos::from_index(usize::MAX), None);
+    }
+
+    #[test]
+    fn regeneration_waits_for_interval() {
+        let mut chunk = Chunk::<TestTile>::default();
+        chunk.set_min_regen_interval(Duration::from_millis(100));
+        assert!(!chunk.ready_to_regenerate(Duration::ZERO));
+
+        chunk.set(ChunkPos::new(0, 0), TestTile(0));
+        assert!(chunk.ready_to_regenerate(Duration::ZERO));
+
+        chunk.last_regen = Some(Duration::from_secs(1));
+        assert!(!chunk.ready_to_regenerate(Duration::from_millis(1050)));
+        assert!(chunk.ready_to_regenerate(Duration::from_millis(1100)));
+        assert!(chunk.ready_to_regenerate(Duration::from_secs(5)));
+
+        chunk.set_min_regen_interval(Duration::ZERO);
+        assert!(chunk.ready_to_regenerate(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn col_major_iterates_columns_first() {
+        let mut chunk = Chunk::<TestTile>::default();
+        chunk.set(ChunkPos::new(1, 0), TestTile(1));
+        chunk.set(ChunkPos::new(0, 1), TestTile(2));
+
+        let positions: Vec<_> = chunk
+            .iter_positions_col_major()
+            .map(|(pos, _)| pos.tup())
+            .take(CHUNK_SIZE + 1)
+            .collect();
+        assert_eq!(positions[..3], [(0, 0), (0, 1), (0, 2)]);
+        assert_eq!(positions[CHUNK_SIZE - 1], (0, CHUNK_SIZE as u8 - 1));
+        assert_eq!(positions[CHUNK_SIZE], (1, 0));
+
+        let tiles: Vec<_> = chunk
+            .iter_positions_col_major()
+            .filter_map(|(_, tile)| tile.map(|tile| tile.0))
+            .collect();
+        assert_eq!(tiles, [2, 1]);
+        assert_eq!(
+            chunk.iter_positions_col_major().count(),
+            CHUNK_SIZE * CHUNK_SIZE
+        );
+    }
+
+    #[test]
+    fn vec_round_trips() {
+        let mut chunk = Chunk::<TestTile>::default();
+        chunk.set(ChunkPos::new(0, 0), TestTile(1));
+        chunk.set(ChunkPos::new(5, 2), TestTile(2));
+        let tiles = chunk.into_vec();
+        assert_eq!(tiles.len(), CHUNK_SIZE * CHUNK_SIZE);
+        assert_eq!(tiles[ChunkPos::new(5, 2).as_index()], Some(TestTile(2)));
+
+        let chunk = Chunk::from_vec(tiles.clone()).unwrap();
+        assert_eq!(chunk.tile_count(), 2);
+        assert_eq!(chunk[ChunkPos::new(0, 0)], Some(TestTile(1)));
+        assert!(chunk.regenerate_mesh);
+        assert_eq!(chunk.mesh_entity, None);
+        assert_eq!(chunk.into_vec(), tiles);
+    }
+
+    #[test]
+    fn from_vec_rejects_wrong_length() {
+        let too_short = vec![None; CHUNK_SIZE * CHUNK_SIZE - 1];
+        let too_long = vec![Some(TestTile(0)); CHUNK_SIZE * CHUNK_SIZE + 1];
+        assert_eq!(
+            Chunk::<TestTile>::from_vec(too_short).err(),
+            Some(WrongLength(CHUNK_SIZE * CHUNK_SIZE - 1))
+        );
+        assert_eq!(
+            Chunk::from_vec(too_long).err(),
+            Some(WrongLength(CHUNK_SIZE * CHUNK_SIZE + 1))
+        );
+        assert_eq!(
+            Chunk::<TestTile>::from_vec(Vec::new()).err(),
+            Some(WrongLength(0))
+        );
+    }
+
+    #[test]
+    fn map_preserves_occupancy() {
+        let mut chunk = Chunk::<TestTile>::default();
+        for (i, pos) in [(0, 0), (7, 3), (31, 31)].into_iter().enumerate() {
+            chunk.set(ChunkPos::new(pos.0, pos.1), TestTile(i as u8));
+        }
+        chunk.set_visual_offset(Vec2::new(0.5, 0.0));
+
+        let mapped = chunk.map(|tile| UnpatchedTile(tile.0 + 10));
+        assert_eq!(mapped.tile_count(), 3);
+        for ((pos, tile), (_, mapped)) in chunk.iter_positions().zip(mapped.iter_positions()) {
+            assert_eq!(
+                mapped.map(|tile| tile.0),
+                tile.map(|tile| tile.0 + 10),
+                "at {pos:?}"
+            );
+        }
+        assert_eq!(mapped.visual_offset(), Vec2::new(0.5, 0.0));
+        assert!(mapped.regenerate_mesh);
+        assert_eq!(mapped.mesh_entity, None);
+    }
+
+    #[test]
+    fn retain_removes_rejected_tiles() {
+        let mut chunk = Chunk::<TestTile>::default();
+        for x in 0..6 {
+            chunk.set(ChunkPos::new(x, 0), TestTile(x));
+        }
+        chunk.regenerate_mesh = false;
+
+        chunk.retain(|_, _| true);
+        assert_eq!(chunk.tile_count(), 6);
+        assert!(!chunk.regenerate_mesh);
+
+        chunk.retain(|pos, tile| pos.x() < 4 && tile.0 % 2 == 0);
+        let kept: Vec<_> = chunk.iter_tiles().map(|tile| tile.0).collect();
+        assert_eq!(kept, [0, 2]);
+        assert_eq!(chunk.tile_count(), 2);
+        assert!(chunk.regenerate_mesh);
+
+        let mut empty = Chunk::<TestTile>::default();
+        empty.retain(|_, _| panic!("there are no tiles to check"));
+        assert!(empty.is_empty());
+        assert!(!empty.regenerate_mesh);
+    }
+
+    #[test]
+    fn checked_arithmetic_stays_in_chunk() {
+        let a = ChunkPos::new(30, 5);
+        let b = ChunkPos::new(1, 5);
+        assert_eq!(a.checked_add(b), Some(ChunkPos::new(31, 10)));
+        assert_eq!(a.checked_add(ChunkPos::new(2, 0)), None);
+        assert_eq!(ChunkPos::new(31, 31).checked_add(ChunkPos::new(0, 1)), None);
+
+        assert_eq!(a.checked_sub(b), Some(ChunkPos::new(29, 0)));
+        assert_eq!(b.checked_sub(a), None);
+        assert_eq!(a.checked_sub(ChunkPos::new(0, 6)), None);
+    }
+
+    #[test]
+    fn count_matching_checks_only_set_tiles() {
+        let mut chunk = Chunk::<TestTile>::default();
+        assert_eq!(chunk.count_matching(|_| true), 0);
+        for x in 0..10 {
+            chunk.set(ChunkPos::new(x, x), TestTile(x % 3));
+        }
+        assert_eq!(chunk.count_matching(|_| true), 10);
+        assert_eq!(chunk.count_matching(|tile| tile.0 == 0), 4);
+        assert_eq!(chunk.count_matching(|tile| tile.0 == 7), 0);
+    }
+}
@@ -0,0 +1,332 @@
+#[cfg(not(feature = "deterministic"))]
+use std::iter;
+#[cfg(feature = "deterministic")]
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use bevy::prelude::*;
+#[cfg(not(feature = "deterministic"))]
+use bevy::utils::HashMap;
+
+use super::{Chunk, Direction};
+use crate::tile::Tile;
+
+/// The chunks of a tilemap, by position
+///
+/// Backed by a hashmap, or with the `deterministic` feature by a [`BTreeMap`] sorted by y,
+/// then by x, so chunks are always iterated in the same order
+#[derive(Debug, Clone)]
+pub(crate) struct ChunkMap<T: Tile> {
+    map: Map<Chunk<T>>,
+}
+
+#[cfg(not(feature = "deterministic"))]
+type Map<V> = HashMap<IVec2, V>;
+#[cfg(not(feature = "deterministic"))]
+type Key = IVec2;
+
+#[cfg(feature = "deterministic")]
+type Map<V> = BTreeMap<ChunkKey, V>;
+#[cfg(feature = "deterministic")]
+type Key = ChunkKey;
+
+/// A chunk position ordered by y, then by x
+#[cfg(feature = "deterministic")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkKey(IVec2);
+
+#[cfg(feature = "deterministic")]
+impl Ord for ChunkKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.y, self.0.x).cmp(&(other.0.y, other.0.x))
+    }
+}
+
+#[cfg(feature = "deterministic")]
+impl PartialOrd for ChunkKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(not(feature = "deterministic"))]
+#[inline]
+fn key(pos: IVec2) -> Key {
+    pos
+}
+
+#[cfg(not(feature = "deterministic"))]
+#[inline]
+fn pos(key: &Key) -> &IVec2 {
+    key
+}
+
+#[cfg(feature = "deterministic")]
+#[inline]
+fn key(pos: IVec2) -> Key {
+    ChunkKey(pos)
+}
+
+#[cfg(feature = "deterministic")]
+#[inline]
+fn pos(key: &Key) -> &IVec2 {
+    &key.0
+}
+
+impl<T: Tile> ChunkMap<T> {
+    #[cfg(not(feature = "deterministic"))]
+    pub fn with_capacity(capacity: usize) -> Self {
+        ChunkMap {
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Sorted maps don't preallocate, so `capacity` is ignored
+    #[cfg(feature = "deterministic")]
+    pub fn with_capacity(_capacity: usize) -> Self {
+        ChunkMap {
+            map: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, pos: &IVec2) -> Option<&Chunk<T>> {
+        self.map.get(&key(*pos))
+    }
+
+    pub fn get_mut(&mut self, pos: &IVec2) -> Option<&mut Chunk<T>> {
+        self.map.get_mut(&key(*pos))
+    }
+
+    pub fn contains_key(&self, pos: &IVec2) -> bool {
+        self.map.contains_key(&key(*pos))
+    }
+
+    pub fn insert(&mut self, pos: IVec2, chunk: Chunk<T>) -> Option<Chunk<T>> {
+        self.map.insert(key(pos), chunk)
+    }
+
+    pub fn remove(&mut self, pos: &IVec2) -> Option<Chunk<T>> {
+        self.map.remove(&key(*pos))
+    }
+
+    /// Returns the chunk at `pos`, inserting the chunk returned by `f` if there isn't one
+    pub fn get_or_insert_with(
+        &mut self,
+        pos: IVec2,
+        f: impl FnOnce() -> Chunk<T>,
+    ) -> &mut Chunk<T> {
+        self.map.entry(key(pos)).or_insert_with(f)
+    }
+
+    /// Returns the chunks at `a` and `b`, or [`None`] if either doesn't exist or they're the
+    /// same chunk
+    #[cfg(not(feature = "deterministic"))]
+    pub fn get_pair_mut(&mut self, a: &IVec2, b: &IVec2) -> Option<[&mut Chunk<T>; 2]> {
+        self.map.get_many_mut([a, b])
+    }
+
+    /// Returns the chunks at `a` and `b`, or [`None`] if either doesn't exist or they're the
+    /// same chunk
+    #[cfg(feature = "deterministic")]
+    pub fn get_pair_mut(&mut self, a: &IVec2, b: &IVec2) -> Option<[&mut Chunk<T>; 2]> {
+        let (a, b) = (key(*a), key(*b));
+        let swapped = match a.cmp(&b) {
+            Ordering::Less => false,
+            Ordering::Equal => return None,
+            Ordering::Greater => true,
+        };
+        let mut range = self.map.range_mut(a.min(b)..=a.max(b));
+        let (low_key, low) = range.next()?;
+        let (high_key, high) = range.next_back()?;
+        if *low_key != a.min(b) || *high_key != a.max(b) {
+            return None;
+        }
+        Some(if swapped { [high, low] } else { [low, high] })
+    }
+
+    /// Returns the chunk at `pos` and the chunks surrounding it, in the order of
+    /// [`Direction::ALL`], or [`None`] if there is no chunk at `pos`
+    #[cfg(not(feature = "deterministic"))]
+    #[allow(clippy::type_complexity)]
+    pub fn get_with_neighbors(
+        &mut self,
+        pos: IVec2,
+    ) -> Option<(&mut Chunk<T>, [Option<&Chunk<T>>; 8])> {
+        fn get_many<'a, const N: usize, V>(map: &'a mut Map<V>, keys: &[IVec2]) -> Vec<&'a mut V> {
+            let keys: [&IVec2; N] = std::array::from_fn(|i| &keys[i]);
+            map.get_many_mut(keys).map_or_else(Vec::new, Vec::from)
+        }
+
+        if !self.map.contains_key(&pos) {
+            return None;
+        }
+        let keys: Vec<IVec2> = iter::once(pos)
+            .chain(Direction::ALL.map(|direction| pos + direction.offset()))
+            .filter(|key| self.map.contains_key(key))
+            .collect();
+        let mut chunks = match keys.len() {
+            1 => get_many::<1, _>(&mut self.map, &keys),
+            2 => get_many::<2, _>(&mut self.map, &keys),
+            3 => get_many::<3, _>(&mut self.map, &keys),
+            4 => get_many::<4, _>(&mut self.map, &keys),
+            5 => get_many::<5, _>(&mut self.map, &keys),
+            6 => get_many::<6, _>(&mut self.map, &keys),
+            7 => get_many::<7, _>(&mut self.map, &keys),
+            8 => get_many::<8, _>(&mut self.map, &keys),
+            9 => get_many::<9, _>(&mut self.map, &keys),
+            _ => return None,
+        }
+        .into_iter();
+        let center = chunks.next()?;
+        let mut neighbors = [None; 8];
+        for (key, chunk) in keys[1..].iter().zip(chunks) {
+            neighbors[neighbor_index(pos, *key)] = Some(&*chunk);
+        }
+        Some((center, neighbors))
+    }
+
+    /// Returns the chunk at `pos` and the chunks surrounding it, in the order of
+    /// [`Direction::ALL`], or [`None`] if there is no chunk at `pos`
+    #[cfg(feature = "deterministic")]
+    #[allow(clippy::type_complexity)]
+    pub fn get_with_neighbors(
+        &mut self,
+        pos: IVec2,
+    ) -> Option<(&mut Chunk<T>, [Option<&Chunk<T>>; 8])> {
+        // Sorted by y, then by x, so every neighbor is between the bottom left and top right
+        let range = key(pos - IVec2::ONE)..=key(pos + IVec2::ONE);
+        let mut center = None;
+        let mut neighbors = [None; 8];
+        for (key, chunk) in self.map.range_mut(range) {
+            let offset = key.0 - pos;
+            if offset == IVec2::ZERO {
+                center = Some(chunk);
+            } else if offset.x.abs() <= 1 {
+                neighbors[neighbor_index(pos, key.0)] = Some(&*chunk);
+            }
+        }
+        center.map(|center| (center, neighbors))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&IVec2, &Chunk<T>)> {
+        self.map.iter().map(|(key, chunk)| (pos(key), chunk))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&IVec2, &mut Chunk<T>)> {
+        self.map.iter_mut().map(|(key, chunk)| (pos(key), chunk))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &IVec2> {
+        self.map.keys().map(pos)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Chunk<T>> {
+        self.map.values()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Chunk<T>> {
+        self.map.values_mut()
+    }
+
+    pub fn into_chunks(self) -> impl Iterator<Item = (IVec2, Chunk<T>)> {
+        self.map.into_iter().map(|(key, chunk)| (*pos(&key), chunk))
+    }
+
+    #[cfg(not(feature = "deterministic"))]
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    #[cfg(feature = "deterministic")]
+    pub fn capacity(&self) -> usize {
+        self.map.len()
+    }
+
+    #[cfg(not(feature = "deterministic"))]
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional)
+    }
+
+    #[cfg(feature = "deterministic")]
+    pub fn reserve(&mut self, _additional: usize) {}
+}
+
+/// The index in [`Direction::ALL`] of the direction from `pos` to `neighbor`
+///
+/// `neighbor` must be next to `pos`
+fn neighbor_index(pos: IVec2, neighbor: IVec2) -> usize {
+    Direction::ALL
+        .iter()
+        .position(|direction| pos + direction.offset() == neighbor)
+        .unwrap()
+}
+
+impl<T: Tile> Default for ChunkMap<T> {
+    fn default() -> Self {
+        ChunkMap {
+            map: Map::default(),
+        }
+    }
+}
+
+impl<T: Tile> FromIterator<(IVec2, Chunk<T>)> for ChunkMap<T> {
+    fn from_iter<I: IntoIterator<Item = (IVec2, Chunk<T>)>>(iter: I) -> Self {
+        ChunkMap {
+            map: iter
+                .into_iter()
+                .map(|(pos, chunk)| (key(pos), chunk))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::TestTile, tilemap::ChunkPos};
+
+    /// A map with a chunk at each of `positions`, holding a tile with it's index
+    fn map_with_chunks(positions: &[IVec2]) -> ChunkMap<TestTile> {
+        positions
+            .iter()
+            .enumerate()
+            .map(|(i, pos)| {
+                let mut chunk = Chunk::default();
+                chunk.set_raw(ChunkPos::ZERO, Some(TestTile(i as u8)));
+                (*pos, chunk)
+            })
+            .collect()
+    }
+
+    fn id(chunk: &Chunk<TestTile>) -> u8 {
+        chunk[ChunkPos::ZERO].unwrap().0
+    }
+
+    #[test]
+    fn neighbors_follow_direction_order() {
+        let center = IVec2::new(3, -2);
+        let mut positions = vec![center];
+        positions.extend(Direction::ALL.map(|direction| center + direction.offset()));
+        positions.push(center + IVec2::new(2, 0));
+        let mut map = map_with_chunks(&positions);
+
+        let (chunk, neighbors) = map.get_with_neighbors(center).unwrap();
+        assert_eq!(id(chunk), 0);
+        let ids = neighbors.map(|neighbor| neighbor.map(id));
+        assert_eq!(ids, [1, 2, 3, 4, 5, 6, 7, 8].map(Some));
+    }
+
+    #[test]
+    fn missing_neighbors_are_none() {
+        let mut map = map_with_chunks(&[IVec2::ZERO, IVec2::X, -IVec2::Y]);
+        let (chunk, neighbors) = map.get_with_neighbors(IVec2::ZERO).unwrap();
+        assert_eq!(id(chunk), 0);
+        let ids = neighbors.map(|neighbor| neighbor.map(id));
+        assert_eq!(ids, [None, None, Some(1), None, Some(2), None, None, None]);
+    }
+
+    #[test]
+    fn missing_center_is_none() {
+        let mut map = map_with_chunks(&[IVec2::X, IVec2::Y]);
+        assert!(map.get_with_neighbors(IVec2::ZERO).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+
+/// One of the 8 directions from a tile to it's neighbors on a square grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Up
+    North,
+    /// Up and right
+    NorthEast,
+    /// Right
+    East,
+    /// Down and right
+    SouthEast,
+    /// Down
+    South,
+    /// Down and left
+    SouthWest,
+    /// Left
+    West,
+    /// Up and left
+    NorthWest,
+}
+
+impl Direction {
+    /// Every direction, clockwise starting from north
+    pub const ALL: [Direction; 8] = [
+        Direction::North,
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::South,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    /// North, east, south, and west; the directions to the neighbors sharing an edge with
+    /// a tile, clockwise starting from north
+    pub const CARDINAL: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    /// The offset from a tile to it's neighbor in this direction
+    #[must_use]
+    pub fn offset(self) -> IVec2 {
+        match self {
+            Direction::North => IVec2::new(0, 1),
+            Direction::NorthEast => IVec2::new(1, 1),
+            Direction::East => IVec2::new(1, 0),
+            Direction::SouthEast => IVec2::new(1, -1),
+            Direction::South => IVec2::new(0, -1),
+            Direction::SouthWest => IVec2::new(-1, -1),
+            Direction::West => IVec2::new(-1, 0),
+            Direction::NorthWest => IVec2::new(-1, 1),
+        }
+    }
+
+    /// Returns the direction with the given [offset](Self::offset()), or [`None`] if
+    /// `offset` isn't the offset to a neighbor
+    #[must_use]
+    pub fn from_offset(offset: IVec2) -> Option<Self> {
+        Direction::ALL
+            .into_iter()
+            .find(|direction| direction.offset() == offset)
+    }
+
+    /// The direction pointing the other way
+    #[must_use]
+    pub fn opposite(self) -> Self {
+        Direction::ALL[(self as usize + 4) % 8]
+    }
+
+    /// This rotated clockwise by 45 degrees
+    #[must_use]
+    pub fn rotate_cw(self) -> Self {
+        Direction::ALL[(self as usize + 1) % 8]
+    }
+
+    /// This rotated counterclockwise by 45 degrees
+    #[must_use]
+    pub fn rotate_ccw(self) -> Self {
+        Direction::ALL[(self as usize + 7) % 8]
+    }
+
+    /// Returns `true` if this is north, east, south, or west
+    #[must_use]
+    pub fn is_cardinal(self) -> bool {
+        matches!(
+            self,
+            Direction::North | Direction::East | Direction::South | Direction::West
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_round_trip() {
+        for direction in Direction::ALL {
+            assert_eq!(Direction::from_offset(direction.offset()), Some(direction));
+            assert_eq!(direction.opposite().offset(), -direction.offset());
+            assert_eq!(direction.rotate_cw().rotate_ccw(), direction);
+        }
+        assert_eq!(Direction::from_offset(IVec2::ZERO), None);
+        assert_eq!(Direction::from_offset(IVec2::new(2, 0)), None);
+    }
+
+    #[test]
+    fn cardinal_matches_is_cardinal() {
+        let cardinal: Vec<_> = Direction::ALL
+            .into_iter()
+            .filter(|direction| direction.is_cardinal())
+            .collect();
+        assert_eq!(cardinal, Direction::CARDINAL);
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::{math::Vec2Swizzles, prelude::*};
+
+use super::TilemapPos;
+
+/// Half of the square root of 3; the distance between rows (or columns) of hexagons
+/// 1 unit wide (or tall)
+const HEX_SPACING: f32 = 0.866_025_4;
+
+/// How tiles in a [`Tilemap`](super::Tilemap) are arranged
+///
+/// Regardless of layout, tiles are stored in square chunks.  Each tile is 1 unit across,
+/// and the center of a tile is 0.5 units right of and above it's offset
+/// (see [`tile_offset()`](Self::tile_offset()))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TileLayout {
+    /// Square tiles in a grid
+    #[default]
+    Square,
+    /// Hexagons with a point at the top, 1 unit wide, in rows
+    ///
+    /// Uses "odd-r" offset coordinates: odd rows are shifted right by half a tile
+    HexPointyTop,
+    /// Hexagons with a flat top, 1 unit tall, in columns
+    ///
+    /// Uses "odd-q" offset coordinates: odd columns are shifted up by half a tile
+    HexFlatTop,
+}
+
+impl TileLayout {
+    /// Returns the offset of the tile at `pos` from the origin of the tilemap
+    ///
+    /// The center of the tile is at this offset + 0.5 on both axes
+    #[must_use]
+    pub fn tile_offset(self, pos: IVec2) -> Vec2 {
+        match self {
+            TileLayout::Square => pos.as_vec2(),
+            TileLayout::HexPointyTop => Vec2::new(
+                pos.x as f32 + (pos.y & 1) as f32 * 0.5,
+                pos.y as f32 * HEX_SPACING,
+            ),
+            TileLayout::HexFlatTop => Vec2::new(
+                pos.x as f32 * HEX_SPACING,
+                pos.y as f32 + (pos.x & 1) as f32 * 0.5,
+            ),
+        }
+    }
+
+    /// Returns the position of the tile covering the point `world`, relative to the
+    /// origin of the tilemap
+    #[must_use]
+    pub fn world_to_tile(self, world: Vec2) -> IVec2 {
+        match self {
+            TileLayout::Square => world.floor().as_ivec2(),
+            TileLayout::HexPointyTop => pointy_world_to_tile(world - Vec2::splat(0.5)),
+            TileLayout::HexFlatTop => {
+                let pos = pointy_world_to_tile((world - Vec2::splat(0.5)).yx());
+                IVec2::new(pos.y, pos.x)
+            }
+        }
+    }
+
+    /// Returns the offsets from `pos` to each of it's neighbors
+    ///
+    /// Square tiles have 4 neighbors (north, east, south, west), and hexagons have 6
+    /// (clockwise, starting from north-east for pointy top and north for flat top).
+    /// For hexagons the offsets depend on whether `pos` is in an odd row or column
+    #[must_use]
+    pub fn neighbor_offsets(self, pos: IVec2) -> &'static [IVec2] {
+        const SQUARE: [IVec2; 4] = [
+            IVec2::new(0, 1),
+            IVec2::new(1, 0),
+            IVec2::new(0, -1),
+            IVec2::new(-1, 0),
+        ];
+        const POINTY_EVEN: [IVec2; 6] = [
+            IVec2::new(0, 1),
+            IVec2::new(1, 0),
+            IVec2::new(0, -1),
+            IVec2::new(-1, -1),
+            IVec2::new(-1, 0),
+            IVec2::new(-1, 1),
+        ];
+        const POINTY_ODD: [IVec2; 6] = [
+            IVec2::new(1, 1),
+            IVec2::new(1, 0),
+            IVec2::new(1, -1),
+            IVec2::new(0, -1),
+            IVec2::new(-1, 0),
+            IVec2::new(0, 1),
+        ];
+        const FLAT_EVEN: [IVec2; 6] = [
+            IVec2::new(0, 1),
+            IVec2::new(1, 0),
+            IVec2::new(1, -1),
+            IVec2::new(0, -1),
+            IVec2::new(-1, -1),
+            IVec2::new(-1, 0),
+        ];
+        const FLAT_ODD: [IVec2; 6] = [
+            IVec2::new(0, 1),
+            IVec2::new(1, 1),
+            IVec2::new(1, 0),
+            IVec2::new(0, -1),
+            IVec2::new(-1, 0),
+            IVec2::new(-1, 1),
+        ];
+        match self {
+            TileLayout::Square => &SQUARE,
+            TileLayout::HexPointyTop if pos.y & 1 == 0 => &POINTY_EVEN,
+            TileLayout::HexPointyTop => &POINTY_ODD,
+            TileLayout::HexFlatTop if pos.x & 1 == 0 => &FLAT_EVEN,
+            TileLayout::HexFlatTop => &FLAT_ODD,
+        }
+    }
+
+    /// Returns an iterator over the positions of the neighbors of `pos`
+    ///
+    /// In the order described by [`neighbor_offsets()`](Self::neighbor_offsets())
+    pub fn neighbors(self, pos: TilemapPos) -> impl Iterator<Item = TilemapPos> {
+        let pos = IVec2::from(pos);
+        self.neighbor_offsets(pos)
+            .iter()
+            .map(move |offset| TilemapPos::from(pos + *offset))
+    }
+}
+
+/// Converts a point to odd-r offset coordinates, where `point` is relative to the center
+/// of the hexagon at (0, 0)
+fn pointy_world_to_tile(point: Vec2) -> IVec2 {
+    // Fractional axial coordinates of a hexagon 1 unit wide
+    let q = point.x - point.y / (2.0 * HEX_SPACING);
+    let r = point.y / HEX_SPACING;
+    let s = -q - r;
+
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+
+    let (q, r) = (rq as i32, rr as i32);
+    IVec2::new(q + (r - (r & 1)) / 2, r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::Direction;
+
+    #[test]
+    fn hex_neighbors_are_adjacent_and_mutual() {
+        for layout in [TileLayout::HexPointyTop, TileLayout::HexFlatTop] {
+            for pos in [IVec2::ZERO, IVec2::new(1, 1), IVec2::new(-3, 2)] {
+                let offsets = layout.neighbor_offsets(pos);
+                assert_eq!(offsets.len(), 6);
+                for offset in offsets {
+                    // Mesh generation only passes the 8 surrounding tiles
+                    assert!(Direction::from_offset(*offset).is_some());
+                    let distance = layout.tile_offset(pos + *offset) - layout.tile_offset(pos);
+                    assert!((distance.length() - 1.0).abs() < 1e-4, "{layout:?} {pos}");
+                    assert!(layout.neighbor_offsets(pos + *offset).contains(&-*offset));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn world_to_tile_finds_tile_centers() {
+        for layout in [
+            TileLayout::Square,
+            TileLayout::HexPointyTop,
+            TileLayout::HexFlatTop,
+        ] {
+            for x in -3..3 {
+                for y in -3..3 {
+                    let pos = IVec2::new(x, y);
+                    let center = layout.tile_offset(pos) + Vec2::splat(0.5);
+                    assert_eq!(layout.world_to_tile(center), pos, "{layout:?}");
+                }
+            }
+        }
+    }
+}
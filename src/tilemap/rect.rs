@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+
+use crate::CHUNK_SIZE;
+
+/// An axis-aligned rectangle of tile positions
+///
+/// Both [`min`](Self::min) and [`max`](Self::max) are inclusive, so a rectangle
+/// where `min == max` contains a single tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IRect {
+    /// The bottom left corner of the rectangle
+    pub min: IVec2,
+    /// The top right corner of the rectangle
+    pub max: IVec2,
+}
+
+impl IRect {
+    /// Creates a rectangle with the given corners
+    ///
+    /// If `min` is greater than `max` on either axis the rectangle is empty
+    #[must_use]
+    #[inline]
+    pub fn new(min: IVec2, max: IVec2) -> Self {
+        IRect { min, max }
+    }
+
+    /// Creates the smallest rectangle containing both `a` and `b`
+    #[must_use]
+    #[inline]
+    pub fn from_corners(a: IVec2, b: IVec2) -> Self {
+        IRect {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// The rectangle covered by the chunk at `chunk`
+    #[must_use]
+    pub fn from_chunk(chunk: IVec2) -> Self {
+        let min = chunk * CHUNK_SIZE as i32;
+        IRect {
+            min,
+            max: min + IVec2::splat(CHUNK_SIZE as i32 - 1),
+        }
+    }
+
+    /// Returns `true` if this contains no positions
+    #[must_use]
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y
+    }
+
+    /// The width and height of this in tiles
+    #[must_use]
+    pub fn size(self) -> IVec2 {
+        (self.max - self.min + IVec2::ONE).max(IVec2::ZERO)
+    }
+
+    /// Returns `true` if `pos` is in this
+    #[must_use]
+    #[inline]
+    pub fn contains(self, pos: IVec2) -> bool {
+        pos.cmpge(self.min).all() && pos.cmple(self.max).all()
+    }
+
+    /// The positions in both this and `other`
+    #[must_use]
+    pub fn intersect(self, other: Self) -> Self {
+        IRect {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    /// The positions of every chunk this overlaps
+    #[must_use]
+    pub fn chunks(self) -> Self {
+        let chunk_size = CHUNK_SIZE as i32;
+        IRect {
+            min: IVec2::new(
+                self.min.x.div_euclid(chunk_size),
+                self.min.y.div_euclid(chunk_size),
+            ),
+            max: IVec2::new(
+                self.max.x.div_euclid(chunk_size),
+                self.max.y.div_euclid(chunk_size),
+            ),
+        }
+    }
+
+    /// Returns an iterator over all positions in this
+    ///
+    /// Iterates in row-major order
+    pub fn iter_positions(self) -> impl Iterator<Item = IVec2> {
+        (self.min.y..=self.max.y)
+            .flat_map(move |y| (self.min.x..=self.max.x).map(move |x| IVec2::new(x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_round_towards_negative_infinity() {
+        let rect = IRect::new(IVec2::new(-1, 0), IVec2::new(32, 31));
+        assert_eq!(
+            rect.chunks(),
+            IRect::new(IVec2::new(-1, 0), IVec2::new(1, 0))
+        );
+        assert_eq!(
+            IRect::from_chunk(IVec2::new(-1, 2)),
+            IRect::new(IVec2::new(-32, 64), IVec2::new(-1, 95))
+        );
+    }
+
+    #[test]
+    fn empty_rects() {
+        let a = IRect::new(IVec2::ZERO, IVec2::ONE);
+        let b = IRect::new(IVec2::splat(2), IVec2::splat(3));
+        let empty = a.intersect(b);
+        assert!(empty.is_empty());
+        assert_eq!(empty.size(), IVec2::ZERO);
+        assert_eq!(empty.iter_positions().count(), 0);
+        assert_eq!(IRect::from_corners(b.max, a.min), IRect::new(a.min, b.max));
+    }
+
+    #[test]
+    fn iterates_row_major() {
+        let rect = IRect::new(IVec2::ZERO, IVec2::new(1, 1));
+        let positions: Vec<_> = rect.iter_positions().collect();
+        assert_eq!(positions, [IVec2::ZERO, IVec2::X, IVec2::Y, IVec2::ONE]);
+        assert!(positions.iter().all(|pos| rect.contains(*pos)));
+    }
+}
@@ -0,0 +1,248 @@
+//! Procedural generation of tilemap regions with wave function collapse
+
+use std::fmt;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    tile::Tile,
+    tilemap::{Direction, IRect, Tilemap, TilemapPos},
+};
+
+/// The tiles wave function collapse can place, and which of them may be next to each other
+///
+/// Tiles are referred to by their index in [`tiles()`](Self::tiles())
+#[derive(Debug, Clone)]
+pub struct AdjacencyRules<T: Tile> {
+    tiles: Vec<T>,
+    /// `allowed[direction][a][b]` is `true` if `b` may be placed in `direction` from `a`,
+    /// with directions indexed in the order of [`Direction::CARDINAL`]
+    allowed: [Vec<Vec<bool>>; 4],
+}
+
+impl<T: Tile> AdjacencyRules<T> {
+    /// Creates rules for `tiles` where no tiles may be next to each other
+    #[must_use]
+    pub fn new(tiles: Vec<T>) -> Self {
+        let len = tiles.len();
+        AdjacencyRules {
+            tiles,
+            allowed: [(); 4].map(|_| vec![vec![false; len]; len]),
+        }
+    }
+
+    /// Creates rules for `tiles` where every tile may be next to every other tile
+    #[must_use]
+    pub fn all_adjacent(tiles: Vec<T>) -> Self {
+        let len = tiles.len();
+        AdjacencyRules {
+            tiles,
+            allowed: [(); 4].map(|_| vec![vec![true; len]; len]),
+        }
+    }
+
+    /// The tiles these rules can place
+    #[must_use]
+    pub fn tiles(&self) -> &[T] {
+        &self.tiles
+    }
+
+    /// Allows the tile at index `right` to be placed directly right of the tile at
+    /// index `left`
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds
+    pub fn allow_horizontal(&mut self, left: usize, right: usize) {
+        self.allowed[1][left][right] = true;
+        self.allowed[3][right][left] = true;
+    }
+
+    /// Allows the tile at index `above` to be placed directly above the tile at
+    /// index `below`
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds
+    pub fn allow_vertical(&mut self, below: usize, above: usize) {
+        self.allowed[0][below][above] = true;
+        self.allowed[2][above][below] = true;
+    }
+}
+
+/// The reasons wave function collapse can fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WfcError {
+    /// The rules have no tiles
+    NoTiles,
+    /// No tile can be placed at this position without breaking the rules
+    Contradiction(TilemapPos),
+}
+
+impl fmt::Display for WfcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WfcError::NoTiles => write!(f, "adjacency rules have no tiles"),
+            WfcError::Contradiction(pos) => write!(f, "no tile can be placed at {pos}"),
+        }
+    }
+}
+
+impl std::error::Error for WfcError {}
+
+impl<T: Tile, M> Tilemap<T, M> {
+    /// Fills `bounds` using wave function collapse, so every pair of neighboring tiles
+    /// in `bounds` follows `rules`
+    ///
+    /// Tiles already in `bounds` are overwritten, and tiles outside of `bounds` are ignored.
+    /// If generation fails this is left unchanged.  Tells every chunk written to to
+    /// regenerate it's mesh the next time it's displayed
+    pub fn generate_wfc(
+        &mut self,
+        bounds: IRect,
+        rules: AdjacencyRules<T>,
+        rng: &mut impl Rng,
+    ) -> Result<(), WfcError> {
+        if bounds.is_empty() {
+            return Ok(());
+        }
+        if rules.tiles.is_empty() {
+            return Err(WfcError::NoTiles);
+        }
+
+        let size = bounds.size();
+        let mut cells = vec![vec![true; rules.tiles.len()]; (size.x * size.y) as usize];
+        // Tiles that can't be next to any tile must be removed before collapsing anything
+        let everywhere = IRect::new(IVec2::ZERO, size - IVec2::ONE).iter_positions();
+        propagate(&mut cells, everywhere.collect(), &rules, bounds)?;
+
+        loop {
+            // Collapse the undecided cell with the fewest options, breaking ties randomly
+            let mut fewest = usize::MAX;
+            let mut candidates = Vec::new();
+            for (i, cell) in cells.iter().enumerate() {
+                let count = cell.iter().filter(|option| **option).count();
+                if count > 1 {
+                    if count < fewest {
+                        fewest = count;
+                        candidates.clear();
+                    }
+                    if count == fewest {
+                        candidates.push(i);
+                    }
+                }
+            }
+            if candidates.is_empty() {
+                break;
+            }
+            let i = candidates[rng.gen_range(0..candidates.len())];
+            let options: Vec<usize> = (0..rules.tiles.len()).filter(|o| cells[i][*o]).collect();
+            let chosen = options[rng.gen_range(0..options.len())];
+            cells[i].iter_mut().for_each(|option| *option = false);
+            cells[i][chosen] = true;
+
+            propagate(
+                &mut cells,
+                vec![IVec2::new(i as i32 % size.x, i as i32 / size.x)],
+                &rules,
+                bounds,
+            )?;
+        }
+
+        for (pos, cell) in bounds.iter_positions().zip(cells) {
+            let option = cell.iter().position(|option| *option).unwrap();
+            self.set(TilemapPos::from(pos), rules.tiles[option].clone());
+        }
+        Ok(())
+    }
+}
+
+/// Removes options from the neighbors of the cells in `stack` until every cell is
+/// consistent with it's neighbors
+///
+/// `cells` are the options for each position in `bounds`, row-major
+fn propagate<T: Tile>(
+    cells: &mut [Vec<bool>],
+    mut stack: Vec<IVec2>,
+    rules: &AdjacencyRules<T>,
+    bounds: IRect,
+) -> Result<(), WfcError> {
+    let size = bounds.size();
+    let index = |pos: IVec2| (pos.x + pos.y * size.x) as usize;
+    while let Some(pos) = stack.pop() {
+        for (i, direction) in Direction::CARDINAL.into_iter().enumerate() {
+            let neighbor = pos + direction.offset();
+            if neighbor.cmplt(IVec2::ZERO).any() || neighbor.cmpge(size).any() {
+                continue;
+            }
+            let mut changed = false;
+            for option in 0..rules.tiles.len() {
+                if cells[index(neighbor)][option]
+                    && !(0..rules.tiles.len())
+                        .any(|from| cells[index(pos)][from] && rules.allowed[i][from][option])
+                {
+                    cells[index(neighbor)][option] = false;
+                    changed = true;
+                }
+            }
+            if !cells[index(neighbor)].contains(&true) {
+                return Err(WfcError::Contradiction(TilemapPos::from(
+                    bounds.min + neighbor,
+                )));
+            }
+            if changed {
+                stack.push(neighbor);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn checkerboard_follows_rules() {
+        let mut rules = AdjacencyRules::new(vec![TestTile(0), TestTile(1)]);
+        rules.allow_horizontal(0, 1);
+        rules.allow_horizontal(1, 0);
+        rules.allow_vertical(0, 1);
+        rules.allow_vertical(1, 0);
+        let bounds = IRect::new(IVec2::new(-3, -2), IVec2::new(4, 5));
+
+        let mut tilemap = test_tilemap::<TestTile>();
+        tilemap
+            .generate_wfc(bounds, rules, &mut StdRng::seed_from_u64(7))
+            .unwrap();
+        for pos in bounds.iter_positions() {
+            let tile = tilemap.get(TilemapPos::from(pos)).unwrap();
+            for direction in Direction::CARDINAL {
+                let neighbor = pos + direction.offset();
+                if bounds.contains(neighbor) {
+                    assert_ne!(tilemap.get(TilemapPos::from(neighbor)), Some(tile));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn failures_leave_tilemap_unchanged() {
+        let bounds = IRect::new(IVec2::ZERO, IVec2::ONE);
+        let mut tilemap = test_tilemap::<TestTile>();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            tilemap.generate_wfc(bounds, AdjacencyRules::new(Vec::new()), &mut rng),
+            Err(WfcError::NoTiles)
+        );
+        assert!(matches!(
+            tilemap.generate_wfc(bounds, AdjacencyRules::new(vec![TestTile(0)]), &mut rng),
+            Err(WfcError::Contradiction(_))
+        ));
+        assert_eq!(tilemap.chunk_positions().count(), 0);
+    }
+}